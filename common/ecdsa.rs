@@ -0,0 +1,30 @@
+//! Recovery of the signer of the approvals signed off-chain for `permit`.
+
+use ink_env::{
+    hash::{Blake2x256, HashOutput},
+    AccountId,
+};
+
+/// Return the account of an ECDSA public key, the Blake2x256 hash of its compressed form as for Substrate accounts.
+pub fn public_key_account(public_key: &[u8; 33]) -> AccountId {
+    let mut output = <Blake2x256 as HashOutput>::Type::default();
+    ink_env::hash_bytes::<Blake2x256>(public_key, &mut output);
+    AccountId::from(output)
+}
+
+/// Return the account which signed `message_hash`, None if the signature is malformed or recovers no key.
+///
+/// `signature` is r and s followed by the recovery id, which can be 0/1 or 27/28.
+pub fn recover_signer(message_hash: &[u8; 32], signature: &[u8]) -> Option<AccountId> {
+    if signature.len() != 65 {
+        return None
+    }
+    let mut rsv = [0u8; 65];
+    rsv.copy_from_slice(signature);
+    if rsv[64] > 26 {
+        rsv[64] -= 27;
+    }
+    let mut public_key = [0u8; 33];
+    ink_env::ecdsa_recover(&rsv, message_hash, &mut public_key).ok()?;
+    Some(public_key_account(&public_key))
+}
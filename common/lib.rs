@@ -0,0 +1,8 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Code shared by the Zenlink contracts. It is a plain library, not a contract.
+
+pub mod ecdsa;
+pub mod receiver;
+pub mod storage;
+pub mod token;
@@ -0,0 +1,32 @@
+//! The hook a contract implements to accept tokens sent with the erc20 `transfer_and_call`.
+
+use ink_env::{
+    call::{build_call, utils::ReturnType, ExecutionInput, Selector},
+    AccountId, Balance, DefaultEnvironment,
+};
+use ink_prelude::vec::Vec;
+
+/// Selector of the `on_token_received(from: AccountId, value: Balance, data: Vec<u8>) -> bool` message.
+///
+/// A receiver declares the message with `#[ink(message, payable, selector = "0xe1936155")]` and returns true to
+/// accept the tokens, the token reverts the whole transfer otherwise.
+pub const ON_TOKEN_RECEIVED_SELECTOR: [u8; 4] = [0xe1, 0x93, 0x61, 0x55];
+
+/// Call `on_token_received(from, value, data)` on `receiver` with `transferred_value` Dot and return its answer.
+///
+/// Panics if the call fails, so the transfer is reverted as if the receiver refused it.
+pub fn notify_token_received(receiver: AccountId, from: AccountId, value: Balance, data: Vec<u8>, transferred_value: Balance) -> bool {
+    build_call::<DefaultEnvironment>()
+        .callee(receiver)
+        .gas_limit(0)
+        .transferred_value(transferred_value)
+        .exec_input(
+            ExecutionInput::new(Selector::new(ON_TOKEN_RECEIVED_SELECTOR))
+                .push_arg(from)
+                .push_arg(value)
+                .push_arg(data),
+        )
+        .returns::<ReturnType<bool>>()
+        .fire()
+        .expect("on_token_received failed")
+}
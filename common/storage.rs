@@ -0,0 +1,21 @@
+//! Keeps the storage of a contract consistent around a call to another contract which may call back into it.
+//!
+//! ink! writes the storage struct of a contract only when a message returns, so a call back would read the storage as
+//! it was before the message. The caller writes its storage with `flush` before such a call, and reads it again with
+//! `reload` once the call returned.
+
+use ink_primitives::Key;
+use ink_storage::traits::{pull_spread_root, push_spread_root, SpreadLayout};
+
+/// Key of the storage struct of an ink! contract.
+const ROOT_KEY: [u8; 32] = [0x00; 32];
+
+/// Write the storage struct of the contract.
+pub fn flush<T: SpreadLayout>(storage: &T) {
+    push_spread_root::<T>(storage, &Key::from(ROOT_KEY));
+}
+
+/// Read the storage struct of the contract again, the call may have changed it.
+pub fn reload<T: SpreadLayout>(storage: &mut T) {
+    *storage = pull_spread_root::<T>(&Key::from(ROOT_KEY));
+}
@@ -0,0 +1,86 @@
+//! Calls to erc20 tokens and to the liquidity of the pools, and the error of a refused transfer.
+//!
+//! The liquidity of every pool has the erc20 `transfer` and `transfer_from` selectors. The pools other than the
+//! Exchange also share the erc20 `balance_of` selector, the Exchange pins its own one, so it is read with `lp_balance_of`.
+
+use ink_env::{
+    call::{build_call, utils::ReturnType, ExecutionInput, Selector},
+    AccountId, Balance, DefaultEnvironment,
+};
+
+/// Selector of the `transfer(to: AccountId, value: Balance) -> bool` message.
+pub const TRANSFER_SELECTOR: [u8; 4] = [0xfa, 0xe3, 0xa0, 0x9d];
+/// Selector of the `transfer_from(from: AccountId, to: AccountId, value: Balance) -> bool` message.
+pub const TRANSFER_FROM_SELECTOR: [u8; 4] = [0xfc, 0xfb, 0x2c, 0xcd];
+/// Selector of the `balance_of(owner: AccountId) -> Balance` message.
+pub const BALANCE_OF_SELECTOR: [u8; 4] = [0x56, 0xe9, 0x29, 0xb2];
+/// Selector of the `balance_of(owner: AccountId) -> Balance` message of the Exchange liquidity.
+pub const LP_BALANCE_OF_SELECTOR: [u8; 4] = [0x85, 0x9b, 0xd6, 0xd2];
+
+/// Errors the contracts moving erc20 tokens return to the caller.
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum Error {
+    /// A token answered false to `transfer` or `transfer_from`, e.g. the token is paused, an account is frozen or the
+    /// allowance is too low. The contract reverted its own changes.
+    TokenTransferFailed,
+}
+
+/// The result type of the contracts moving erc20 tokens.
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Transfer `value` tokens of the calling contract to `to`.
+pub fn transfer(token: AccountId, to: AccountId, value: Balance, gas_limit: u64) -> Result<()> {
+    let transferred = build_call::<DefaultEnvironment>()
+        .callee(token)
+        .gas_limit(gas_limit)
+        .transferred_value(0)
+        .exec_input(
+            ExecutionInput::new(Selector::new(TRANSFER_SELECTOR))
+                .push_arg(&to)
+                .push_arg(value),
+        )
+        .returns::<ReturnType<bool>>()
+        .fire()
+        .unwrap();
+    if transferred { Ok(()) } else { Err(Error::TokenTransferFailed) }
+}
+
+/// Transfer `value` tokens from `from` to `to` with the allowance of the calling contract.
+pub fn transfer_from(token: AccountId, from: AccountId, to: AccountId, value: Balance, gas_limit: u64) -> Result<()> {
+    let transferred = build_call::<DefaultEnvironment>()
+        .callee(token)
+        .gas_limit(gas_limit)
+        .transferred_value(0)
+        .exec_input(
+            ExecutionInput::new(Selector::new(TRANSFER_FROM_SELECTOR))
+                .push_arg(&from)
+                .push_arg(&to)
+                .push_arg(value),
+        )
+        .returns::<ReturnType<bool>>()
+        .fire()
+        .unwrap();
+    if transferred { Ok(()) } else { Err(Error::TokenTransferFailed) }
+}
+
+/// Return the token balance of `owner`, or its liquidity in a pool other than the Exchange.
+pub fn balance_of(token: AccountId, owner: AccountId, gas_limit: u64) -> Balance {
+    call_balance_of(token, BALANCE_OF_SELECTOR, owner, gas_limit)
+}
+
+/// Return the liquidity of `owner` in an Exchange.
+pub fn lp_balance_of(exchange: AccountId, owner: AccountId, gas_limit: u64) -> Balance {
+    call_balance_of(exchange, LP_BALANCE_OF_SELECTOR, owner, gas_limit)
+}
+
+fn call_balance_of(callee: AccountId, selector: [u8; 4], owner: AccountId, gas_limit: u64) -> Balance {
+    build_call::<DefaultEnvironment>()
+        .callee(callee)
+        .gas_limit(gas_limit)
+        .transferred_value(0)
+        .exec_input(ExecutionInput::new(Selector::new(selector)).push_arg(&owner))
+        .returns::<ReturnType<Balance>>()
+        .fire()
+        .unwrap()
+}
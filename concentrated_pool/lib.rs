@@ -0,0 +1,1163 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::concentrated_pool::ConcentratedPool;
+
+use ink_lang as ink;
+
+#[ink::contract]
+mod concentrated_pool {
+
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::{
+        collections::HashMap as StorageHashMap,
+        traits::{PackedLayout, SpreadLayout},
+    };
+
+    use ink_prelude::vec::Vec;
+
+    use primitive_types::{U256, U512};
+
+    /// Serial number of a liquidity position.
+    pub type PositionId = u128;
+
+    /// Smallest tick, the price `1.0001 ^ MIN_TICK` is about 2^-64.
+    pub const MIN_TICK : i32 = -443636;
+    /// Largest tick, the price `1.0001 ^ MAX_TICK` is about 2^64.
+    pub const MAX_TICK : i32 = 443636;
+    /// Square root price of MIN_TICK, as a Q64.64 fixed point number.
+    pub const MIN_SQRT_RATIO : u128 = 4295048017;
+    /// Square root price of MAX_TICK, as a Q64.64 fixed point number.
+    pub const MAX_SQRT_RATIO : u128 = 79226673515401279992447579062;
+    /// Fees are in hundredths of a basis point, e.g. 3000 is 0.3%.
+    pub const FEE_DENOMINATOR : u32 = 1_000_000;
+
+    /// `2^128 / sqrt(1.0001) ^ (2^i)` rounded down, multiplied together for each bit `i` of a tick.
+    const TICK_RATIOS : [u128; 19] = [
+        0xfffcb933bd6fad37aa2d162d1a594001,
+        0xfff97272373d413259a46990580e2139,
+        0xfff2e50f5f656932ef12357cf3c7fdcb,
+        0xffe5caca7e10e4e61c3624eaa0941ccf,
+        0xffcb9843d60f6159c9db58835c926643,
+        0xff973b41fa98c081472e6896dfb254bf,
+        0xff2ea16466c96a3843ec78b326b52860,
+        0xfe5dee046a99a2a811c461f1969c3052,
+        0xfcbe86c7900a88aedcffc83b479aa3a3,
+        0xf987a7253ac413176f2b074cf7815e53,
+        0xf3392b0822b70005940c7a398e4b70f2,
+        0xe7159475a2c29b7443b29c7fa6e889d8,
+        0xd097f3bdfd2022b8845ad8f792aa5825,
+        0xa9f746462d870fdf8a65dc1f90e061e4,
+        0x70d869a156d2a1b890bb3df62baf32f6,
+        0x31be135f97d08fd981231505542fcfa5,
+        0x9aa508b5b7a84e1c677de54f3e99bc8,
+        0x5d6af8dedb81196699c329225ee604,
+        0x2216e584f5fa1ea926041bedfe97,
+    ];
+
+    /// State of an initialized tick, a tick is initialized while a position starts or ends at it.
+    #[derive(Debug, Default, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub struct TickInfo {
+        //liquidity of the positions starting or ending at this tick
+        pub liquidity_gross : u128,
+        //liquidity added to the active liquidity when the price crosses this tick upwards
+        pub liquidity_net : i128,
+        //fee growth per liquidity on the other side of this tick from the current tick, as Q64.64
+        pub fee_growth_outside0_x64 : u128,
+        pub fee_growth_outside1_x64 : u128,
+    }
+
+    /// Liquidity provided between two ticks, owned by an account.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub struct Position {
+        pub owner : AccountId,
+        pub tick_lower : i32,
+        pub tick_upper : i32,
+        pub liquidity : u128,
+        //fee growth per liquidity inside the range when the fees were last accrued, as Q64.64
+        pub fee_growth_inside0_last_x64 : u128,
+        pub fee_growth_inside1_last_x64 : u128,
+        //fees and burned liquidity which the owner can collect
+        pub tokens_owed0 : Balance,
+        pub tokens_owed1 : Balance,
+    }
+
+    /// Result of a swap computed before the tokens are transferred.
+    struct SwapComputation {
+        amount_in : Balance,
+        amount_out : Balance,
+        sqrt_price_x64 : u128,
+        tick : i32,
+        liquidity : u128,
+        //fee growth of the input token
+        fee_growth_global_x64 : u128,
+        //ticks crossed by the swap, with the fee growth of the input token when they were crossed
+        crossed_ticks : Vec<(i32, u128)>,
+    }
+
+    #[ink(event)]
+    pub struct Initialize {
+        sqrt_price_x64 : u128,
+        tick : i32,
+    }
+
+    #[ink(event)]
+    pub struct Mint {
+        #[ink(topic)]
+        owner : AccountId,
+        #[ink(topic)]
+        position_id : PositionId,
+        tick_lower : i32,
+        tick_upper : i32,
+        liquidity : u128,
+        amount0 : Balance,
+        amount1 : Balance,
+    }
+
+    #[ink(event)]
+    pub struct Burn {
+        #[ink(topic)]
+        owner : AccountId,
+        #[ink(topic)]
+        position_id : PositionId,
+        liquidity : u128,
+        amount0 : Balance,
+        amount1 : Balance,
+    }
+
+    #[ink(event)]
+    pub struct Collect {
+        #[ink(topic)]
+        owner : AccountId,
+        #[ink(topic)]
+        position_id : PositionId,
+        amount0 : Balance,
+        amount1 : Balance,
+    }
+
+    #[ink(event)]
+    pub struct Swap {
+        #[ink(topic)]
+        buyer : AccountId,
+        zero_for_one : bool,
+        amount_in : Balance,
+        amount_out : Balance,
+        sqrt_price_x64 : u128,
+        liquidity : u128,
+        tick : i32,
+    }
+
+    #[ink(event)]
+    pub struct PositionTransfer {
+        #[ink(topic)]
+        from : AccountId,
+        #[ink(topic)]
+        to : AccountId,
+        #[ink(topic)]
+        position_id : PositionId,
+    }
+
+    pub use common::token::{Error, Result};
+
+    /// A pool of two Erc20 tokens where each liquidity position only trades between a lower and an upper price. The price
+    /// is `1.0001 ^ tick` token1 per token0, stored as its square root in Q64.64 fixed point.
+    #[ink(storage)]
+    pub struct ConcentratedPool {
+        token0 : AccountId,
+        token1 : AccountId,
+        //swap fee in hundredths of a basis point
+        fee : u32,
+        //positions start and end at multiples of the tick spacing
+        tick_spacing : i32,
+        max_liquidity_per_tick : u128,
+        //square root of the current price as Q64.64, 0 until initialized
+        sqrt_price_x64 : u128,
+        //greatest tick whose price is lower or equal to the current price
+        tick : i32,
+        //liquidity of the positions whose range contains the current tick
+        liquidity : u128,
+        //fee growth per liquidity since the pool creation as Q64.64, they may overflow
+        fee_growth_global0_x64 : u128,
+        fee_growth_global1_x64 : u128,
+        ticks : StorageHashMap<i32, TickInfo>,
+        //bit i of word w is set if the tick (w * 128 + i) * tick_spacing is initialized, empty words are removed
+        tick_bitmap : StorageHashMap<i32, u128>,
+        positions : StorageHashMap<PositionId, Position>,
+        position_count : PositionId,
+        gas_limit :u64,
+        pool_account_id: AccountId,
+    }
+
+    impl ConcentratedPool {
+
+        /// Constructor of the ConcentratedPool contract
+        ///
+        /// NOTE: The pool can be traded once `initialize` set its price and positions are minted.
+        ///
+        /// #Params
+        ///
+        /// - `token0`: AccountId of the Erc20 token priced in token1.
+        /// - `token1`: AccountId of the other Erc20 token.
+        /// - `fee`: Swap fee in hundredths of a basis point, e.g. 3000 for 0.3%.
+        /// - `tick_spacing`: Positions start and end at multiples of it, e.g. 60 for a 0.3% fee.
+        #[ink(constructor)]
+        pub fn new(token0 : AccountId, token1 : AccountId, fee : u32, tick_spacing : i32) -> Self {
+            assert!(token0 != token1 && token0 != AccountId::default() && token1 != AccountId::default());
+            assert!(fee < FEE_DENOMINATOR);
+            assert!(tick_spacing > 0 && tick_spacing < 16384);
+            let min_tick = MIN_TICK / tick_spacing * tick_spacing;
+            let max_tick = MAX_TICK / tick_spacing * tick_spacing;
+            let num_ticks = ((max_tick - min_tick) / tick_spacing) as u128 + 1;
+            Self{
+                token0,
+                token1,
+                fee,
+                tick_spacing,
+                max_liquidity_per_tick : u128::MAX / num_ticks,
+                sqrt_price_x64 : 0,
+                tick : 0,
+                liquidity : 0,
+                fee_growth_global0_x64 : 0,
+                fee_growth_global1_x64 : 0,
+                ticks : StorageHashMap::new(),
+                tick_bitmap : StorageHashMap::new(),
+                positions : StorageHashMap::new(),
+                position_count : 0,
+                gas_limit : 507085500000,
+                pool_account_id : Self::env().account_id(),
+            }
+        }
+
+        #[ink(constructor)]
+        pub fn default() -> Self {
+            Self::new(AccountId::from([0x01; 32]), AccountId::from([0x02; 32]), 3000, 60)
+        }
+
+        /// Set the initial price of the pool, once.
+        ///
+        /// #Params
+        ///
+        /// - `sqrt_price_x64`: Square root of the price of token0 in token1, as Q64.64.
+        #[ink(message)]
+        pub fn initialize(&mut self, sqrt_price_x64 : u128){
+            assert!(self.sqrt_price_x64 == 0);
+            let tick = Self::tick_at_sqrt_ratio(sqrt_price_x64);
+            self.sqrt_price_x64 = sqrt_price_x64;
+            self.tick = tick;
+
+            self.env().emit_event( Initialize {
+                sqrt_price_x64,
+                tick,
+            });
+        }
+
+        /// Return the tokens traded in this pool, (token0, token1).
+        #[ink(message)]
+        pub fn tokens(&self) -> (AccountId, AccountId){
+            (self.token0, self.token1)
+        }
+
+        /// Return the swap fee in hundredths of a basis point.
+        #[ink(message)]
+        pub fn fee(&self) -> u32{
+            self.fee
+        }
+
+        /// Return the tick spacing of the positions.
+        #[ink(message)]
+        pub fn tick_spacing(&self) -> i32{
+            self.tick_spacing
+        }
+
+        /// Return the square root of the current price as Q64.64, 0 until initialized.
+        #[ink(message)]
+        pub fn sqrt_price_x64(&self) -> u128{
+            self.sqrt_price_x64
+        }
+
+        /// Return the current tick.
+        #[ink(message)]
+        pub fn tick(&self) -> i32{
+            self.tick
+        }
+
+        /// Return the liquidity of the positions in range.
+        #[ink(message)]
+        pub fn liquidity(&self) -> u128{
+            self.liquidity
+        }
+
+        /// Return the fee growth per liquidity of both tokens since the pool creation, as Q64.64.
+        #[ink(message)]
+        pub fn fee_growth_global(&self) -> (u128, u128){
+            (self.fee_growth_global0_x64, self.fee_growth_global1_x64)
+        }
+
+        /// Return the state of a tick, None if no position starts or ends at it.
+        #[ink(message)]
+        pub fn tick_info(&self, tick : i32) -> Option<TickInfo>{
+            self.ticks.get(&tick).cloned()
+        }
+
+        /// Return a word of the tick bitmap. Bit i is set if the tick `(word_position * 128 + i) * tick_spacing` is initialized.
+        #[ink(message)]
+        pub fn tick_bitmap(&self, word_position : i32) -> u128{
+            self.tick_bitmap.get(&word_position).cloned().unwrap_or(0)
+        }
+
+        /// Return a position, None if it does not exist.
+        #[ink(message)]
+        pub fn position(&self, position_id : PositionId) -> Option<Position>{
+            self.positions.get(&position_id).cloned()
+        }
+
+        /// Return the number of positions minted.
+        #[ink(message)]
+        pub fn position_count(&self) -> PositionId{
+            self.position_count
+        }
+
+        /// Return the owner of a position, None if it does not exist.
+        #[ink(message)]
+        pub fn owner_of(&self, position_id : PositionId) -> Option<AccountId>{
+            self.positions.get(&position_id).map(|position| position.owner)
+        }
+
+        /// The position owner transfers the position, its liquidity and the fees owed to another account.
+        ///
+        /// Return false if the caller does not own the position.
+        #[ink(message)]
+        pub fn transfer_position(&mut self, to : AccountId, position_id : PositionId) -> bool{
+            let caller = self.env().caller();
+            let mut position = match self.positions.get(&position_id) {
+                Some(position) if position.owner == caller => position.clone(),
+                _ => return false,
+            };
+            position.owner = to;
+            self.positions.insert(position_id, position);
+
+            self.env().emit_event( PositionTransfer {
+                from : caller,
+                to,
+                position_id,
+            });
+            true
+        }
+
+        /// Return the fees owed to a position including the fees not accrued yet, (token0, token1).
+        #[ink(message)]
+        pub fn fees_owed(&self, position_id : PositionId) -> (Balance, Balance){
+            let position = self.positions.get(&position_id).unwrap();
+            let (fee_growth_inside0_x64, fee_growth_inside1_x64) = self.fee_growth_inside(position.tick_lower, position.tick_upper);
+            (
+                position.tokens_owed0 + Self::fees_earned(fee_growth_inside0_x64.wrapping_sub(position.fee_growth_inside0_last_x64), position.liquidity),
+                position.tokens_owed1 + Self::fees_earned(fee_growth_inside1_x64.wrapping_sub(position.fee_growth_inside1_last_x64), position.liquidity),
+            )
+        }
+
+        /// Mint a new position owned by the caller, providing as much liquidity as the desired amounts allow at the current price.
+        ///
+        /// Return (position id, liquidity, amount of token0, amount of token1).
+        ///
+        /// #Params
+        ///
+        /// - `tick_lower`: Lower tick of the range, a multiple of the tick spacing.
+        /// - `tick_upper`: Upper tick of the range, a multiple of the tick spacing.
+        /// - `amount0_desired`: Maximum amount of token0 deposited.
+        /// - `amount1_desired`: Maximum amount of token1 deposited.
+        /// - `amount0_min`: Minimum amount of token0 deposited.
+        /// - `amount1_min`: Minimum amount of token1 deposited.
+        ///
+        /// NOTE: A range above the current price only takes token0, a range below it only takes token1.
+        #[ink(message)]
+        pub fn mint(&mut self, tick_lower : i32, tick_upper : i32, amount0_desired : Balance, amount1_desired : Balance,
+            amount0_min : Balance, amount1_min : Balance) -> Result<(PositionId, u128, Balance, Balance)>{
+            assert!(self.sqrt_price_x64 != 0);
+            self.check_ticks(tick_lower, tick_upper);
+            let sqrt_price_lower = Self::sqrt_ratio_at_tick(tick_lower);
+            let sqrt_price_upper = Self::sqrt_ratio_at_tick(tick_upper);
+            let liquidity = Self::liquidity_for_amounts(self.sqrt_price_x64, sqrt_price_lower, sqrt_price_upper,
+                amount0_desired, amount1_desired);
+            assert!(liquidity > 0);
+            let (amount0, amount1) = Self::amounts_for_liquidity(self.sqrt_price_x64, sqrt_price_lower, sqrt_price_upper, liquidity, true);
+            assert!(amount0 >= amount0_min && amount1 >= amount1_min);
+            let caller = self.env().caller();
+
+            self.token_transfer_from(self.token0, caller, self.pool_account_id, amount0)?;
+            if let Err(error) = self.token_transfer_from(self.token1, caller, self.pool_account_id, amount1) {
+                self.token_transfer(self.token0, caller, amount0).expect("token transfer failed");
+                return Err(error)
+            }
+
+            self.update_tick(tick_lower, liquidity as i128, false);
+            self.update_tick(tick_upper, liquidity as i128, true);
+            if self.tick >= tick_lower && self.tick < tick_upper {
+                self.liquidity += liquidity;
+            }
+            let (fee_growth_inside0_x64, fee_growth_inside1_x64) = self.fee_growth_inside(tick_lower, tick_upper);
+            let position_id = self.position_count + 1;
+            self.position_count = position_id;
+            self.positions.insert(position_id, Position {
+                owner : caller,
+                tick_lower,
+                tick_upper,
+                liquidity,
+                fee_growth_inside0_last_x64 : fee_growth_inside0_x64,
+                fee_growth_inside1_last_x64 : fee_growth_inside1_x64,
+                tokens_owed0 : 0,
+                tokens_owed1 : 0,
+            });
+
+            self.env().emit_event( PositionTransfer {
+                from : AccountId::default(),
+                to : caller,
+                position_id,
+            });
+            self.env().emit_event( Mint {
+                owner : caller,
+                position_id,
+                tick_lower,
+                tick_upper,
+                liquidity,
+                amount0,
+                amount1,
+            });
+            Ok((position_id, liquidity, amount0, amount1))
+        }
+
+        /// Remove liquidity from a position owned by the caller. The tokens are owed to the position until `collect`.
+        ///
+        /// Return (amount of token0, amount of token1) owed for the liquidity removed.
+        ///
+        /// #Params
+        ///
+        /// - `position_id`: The position.
+        /// - `liquidity`: Liquidity removed.
+        #[ink(message)]
+        pub fn burn(&mut self, position_id : PositionId, liquidity : u128) -> (Balance, Balance){
+            let caller = self.env().caller();
+            let mut position = self.positions.get(&position_id).unwrap().clone();
+            assert!(position.owner == caller);
+            assert!(liquidity > 0 && liquidity <= position.liquidity);
+            self.accrue_fees(&mut position);
+
+            self.update_tick(position.tick_lower, -(liquidity as i128), false);
+            self.update_tick(position.tick_upper, -(liquidity as i128), true);
+            if self.tick >= position.tick_lower && self.tick < position.tick_upper {
+                self.liquidity -= liquidity;
+            }
+            let (amount0, amount1) = Self::amounts_for_liquidity(self.sqrt_price_x64, Self::sqrt_ratio_at_tick(position.tick_lower),
+                Self::sqrt_ratio_at_tick(position.tick_upper), liquidity, false);
+            position.liquidity -= liquidity;
+            position.tokens_owed0 += amount0;
+            position.tokens_owed1 += amount1;
+            self.positions.insert(position_id, position);
+
+            self.env().emit_event( Burn {
+                owner : caller,
+                position_id,
+                liquidity,
+                amount0,
+                amount1,
+            });
+            (amount0, amount1)
+        }
+
+        /// Transfer the fees and the burned liquidity owed to a position owned by the caller.
+        ///
+        /// Return (amount of token0, amount of token1) transferred.
+        ///
+        /// #Params
+        ///
+        /// - `position_id`: The position.
+        /// - `amount0_max`: Maximum amount of token0 transferred.
+        /// - `amount1_max`: Maximum amount of token1 transferred.
+        #[ink(message)]
+        pub fn collect(&mut self, position_id : PositionId, amount0_max : Balance, amount1_max : Balance) -> Result<(Balance, Balance)>{
+            let caller = self.env().caller();
+            let mut position = self.positions.get(&position_id).unwrap().clone();
+            assert!(position.owner == caller);
+            self.accrue_fees(&mut position);
+            let amount0 = position.tokens_owed0.min(amount0_max);
+            let amount1 = position.tokens_owed1.min(amount1_max);
+
+            self.token_transfer(self.token0, caller, amount0)?;
+
+            position.tokens_owed0 -= amount0;
+            position.tokens_owed1 -= amount1;
+            self.positions.insert(position_id, position);
+            self.token_transfer(self.token1, caller, amount1).expect("token transfer failed");
+
+            self.env().emit_event( Collect {
+                owner : caller,
+                position_id,
+                amount0,
+                amount1,
+            });
+            Ok((amount0, amount1))
+        }
+
+        /// Swap an exact amount of one token for the other, crossing as many ticks as needed.
+        ///
+        /// Return The amount of token bought.
+        ///
+        /// #Params
+        ///
+        /// - `zero_for_one`: True to sell token0 for token1, false to sell token1 for token0.
+        /// - `amount_in`: Amount of token sold.
+        /// - `min_amount_out`: Minimum amount of token bought.
+        /// - `sqrt_price_limit_x64`: The swap stops at this square root price, 0 for no limit. Less is sold if it is reached.
+        #[ink(message)]
+        pub fn swap_exact_input(&mut self, zero_for_one : bool, amount_in : Balance, min_amount_out : Balance,
+            sqrt_price_limit_x64 : u128) -> Result<Balance>{
+            let swap = self.compute_swap(zero_for_one, amount_in, true, sqrt_price_limit_x64);
+            assert!(swap.amount_out >= min_amount_out);
+            let amount_out = swap.amount_out;
+            self.settle_swap(zero_for_one, swap)?;
+            Ok(amount_out)
+        }
+
+        /// Swap one token for an exact amount of the other, crossing as many ticks as needed.
+        ///
+        /// Return The amount of token sold.
+        ///
+        /// #Params
+        ///
+        /// - `zero_for_one`: True to sell token0 for token1, false to sell token1 for token0.
+        /// - `amount_out`: Amount of token bought.
+        /// - `max_amount_in`: Maximum amount of token sold.
+        /// - `sqrt_price_limit_x64`: The swap stops at this square root price, 0 for no limit. Less is bought if it is reached.
+        #[ink(message)]
+        pub fn swap_exact_output(&mut self, zero_for_one : bool, amount_out : Balance, max_amount_in : Balance,
+            sqrt_price_limit_x64 : u128) -> Result<Balance>{
+            let swap = self.compute_swap(zero_for_one, amount_out, false, sqrt_price_limit_x64);
+            assert!(swap.amount_in <= max_amount_in);
+            let amount_in = swap.amount_in;
+            self.settle_swap(zero_for_one, swap)?;
+            Ok(amount_in)
+        }
+
+        /// Return (amount sold, amount bought) of a swap at the current state, without executing it.
+        ///
+        /// #Params
+        ///
+        /// - `zero_for_one`: True to sell token0 for token1, false to sell token1 for token0.
+        /// - `amount`: Amount sold if `exact_input`, amount bought otherwise.
+        /// - `exact_input`: Whether `amount` is the amount sold.
+        /// - `sqrt_price_limit_x64`: The swap stops at this square root price, 0 for no limit.
+        #[ink(message)]
+        pub fn quote(&self, zero_for_one : bool, amount : Balance, exact_input : bool, sqrt_price_limit_x64 : u128) -> (Balance, Balance){
+            let swap = self.compute_swap(zero_for_one, amount, exact_input, sqrt_price_limit_x64);
+            (swap.amount_in, swap.amount_out)
+        }
+
+        fn settle_swap(&mut self, zero_for_one : bool, swap : SwapComputation) -> Result<()>{
+            let buyer = self.env().caller();
+            let (token_in, token_out) = if zero_for_one { (self.token0, self.token1) } else { (self.token1, self.token0) };
+
+            self.token_transfer_from(token_in, buyer, self.pool_account_id, swap.amount_in)?;
+
+            for (tick, fee_growth_global_x64) in swap.crossed_ticks.iter() {
+                let (fee_growth_global0_x64, fee_growth_global1_x64) = if zero_for_one {
+                    (*fee_growth_global_x64, self.fee_growth_global1_x64)
+                } else {
+                    (self.fee_growth_global0_x64, *fee_growth_global_x64)
+                };
+                let mut info = self.ticks.get(tick).unwrap().clone();
+                info.fee_growth_outside0_x64 = fee_growth_global0_x64.wrapping_sub(info.fee_growth_outside0_x64);
+                info.fee_growth_outside1_x64 = fee_growth_global1_x64.wrapping_sub(info.fee_growth_outside1_x64);
+                self.ticks.insert(*tick, info);
+            }
+            self.sqrt_price_x64 = swap.sqrt_price_x64;
+            self.tick = swap.tick;
+            self.liquidity = swap.liquidity;
+            if zero_for_one {
+                self.fee_growth_global0_x64 = swap.fee_growth_global_x64;
+            } else {
+                self.fee_growth_global1_x64 = swap.fee_growth_global_x64;
+            }
+
+            self.token_transfer(token_out, buyer, swap.amount_out).expect("token transfer failed");
+
+            self.env().emit_event( Swap {
+                buyer,
+                zero_for_one,
+                amount_in : swap.amount_in,
+                amount_out : swap.amount_out,
+                sqrt_price_x64 : swap.sqrt_price_x64,
+                liquidity : swap.liquidity,
+                tick : swap.tick,
+            });
+            Ok(())
+        }
+
+        fn compute_swap(&self, zero_for_one : bool, amount_specified : Balance, exact_input : bool, sqrt_price_limit_x64 : u128) -> SwapComputation{
+            assert!(self.sqrt_price_x64 != 0 && amount_specified > 0);
+            let sqrt_price_limit_x64 = match sqrt_price_limit_x64 {
+                0 if zero_for_one => MIN_SQRT_RATIO + 1,
+                0 => MAX_SQRT_RATIO - 1,
+                limit => limit,
+            };
+            if zero_for_one {
+                assert!(sqrt_price_limit_x64 < self.sqrt_price_x64 && sqrt_price_limit_x64 > MIN_SQRT_RATIO);
+            } else {
+                assert!(sqrt_price_limit_x64 > self.sqrt_price_x64 && sqrt_price_limit_x64 < MAX_SQRT_RATIO);
+            }
+
+            let mut swap = SwapComputation {
+                amount_in : 0,
+                amount_out : 0,
+                sqrt_price_x64 : self.sqrt_price_x64,
+                tick : self.tick,
+                liquidity : self.liquidity,
+                fee_growth_global_x64 : if zero_for_one { self.fee_growth_global0_x64 } else { self.fee_growth_global1_x64 },
+                crossed_ticks : Vec::new(),
+            };
+            let mut amount_remaining = amount_specified;
+            //Each step trades up to the next initialized tick, where the liquidity changes.
+            while amount_remaining != 0 && swap.sqrt_price_x64 != sqrt_price_limit_x64 {
+                let (tick_next, initialized) = self.next_initialized_tick(swap.tick, zero_for_one);
+                let sqrt_price_next = Self::sqrt_ratio_at_tick(tick_next);
+                let sqrt_price_target = if zero_for_one {
+                    sqrt_price_next.max(sqrt_price_limit_x64)
+                } else {
+                    sqrt_price_next.min(sqrt_price_limit_x64)
+                };
+                let sqrt_price_start = swap.sqrt_price_x64;
+                let (sqrt_price, amount_in, amount_out, fee_amount) = Self::compute_swap_step(sqrt_price_start, sqrt_price_target,
+                    swap.liquidity, amount_remaining, self.fee, exact_input);
+                swap.sqrt_price_x64 = sqrt_price;
+                if exact_input {
+                    amount_remaining -= amount_in + fee_amount;
+                } else {
+                    amount_remaining -= amount_out;
+                }
+                swap.amount_in += amount_in + fee_amount;
+                swap.amount_out += amount_out;
+                if swap.liquidity > 0 {
+                    let fee_growth = ((U256::from(fee_amount) << 64) / U256::from(swap.liquidity)).low_u128();
+                    swap.fee_growth_global_x64 = swap.fee_growth_global_x64.wrapping_add(fee_growth);
+                }
+
+                if sqrt_price == sqrt_price_next {
+                    if initialized {
+                        let liquidity_net = self.ticks.get(&tick_next).unwrap().liquidity_net;
+                        let liquidity_net = if zero_for_one { -liquidity_net } else { liquidity_net };
+                        swap.liquidity = Self::add_liquidity_delta(swap.liquidity, liquidity_net);
+                        swap.crossed_ticks.push((tick_next, swap.fee_growth_global_x64));
+                    }
+                    swap.tick = if zero_for_one { tick_next - 1 } else { tick_next };
+                } else if sqrt_price != sqrt_price_start {
+                    swap.tick = Self::tick_at_sqrt_ratio(sqrt_price);
+                }
+            }
+            swap
+        }
+
+        /// Return the next initialized tick from `tick` in the swap direction and true, or the last tick of the bitmap word
+        /// and false if the word has none. Selling token0 looks for ticks lower or equal to `tick`, selling token1 for ticks
+        /// greater than `tick`.
+        ///
+        /// NOTE: The search reads a single word so a swap step costs the same however far the next initialized tick is.
+        fn next_initialized_tick(&self, tick : i32, zero_for_one : bool) -> (i32, bool){
+            let compressed = Self::floor_div(tick, self.tick_spacing);
+            let (tick_next, initialized) = if zero_for_one {
+                let (word_position, bit_position) = Self::bitmap_position(compressed);
+                let masked = self.tick_bitmap(word_position) & (u128::MAX >> (127 - bit_position));
+                if masked != 0 {
+                    let most_significant_bit = 127 - masked.leading_zeros() as i32;
+                    ((compressed - (bit_position as i32 - most_significant_bit)) * self.tick_spacing, true)
+                } else {
+                    ((compressed - bit_position as i32) * self.tick_spacing, false)
+                }
+            } else {
+                let (word_position, bit_position) = Self::bitmap_position(compressed + 1);
+                let masked = self.tick_bitmap(word_position) & (u128::MAX << bit_position);
+                if masked != 0 {
+                    let least_significant_bit = masked.trailing_zeros() as i32;
+                    ((compressed + 1 + (least_significant_bit - bit_position as i32)) * self.tick_spacing, true)
+                } else {
+                    ((compressed + 1 + (127 - bit_position as i32)) * self.tick_spacing, false)
+                }
+            };
+            (tick_next.max(MIN_TICK).min(MAX_TICK), initialized)
+        }
+
+        /// Set the bit of an initialized tick in the tick bitmap, or clear it.
+        fn flip_tick(&mut self, tick : i32){
+            let (word_position, bit_position) = Self::bitmap_position(tick / self.tick_spacing);
+            let word = self.tick_bitmap(word_position) ^ (1 << bit_position);
+            if word == 0 {
+                self.tick_bitmap.take(&word_position);
+            } else {
+                self.tick_bitmap.insert(word_position, word);
+            }
+        }
+
+        /// Return (word, bit) of a tick divided by the tick spacing in the tick bitmap.
+        fn bitmap_position(compressed : i32) -> (i32, u32){
+            (compressed >> 7, (compressed & 127) as u32)
+        }
+
+        fn floor_div(a : i32, b : i32) -> i32{
+            if a < 0 && a % b != 0 { a / b - 1 } else { a / b }
+        }
+
+        fn check_ticks(&self, tick_lower : i32, tick_upper : i32){
+            assert!(tick_lower < tick_upper);
+            assert!(tick_lower >= MIN_TICK && tick_upper <= MAX_TICK);
+            assert!(tick_lower % self.tick_spacing == 0 && tick_upper % self.tick_spacing == 0);
+        }
+
+        /// Add `liquidity_delta` to the positions starting, or ending if `upper`, at `tick`.
+        fn update_tick(&mut self, tick : i32, liquidity_delta : i128, upper : bool){
+            let mut info = self.ticks.get(&tick).cloned().unwrap_or_default();
+            let liquidity_gross_before = info.liquidity_gross;
+            info.liquidity_gross = Self::add_liquidity_delta(liquidity_gross_before, liquidity_delta);
+            assert!(info.liquidity_gross <= self.max_liquidity_per_tick);
+            if liquidity_gross_before == 0 {
+                //By convention all the fees were collected below the tick.
+                if tick <= self.tick {
+                    info.fee_growth_outside0_x64 = self.fee_growth_global0_x64;
+                    info.fee_growth_outside1_x64 = self.fee_growth_global1_x64;
+                }
+                self.flip_tick(tick);
+            }
+            info.liquidity_net = if upper { info.liquidity_net - liquidity_delta } else { info.liquidity_net + liquidity_delta };
+
+            if info.liquidity_gross == 0 {
+                self.ticks.take(&tick);
+                self.flip_tick(tick);
+            } else {
+                self.ticks.insert(tick, info);
+            }
+        }
+
+        /// Return the fee growth per liquidity of both tokens between two ticks, as Q64.64.
+        fn fee_growth_inside(&self, tick_lower : i32, tick_upper : i32) -> (u128, u128){
+            let lower = self.ticks.get(&tick_lower).cloned().unwrap_or_default();
+            let upper = self.ticks.get(&tick_upper).cloned().unwrap_or_default();
+            let (global0, global1) = (self.fee_growth_global0_x64, self.fee_growth_global1_x64);
+            let (below0, below1) = if self.tick >= tick_lower {
+                (lower.fee_growth_outside0_x64, lower.fee_growth_outside1_x64)
+            } else {
+                (global0.wrapping_sub(lower.fee_growth_outside0_x64), global1.wrapping_sub(lower.fee_growth_outside1_x64))
+            };
+            let (above0, above1) = if self.tick < tick_upper {
+                (upper.fee_growth_outside0_x64, upper.fee_growth_outside1_x64)
+            } else {
+                (global0.wrapping_sub(upper.fee_growth_outside0_x64), global1.wrapping_sub(upper.fee_growth_outside1_x64))
+            };
+            (global0.wrapping_sub(below0).wrapping_sub(above0), global1.wrapping_sub(below1).wrapping_sub(above1))
+        }
+
+        /// Move the fees earned by a position since its last accrual to the tokens it is owed.
+        fn accrue_fees(&self, position : &mut Position){
+            let (fee_growth_inside0_x64, fee_growth_inside1_x64) = self.fee_growth_inside(position.tick_lower, position.tick_upper);
+            position.tokens_owed0 += Self::fees_earned(fee_growth_inside0_x64.wrapping_sub(position.fee_growth_inside0_last_x64), position.liquidity);
+            position.tokens_owed1 += Self::fees_earned(fee_growth_inside1_x64.wrapping_sub(position.fee_growth_inside1_last_x64), position.liquidity);
+            position.fee_growth_inside0_last_x64 = fee_growth_inside0_x64;
+            position.fee_growth_inside1_last_x64 = fee_growth_inside1_x64;
+        }
+
+        fn fees_earned(fee_growth_x64 : u128, liquidity : u128) -> Balance{
+            ((U256::from(fee_growth_x64) * U256::from(liquidity)) >> 64).low_u128()
+        }
+
+        fn add_liquidity_delta(liquidity : u128, liquidity_delta : i128) -> u128{
+            if liquidity_delta < 0 {
+                liquidity.checked_sub((-liquidity_delta) as u128).expect("liquidity underflow")
+            } else {
+                liquidity.checked_add(liquidity_delta as u128).expect("liquidity overflow")
+            }
+        }
+
+        /// Return `a * b / denominator`, rounded up if `round_up`.
+        fn mul_div(a : U512, b : U512, denominator : U512, round_up : bool) -> U512{
+            assert!(!denominator.is_zero());
+            let (quotient, remainder) = (a * b).div_mod(denominator);
+            if round_up && !remainder.is_zero() { quotient + U512::one() } else { quotient }
+        }
+
+        fn to_u128(value : U512) -> u128{
+            assert!(value <= U512::from(u128::MAX));
+            value.as_u128()
+        }
+
+        /// Return the square root of `1.0001 ^ tick` as Q64.64, rounded up.
+        pub fn sqrt_ratio_at_tick(tick : i32) -> u128{
+            assert!(tick >= MIN_TICK && tick <= MAX_TICK);
+            let abs_tick = tick.abs() as u32;
+            //Q128.128 ratio of 1 / sqrt(1.0001 ^ abs_tick)
+            let mut ratio = if abs_tick & 1 != 0 { U256::from(TICK_RATIOS[0]) } else { U256::one() << 128 };
+            for (i, tick_ratio) in TICK_RATIOS.iter().enumerate().skip(1) {
+                if abs_tick & (1 << i) != 0 {
+                    ratio = (ratio * U256::from(*tick_ratio)) >> 128;
+                }
+            }
+            if tick > 0 {
+                ratio = U256::max_value() / ratio;
+            }
+            let sqrt_price = if ratio.low_u64() != 0 { (ratio >> 64) + U256::one() } else { ratio >> 64 };
+            sqrt_price.as_u128()
+        }
+
+        /// Return the greatest tick whose square root price is lower or equal to `sqrt_price_x64`.
+        pub fn tick_at_sqrt_ratio(sqrt_price_x64 : u128) -> i32{
+            assert!(sqrt_price_x64 >= MIN_SQRT_RATIO && sqrt_price_x64 <= MAX_SQRT_RATIO);
+            let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+            while low < high {
+                let middle = low + (high - low + 1) / 2;
+                if Self::sqrt_ratio_at_tick(middle) <= sqrt_price_x64 {
+                    low = middle;
+                } else {
+                    high = middle - 1;
+                }
+            }
+            low
+        }
+
+        /// Return the token0 traded by `liquidity` between two square root prices:
+        /// `liquidity * (sqrt_b - sqrt_a) / (sqrt_a * sqrt_b)`
+        pub fn amount0_delta(sqrt_price_a_x64 : u128, sqrt_price_b_x64 : u128, liquidity : u128, round_up : bool) -> Balance{
+            let (sqrt_a, sqrt_b) = if sqrt_price_a_x64 > sqrt_price_b_x64 { (sqrt_price_b_x64, sqrt_price_a_x64) } else { (sqrt_price_a_x64, sqrt_price_b_x64) };
+            assert!(sqrt_a > 0);
+            let numerator = Self::mul_div(U512::from(liquidity) << 64, U512::from(sqrt_b - sqrt_a), U512::from(sqrt_b), round_up);
+            let (amount, remainder) = numerator.div_mod(U512::from(sqrt_a));
+            let amount = if round_up && !remainder.is_zero() { amount + U512::one() } else { amount };
+            Self::to_u128(amount)
+        }
+
+        /// Return the token1 traded by `liquidity` between two square root prices: `liquidity * (sqrt_b - sqrt_a)`
+        pub fn amount1_delta(sqrt_price_a_x64 : u128, sqrt_price_b_x64 : u128, liquidity : u128, round_up : bool) -> Balance{
+            let (sqrt_a, sqrt_b) = if sqrt_price_a_x64 > sqrt_price_b_x64 { (sqrt_price_b_x64, sqrt_price_a_x64) } else { (sqrt_price_a_x64, sqrt_price_b_x64) };
+            Self::to_u128(Self::mul_div(U512::from(liquidity), U512::from(sqrt_b - sqrt_a), U512::one() << 64, round_up))
+        }
+
+        /// Return the square root price after adding or removing `amount` of token0, rounded up.
+        fn next_sqrt_price_from_amount0(sqrt_price_x64 : u128, liquidity : u128, amount : Balance, add : bool) -> u128{
+            if amount == 0 {
+                return sqrt_price_x64
+            }
+            let numerator = U512::from(liquidity) << 64;
+            let product = U512::from(amount) * U512::from(sqrt_price_x64);
+            let denominator = if add {
+                numerator + product
+            } else {
+                assert!(numerator > product);
+                numerator - product
+            };
+            Self::to_u128(Self::mul_div(numerator, U512::from(sqrt_price_x64), denominator, true))
+        }
+
+        /// Return the square root price after adding or removing `amount` of token1, rounded down.
+        fn next_sqrt_price_from_amount1(sqrt_price_x64 : u128, liquidity : u128, amount : Balance, add : bool) -> u128{
+            assert!(liquidity > 0);
+            let quotient = Self::mul_div(U512::from(amount), U512::one() << 64, U512::from(liquidity), !add);
+            if add {
+                Self::to_u128(U512::from(sqrt_price_x64) + quotient)
+            } else {
+                assert!(U512::from(sqrt_price_x64) > quotient);
+                Self::to_u128(U512::from(sqrt_price_x64) - quotient)
+            }
+        }
+
+        /// Compute a swap step from `sqrt_price_current_x64` towards `sqrt_price_target_x64` with constant liquidity.
+        ///
+        /// Return (square root price reached, amount in without fee, amount out, fee amount).
+        pub fn compute_swap_step(sqrt_price_current_x64 : u128, sqrt_price_target_x64 : u128, liquidity : u128, amount_remaining : Balance,
+            fee : u32, exact_input : bool) -> (u128, Balance, Balance, Balance){
+            let zero_for_one = sqrt_price_current_x64 >= sqrt_price_target_x64;
+            let (mut amount_in, mut amount_out) = (0, 0);
+            let sqrt_price_next;
+            if exact_input {
+                let amount_remaining_less_fee = Self::to_u128(Self::mul_div(U512::from(amount_remaining),
+                    U512::from(FEE_DENOMINATOR - fee), U512::from(FEE_DENOMINATOR), false));
+                amount_in = if zero_for_one {
+                    Self::amount0_delta(sqrt_price_target_x64, sqrt_price_current_x64, liquidity, true)
+                } else {
+                    Self::amount1_delta(sqrt_price_current_x64, sqrt_price_target_x64, liquidity, true)
+                };
+                sqrt_price_next = if amount_remaining_less_fee >= amount_in {
+                    sqrt_price_target_x64
+                } else if zero_for_one {
+                    Self::next_sqrt_price_from_amount0(sqrt_price_current_x64, liquidity, amount_remaining_less_fee, true)
+                } else {
+                    Self::next_sqrt_price_from_amount1(sqrt_price_current_x64, liquidity, amount_remaining_less_fee, true)
+                };
+            } else {
+                amount_out = if zero_for_one {
+                    Self::amount1_delta(sqrt_price_target_x64, sqrt_price_current_x64, liquidity, false)
+                } else {
+                    Self::amount0_delta(sqrt_price_current_x64, sqrt_price_target_x64, liquidity, false)
+                };
+                sqrt_price_next = if amount_remaining >= amount_out {
+                    sqrt_price_target_x64
+                } else if zero_for_one {
+                    Self::next_sqrt_price_from_amount1(sqrt_price_current_x64, liquidity, amount_remaining, false)
+                } else {
+                    Self::next_sqrt_price_from_amount0(sqrt_price_current_x64, liquidity, amount_remaining, false)
+                };
+            }
+
+            let reached_target = sqrt_price_next == sqrt_price_target_x64;
+            if zero_for_one {
+                if !(reached_target && exact_input) {
+                    amount_in = Self::amount0_delta(sqrt_price_next, sqrt_price_current_x64, liquidity, true);
+                }
+                if !(reached_target && !exact_input) {
+                    amount_out = Self::amount1_delta(sqrt_price_next, sqrt_price_current_x64, liquidity, false);
+                }
+            } else {
+                if !(reached_target && exact_input) {
+                    amount_in = Self::amount1_delta(sqrt_price_current_x64, sqrt_price_next, liquidity, true);
+                }
+                if !(reached_target && !exact_input) {
+                    amount_out = Self::amount0_delta(sqrt_price_current_x64, sqrt_price_next, liquidity, false);
+                }
+            }
+            if !exact_input && amount_out > amount_remaining {
+                amount_out = amount_remaining;
+            }
+            //The remainder of an exact input which does not reach the target is taken as fee.
+            let fee_amount = if exact_input && !reached_target {
+                amount_remaining - amount_in
+            } else {
+                Self::to_u128(Self::mul_div(U512::from(amount_in), U512::from(fee), U512::from(FEE_DENOMINATOR - fee), true))
+            };
+            (sqrt_price_next, amount_in, amount_out, fee_amount)
+        }
+
+        /// Return the greatest liquidity the amounts can provide between `sqrt_price_a_x64` and `sqrt_price_b_x64` at the current price.
+        pub fn liquidity_for_amounts(sqrt_price_x64 : u128, sqrt_price_a_x64 : u128, sqrt_price_b_x64 : u128,
+            amount0 : Balance, amount1 : Balance) -> u128{
+            let liquidity0 = |sqrt_a : u128, sqrt_b : u128| {
+                let intermediate = Self::mul_div(U512::from(sqrt_a), U512::from(sqrt_b), U512::one() << 64, false);
+                Self::to_u128(Self::mul_div(intermediate, U512::from(amount0), U512::from(sqrt_b - sqrt_a), false))
+            };
+            let liquidity1 = |sqrt_a : u128, sqrt_b : u128| {
+                Self::to_u128(Self::mul_div(U512::from(amount1), U512::one() << 64, U512::from(sqrt_b - sqrt_a), false))
+            };
+            assert!(sqrt_price_a_x64 < sqrt_price_b_x64);
+            if sqrt_price_x64 <= sqrt_price_a_x64 {
+                liquidity0(sqrt_price_a_x64, sqrt_price_b_x64)
+            } else if sqrt_price_x64 < sqrt_price_b_x64 {
+                liquidity0(sqrt_price_x64, sqrt_price_b_x64).min(liquidity1(sqrt_price_a_x64, sqrt_price_x64))
+            } else {
+                liquidity1(sqrt_price_a_x64, sqrt_price_b_x64)
+            }
+        }
+
+        /// Return (amount of token0, amount of token1) of `liquidity` between `sqrt_price_a_x64` and `sqrt_price_b_x64` at the current price.
+        pub fn amounts_for_liquidity(sqrt_price_x64 : u128, sqrt_price_a_x64 : u128, sqrt_price_b_x64 : u128, liquidity : u128,
+            round_up : bool) -> (Balance, Balance){
+            if sqrt_price_x64 <= sqrt_price_a_x64 {
+                (Self::amount0_delta(sqrt_price_a_x64, sqrt_price_b_x64, liquidity, round_up), 0)
+            } else if sqrt_price_x64 < sqrt_price_b_x64 {
+                (
+                    Self::amount0_delta(sqrt_price_x64, sqrt_price_b_x64, liquidity, round_up),
+                    Self::amount1_delta(sqrt_price_a_x64, sqrt_price_x64, liquidity, round_up),
+                )
+            } else {
+                (0, Self::amount1_delta(sqrt_price_a_x64, sqrt_price_b_x64, liquidity, round_up))
+            }
+        }
+
+        #[cfg(not(test))]
+        fn token_transfer(&mut self, token : AccountId, to : AccountId, value : Balance)->Result<()>{
+            common::token::transfer(token, to, value, self.gas_limit / 2)
+        }
+
+        #[cfg(not(test))]
+        fn token_transfer_from(&mut self, token : AccountId, from : AccountId, to : AccountId, value : Balance) ->Result<()>{
+            common::token::transfer_from(token, from, to, value, self.gas_limit / 2)
+        }
+
+        /// The off-chain environment can not call contracts, the tests assume the tokens always transfer.
+        #[cfg(test)]
+        fn token_transfer(&mut self, _token : AccountId, _to : AccountId, _value : Balance)->Result<()>{
+            Ok(())
+        }
+
+        #[cfg(test)]
+        fn token_transfer_from(&mut self, _token : AccountId, _from : AccountId, _to : AccountId, _value : Balance) ->Result<()>{
+            Ok(())
+        }
+
+        ///Return the ConcentratedPool self account id.
+        #[ink(message)]
+        pub fn get_address(&self) ->AccountId{
+            self.pool_account_id
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::concentrated_pool::{MIN_TICK, MAX_TICK, MIN_SQRT_RATIO, MAX_SQRT_RATIO};
+    use ink_env::{
+        AccountId,
+    };
+    use ink_lang as ink;
+
+    const Q64 : u128 = 1 << 64;
+    const E18 : u128 = 1_000_000_000_000_000_000;
+
+    #[ink::test]
+    fn test_sqrt_ratio_at_tick_should_round_trip(){
+        assert_eq!(ConcentratedPool::sqrt_ratio_at_tick(0), Q64);
+        assert_eq!(ConcentratedPool::sqrt_ratio_at_tick(MIN_TICK), MIN_SQRT_RATIO);
+        assert_eq!(ConcentratedPool::sqrt_ratio_at_tick(MAX_TICK), MAX_SQRT_RATIO);
+        //sqrt(1.0001 ^ 6932) is about sqrt(2)
+        assert_eq!(ConcentratedPool::sqrt_ratio_at_tick(6932), 26087872550306729022);
+        for tick in [MIN_TICK + 1, -100, -1, 1, 100, 6932, MAX_TICK].iter() {
+            let sqrt_price = ConcentratedPool::sqrt_ratio_at_tick(*tick);
+            assert_eq!(ConcentratedPool::tick_at_sqrt_ratio(sqrt_price), *tick);
+            assert_eq!(ConcentratedPool::tick_at_sqrt_ratio(sqrt_price - 1), *tick - 1);
+        }
+    }
+
+    #[ink::test]
+    fn test_liquidity_for_amounts_should_match_amounts_for_liquidity(){
+        let sqrt_price_lower = ConcentratedPool::sqrt_ratio_at_tick(-600);
+        let sqrt_price_upper = ConcentratedPool::sqrt_ratio_at_tick(600);
+        let liquidity = ConcentratedPool::liquidity_for_amounts(Q64, sqrt_price_lower, sqrt_price_upper, E18, E18);
+        assert_eq!(liquidity, 33837499809738371383);
+        assert_eq!(ConcentratedPool::amounts_for_liquidity(Q64, sqrt_price_lower, sqrt_price_upper, liquidity, true), (E18, E18 - 2));
+        //Out of range positions only hold one token.
+        assert_eq!(ConcentratedPool::amounts_for_liquidity(sqrt_price_lower, sqrt_price_lower, sqrt_price_upper, liquidity, true),
+            (2030452988375912755, 0));
+        assert_eq!(ConcentratedPool::amounts_for_liquidity(sqrt_price_upper, sqrt_price_lower, sqrt_price_upper, liquidity, true),
+            (0, 2030452988375912755));
+    }
+
+    #[ink::test]
+    fn test_compute_swap_step_should_charge_fee(){
+        let sqrt_price_lower = ConcentratedPool::sqrt_ratio_at_tick(-600);
+        let liquidity = 33837499809738371383;
+        let (sqrt_price, amount_in, amount_out, fee_amount) = ConcentratedPool::compute_swap_step(Q64, sqrt_price_lower, liquidity,
+            E18 / 100, 3000, true);
+        assert_eq!((sqrt_price, amount_in, amount_out, fee_amount), (18441310461110184745, 9969999999999999, 9967063269409536, 30000000000001));
+        assert_eq!(amount_in + fee_amount, E18 / 100);
+
+        //Buying the same output costs the same input.
+        assert_eq!(ConcentratedPool::compute_swap_step(Q64, sqrt_price_lower, liquidity, amount_out, 3000, false),
+            (18441310461110184745, 9969999999999999, 9967063269409536, 30000000000000));
+
+        //A large input stops at the target price.
+        assert_eq!(ConcentratedPool::compute_swap_step(Q64, sqrt_price_lower, liquidity, 100 * E18, 3000, true),
+            (sqrt_price_lower, 1030452988375912755, 999999999999999997, 3100660947971654));
+    }
+
+    #[ink::test]
+    fn test_initialize_should_set_tick(){
+        let mut pool = ConcentratedPool::default();
+        assert_eq!(pool.sqrt_price_x64(), 0);
+        pool.initialize(ConcentratedPool::sqrt_ratio_at_tick(100) + 1);
+        assert_eq!(pool.tick(), 100);
+        //Without liquidity a swap only moves the price.
+        assert_eq!(pool.quote(true, E18, true, 0), (0, 0));
+    }
+
+    fn set_caller(caller: AccountId) {
+        let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().unwrap_or([0x0; 32].into());
+        let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+        data.push_arg(&caller);
+        ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+            caller,
+            callee,
+            1000000,
+            1000000,
+            data,
+        );
+    }
+
+    #[ink::test]
+    fn test_tick_bitmap_should_track_initialized_ticks(){
+        let mut pool = ConcentratedPool::default();
+        pool.initialize(Q64);
+        let (position_id, liquidity, _, _) = pool.mint(-600, 60 * 200, E18, E18, 0, 0).unwrap();
+        //-600 is bit 118 of word -1, 12000 is bit 72 of word 1.
+        assert_eq!(pool.tick_bitmap(-1), 1u128 << 118);
+        assert_eq!(pool.tick_bitmap(0), 0);
+        assert_eq!(pool.tick_bitmap(1), 1u128 << 72);
+
+        //A swap crosses the empty word 0 to reach the upper tick.
+        pool.swap_exact_input(false, 100 * E18, 0, ConcentratedPool::sqrt_ratio_at_tick(60 * 200)).unwrap();
+        assert_eq!(pool.tick(), 60 * 200);
+        assert_eq!(pool.liquidity(), 0);
+
+        pool.burn(position_id, liquidity);
+        assert_eq!(pool.tick_bitmap(-1), 0);
+        assert_eq!(pool.tick_bitmap(1), 0);
+        assert_eq!(pool.tick_info(-600), None);
+    }
+
+    #[ink::test]
+    fn test_fee_growth_inside_should_split_fees_at_crossed_ticks(){
+        let mut pool = ConcentratedPool::default();
+        pool.initialize(Q64);
+        //Position 1 is around the price, position 2 below it and position 3 above it.
+        pool.mint(-600, 600, E18, E18, 0, 0).unwrap();
+        pool.mint(-1200, -600, E18, E18, 0, 0).unwrap();
+        pool.mint(600, 1200, E18, E18, 0, 0).unwrap();
+
+        //Reaching tick -600 takes 1033553649323884409 token0 including a fee of 3100660947971654, the rest is traded by position 2.
+        pool.swap_exact_input(true, 2 * E18, 0, 0).unwrap();
+        assert!(pool.tick() >= -1200 && pool.tick() < -600);
+        let (fees1, fees2, fees3) = (pool.fees_owed(1), pool.fees_owed(2), pool.fees_owed(3));
+        assert!(fees1.0 <= 3100660947971654 && fees1.0 + 2 >= 3100660947971654);
+        assert!(fees2.0 > 0);
+        //The fees are 0.3% of the input, less the rounding of each position.
+        assert!(fees1.0 + fees2.0 <= 6_000_000_000_000_002 && fees1.0 + fees2.0 + 6 >= 6_000_000_000_000_000);
+        assert_eq!((fees1.1, fees2.1), (0, 0));
+        assert_eq!(fees3, (0, 0));
+
+        //Crossing tick -600 back moves the token1 fees to position 1.
+        pool.swap_exact_input(false, 2 * E18, 0, 0).unwrap();
+        assert!(pool.tick() >= -600 && pool.tick() < 600);
+        let (fees1, fees2, fees3) = (pool.fees_owed(1), pool.fees_owed(2), pool.fees_owed(3));
+        assert!(fees1.1 > 0 && fees2.1 > 0);
+        assert!(fees1.1 + fees2.1 <= 6_000_000_000_000_006 && fees1.1 + fees2.1 + 12 >= 6_000_000_000_000_000);
+        assert_eq!(fees3, (0, 0));
+    }
+
+    #[ink::test]
+    fn test_burn_and_collect_should_pay_liquidity_and_fees(){
+        let mut pool = ConcentratedPool::default();
+        pool.initialize(Q64);
+        let (position_id, liquidity, _, _) = pool.mint(-600, 600, E18, E18, 0, 0).unwrap();
+        pool.swap_exact_input(true, E18 / 100, 0, 0).unwrap();
+        let fees = pool.fees_owed(position_id);
+        assert!(fees.0 > 0);
+        assert_eq!(fees.1, 0);
+
+        let (amount0, amount1) = pool.burn(position_id, liquidity / 2);
+        assert!(amount0 > 0 && amount1 > 0);
+        assert_eq!(pool.liquidity(), liquidity - liquidity / 2);
+        let position = pool.position(position_id).unwrap();
+        assert_eq!(position.liquidity, liquidity - liquidity / 2);
+        assert_eq!((position.tokens_owed0, position.tokens_owed1), (amount0 + fees.0, amount1));
+        assert_eq!(pool.fees_owed(position_id), (amount0 + fees.0, amount1));
+
+        //Collect is capped by the maximum amounts.
+        assert_eq!(pool.collect(position_id, fees.0, u128::MAX), Ok((fees.0, amount1)));
+        assert_eq!(pool.fees_owed(position_id), (amount0, 0));
+        assert_eq!(pool.collect(position_id, u128::MAX, u128::MAX), Ok((amount0, 0)));
+        assert_eq!(pool.fees_owed(position_id), (0, 0));
+        assert_eq!(pool.collect(position_id, u128::MAX, u128::MAX), Ok((0, 0)));
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_burn_by_non_owner_should_panic(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut pool = ConcentratedPool::default();
+        pool.initialize(Q64);
+        let (position_id, liquidity, _, _) = pool.mint(-600, 600, E18, E18, 0, 0).unwrap();
+        set_caller(accounts.bob);
+        pool.burn(position_id, liquidity);
+    }
+}
@@ -7,15 +7,75 @@ use ink_lang as ink;
 
 #[ink::contract]
 pub mod erc20 {
+    use ink_env::hash::{Blake2x256, HashOutput};
+    use ink_prelude::{string::String, vec::Vec};
+    use ink_storage::traits::{PackedLayout, SpreadLayout};
+
+    /// Domain separator of the payload signed for `permit`.
+    const PERMIT_DOMAIN: &[u8] = b"zenlink:erc20:permit";
+
+    /// Selector of the `on_token_received` message a contract implements to accept tokens sent with `transfer_and_call`.
+    pub use common::receiver::ON_TOKEN_RECEIVED_SELECTOR;
+
+    /// Roles the owner can grant to other accounts.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub enum Role {
+        /// Allowed to mint new tokens.
+        Minter,
+        /// Allowed to pause transfers and freeze accounts.
+        Admin,
+    }
+
     //#[cfg(not(feature = "ink-as-dependency"))]
     #[ink(storage)]
     pub struct Erc20 {
+        /// The token name.
+        name: Option<String>,
+        /// The token symbol.
+        symbol: Option<String>,
+        /// The number of decimals used to display a balance.
+        decimals: u8,
         /// The total supply.
         total_supply: Balance,
         /// The balance of each user.
         balances: ink_storage::collections::HashMap<AccountId, Balance>,
         /// Approval spender on behalf of the message's sender.
         allowances: ink_storage::collections::HashMap<(AccountId, AccountId), Balance>,
+        /// The account allowed to grant and revoke roles.
+        owner: AccountId,
+        /// The roles granted to each account.
+        roles: ink_storage::collections::HashMap<(Role, AccountId), bool>,
+        /// The next permit nonce of each owner.
+        nonces: ink_storage::collections::HashMap<AccountId, u64>,
+        /// Whether transfers are paused.
+        paused: bool,
+        /// Accounts which can neither send nor receive tokens.
+        frozen: ink_storage::collections::HashMap<AccountId, bool>,
+        /// The id of the latest snapshot, 0 if no snapshot was taken yet.
+        current_snapshot_id: u64,
+        /// The balance of each account before its first update after a snapshot, as `(snapshot_id, balance)`.
+        balance_snapshots: ink_storage::collections::HashMap<AccountId, Vec<(u64, Balance)>>,
+        /// The total supply before its first update after a snapshot, as `(snapshot_id, total_supply)`.
+        total_supply_snapshots: Vec<(u64, Balance)>,
+        /// The account each account delegates its voting power to.
+        delegates: ink_storage::collections::HashMap<AccountId, AccountId>,
+        /// The voting power of each delegate, as `(block_number, votes)` ordered by block.
+        vote_checkpoints: ink_storage::collections::HashMap<AccountId, Vec<(BlockNumber, Balance)>>,
+        /// The total supply can never exceed the cap, `None` if the supply is not capped.
+        max_supply: Option<Balance>,
+        /// The block the emission schedule starts at.
+        emission_start_block: BlockNumber,
+        /// The tokens minters can claim per block, 0 if there is no emission schedule.
+        emission_per_block: Balance,
+        /// The block up to which the emission was claimed.
+        emission_claimed_block: BlockNumber,
+        /// Whether the accounts holding tokens are indexed.
+        holder_index_enabled: bool,
+        /// The accounts with a non-zero balance, only maintained once the holder index is enabled.
+        holders: ink_storage::collections::Vec<AccountId>,
+        /// The position of each account in `holders`.
+        holder_positions: ink_storage::collections::HashMap<AccountId, u32>,
     }
 
     #[ink(event)]
@@ -38,12 +98,120 @@ pub mod erc20 {
         value: Balance,
     }
 
+    #[ink(event)]
+    pub struct RoleGranted {
+        #[ink(topic)]
+        role: Role,
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        sender: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RoleRevoked {
+        #[ink(topic)]
+        role: Role,
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        sender: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Paused {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Unpaused {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Frozen {
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        sender: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Unfrozen {
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        sender: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Snapshot {
+        #[ink(topic)]
+        id: u64,
+    }
+
+    #[ink(event)]
+    pub struct DelegateChanged {
+        #[ink(topic)]
+        delegator: AccountId,
+        #[ink(topic)]
+        from_delegate: Option<AccountId>,
+        #[ink(topic)]
+        to_delegate: Option<AccountId>,
+    }
+
+    #[ink(event)]
+    pub struct DelegateVotesChanged {
+        #[ink(topic)]
+        delegate: AccountId,
+        previous_votes: Balance,
+        new_votes: Balance,
+    }
+
+    #[ink(event)]
+    pub struct EmissionScheduled {
+        #[ink(topic)]
+        start_block: BlockNumber,
+        #[ink(topic)]
+        tokens_per_block: Balance,
+    }
+
+    #[ink(event)]
+    pub struct Emission {
+        #[ink(topic)]
+        minter: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        #[ink(topic)]
+        value: Balance,
+        block: BlockNumber,
+    }
+
     impl Erc20 {
+        /// The deployer becomes the owner and is granted the minter and admin roles.
         #[ink(constructor)]
-        pub fn new(initial_supply: Balance) -> Self {
+        pub fn new(initial_supply: Balance, name: Option<String>, symbol: Option<String>, decimals: u8) -> Self {
+            Self::new_capped(initial_supply, name, symbol, decimals, None)
+        }
+
+        /// Same as `new`, but the total supply can never exceed `max_supply`.
+        #[ink(constructor)]
+        pub fn new_capped(
+            initial_supply: Balance,
+            name: Option<String>,
+            symbol: Option<String>,
+            decimals: u8,
+            max_supply: Option<Balance>,
+        ) -> Self {
+            assert!(initial_supply <= max_supply.unwrap_or(Balance::MAX));
             let caller = Self::env().caller();
             let mut balances = ink_storage::collections::HashMap::new();
             balances.insert(caller, initial_supply);
+            let mut roles = ink_storage::collections::HashMap::new();
+            roles.insert((Role::Minter, caller), true);
+            roles.insert((Role::Admin, caller), true);
 
             Self::env().emit_event(Transfer {
                 from: None,
@@ -52,9 +220,29 @@ pub mod erc20 {
             });
 
             Self {
+                name,
+                symbol,
+                decimals,
                 total_supply: initial_supply,
                 balances,
-                allowances: ink_storage::collections::HashMap::new()
+                allowances: ink_storage::collections::HashMap::new(),
+                owner: caller,
+                roles,
+                nonces: ink_storage::collections::HashMap::new(),
+                paused: false,
+                frozen: ink_storage::collections::HashMap::new(),
+                current_snapshot_id: 0,
+                balance_snapshots: ink_storage::collections::HashMap::new(),
+                total_supply_snapshots: Vec::new(),
+                delegates: ink_storage::collections::HashMap::new(),
+                vote_checkpoints: ink_storage::collections::HashMap::new(),
+                max_supply,
+                emission_start_block: 0,
+                emission_per_block: 0,
+                emission_claimed_block: 0,
+                holder_index_enabled: false,
+                holders: ink_storage::collections::Vec::new(),
+                holder_positions: ink_storage::collections::HashMap::new(),
             }
         }
 
@@ -64,6 +252,24 @@ pub mod erc20 {
             //self.env().balance()
         }
 
+        /// Return the token name.
+        #[ink(message, selector = "0xba2d1c0b")]
+        pub fn token_name(&self) -> Option<String> {
+            self.name.clone()
+        }
+
+        /// Return the token symbol.
+        #[ink(message, selector = "0x58af15a8")]
+        pub fn token_symbol(&self) -> Option<String> {
+            self.symbol.clone()
+        }
+
+        /// Return the number of decimals used to display a balance.
+        #[ink(message, selector = "0x55d2c725")]
+        pub fn token_decimals(&self) -> u8 {
+            self.decimals
+        }
+
         #[ink(message)]
         pub fn total_supply(&self) -> Balance {
             self.total_supply
@@ -77,14 +283,54 @@ pub mod erc20 {
         #[ink(message)]
         pub fn approve(&mut self, spender: AccountId, value: Balance) -> bool {
             let owner = self.env().caller();
-            self.allowances.insert((owner, spender), value);
-            self.env().emit_event(Approval{
-                owner: owner,
-                spender: spender,
-                value
-            });
+            self.set_allowance(owner, spender, value)
+        }
 
-            true
+        /// Atomically increase the allowance granted to `spender` by the caller.
+        #[ink(message)]
+        pub fn increase_allowance(&mut self, spender: AccountId, delta_value: Balance) -> bool {
+            let owner = self.env().caller();
+            let allowance = self.allowance_of_or_zero(&owner, &spender);
+            match allowance.checked_add(delta_value) {
+                Some(value) => self.set_allowance(owner, spender, value),
+                None => false,
+            }
+        }
+
+        /// Atomically decrease the allowance granted to `spender` by the caller.
+        #[ink(message)]
+        pub fn decrease_allowance(&mut self, spender: AccountId, delta_value: Balance) -> bool {
+            let owner = self.env().caller();
+            let allowance = self.allowance_of_or_zero(&owner, &spender);
+            match allowance.checked_sub(delta_value) {
+                Some(value) => self.set_allowance(owner, spender, value),
+                None => false,
+            }
+        }
+
+        /// Set the allowance of `spender` over the tokens of `owner` with a signature of `owner`,
+        /// so the spender can pull the tokens without a separate `approve` transaction.
+        ///
+        /// `signature` is a 65 bytes ECDSA signature of `permit_hash`, the signer account is the
+        /// blake2 hash of the compressed public key. The owner's nonce is consumed on success.
+        #[ink(message)]
+        pub fn permit(&mut self, owner: AccountId, spender: AccountId, value: Balance, deadline: Timestamp, signature: Vec<u8>) -> bool {
+            if deadline < self.env().block_timestamp() {
+                return false
+            }
+            let nonce = self.nonces(owner);
+            let message_hash = self.permit_hash(owner, spender, value, nonce, deadline);
+            if common::ecdsa::recover_signer(&message_hash, &signature) != Some(owner) {
+                return false
+            }
+            self.nonces.insert(owner, nonce + 1);
+            self.set_allowance(owner, spender, value)
+        }
+
+        /// Return the nonce the next permit of `owner` must be signed with.
+        #[ink(message)]
+        pub fn nonces(&self, owner: AccountId) -> u64 {
+            *self.nonces.get(&owner).unwrap_or(&0)
         }
 
         #[ink(message)]
@@ -92,15 +338,20 @@ pub mod erc20 {
             self.allowance_of_or_zero(&owner, &spender)
         }
 
+        /// Transfer `value` tokens of `from` to `to`, spending the caller's allowance.
         #[ink(message)]
         pub fn transfer_from(&mut self, from: AccountId, to: AccountId, value: Balance) -> bool {
-            //let caller = self.env().caller();
-            // let allowance = self.allowance_of_or_zero(&from, &caller);
-            // if allowance < value {
-            //     return false
-            // }
-            // self.allowances.insert((from, caller), allowance - value);
-            self.transfer_from_to(from, to, value)
+            let caller = self.env().caller();
+            let allowance = self.allowance_of_or_zero(&from, &caller);
+            if allowance < value {
+                return false
+            }
+            if !self.transfer_from_to(from, to, value) {
+                return false
+            }
+            self.allowances.insert((from, caller), allowance - value);
+
+            true
         }
 
         #[ink(message)]
@@ -109,11 +360,516 @@ pub mod erc20 {
             self.transfer_from_to(self.env().caller(), to, value)
         }
 
+        /// Transfer tokens to the contract `to` and notify it by calling its `on_token_received(from, value, data)`
+        /// message, Dot sent with this call is forwarded to the receiver.
+        ///
+        /// The whole transfer is reverted if the receiver fails or returns `false`.
+        #[ink(message, payable, selector = "0x0e60abc1")]
+        pub fn transfer_and_call(&mut self, to: AccountId, value: Balance, data: Vec<u8>) -> bool {
+            let from = self.env().caller();
+            let transferred_value = self.env().transferred_balance();
+            if !self.transfer_from_to(from, to, value) {
+                if transferred_value > 0 {
+                    self.env().transfer(from, transferred_value).expect("transfer error");
+                }
+                return false
+            }
+
+            // The receiver may spend the tokens it received before returning.
+            common::storage::flush(self);
+            let accepted = self.notify_token_received(to, from, value, data, transferred_value);
+            assert!(accepted, "receiver refused the tokens");
+            common::storage::reload(self);
+
+            true
+        }
+
+        /// Transfer tokens of the caller to several recipients.
+        ///
+        /// Either all transfers succeed or none is made.
+        #[ink(message)]
+        pub fn batch_transfer(&mut self, transfers: Vec<(AccountId, Balance)>) -> bool {
+            self.batch_transfer_from_to(self.env().caller(), transfers)
+        }
+
+        /// Transfer tokens of `from` to several recipients, spending the caller's allowance for the total.
+        ///
+        /// Either all transfers succeed or none is made.
+        #[ink(message)]
+        pub fn batch_transfer_from(&mut self, from: AccountId, transfers: Vec<(AccountId, Balance)>) -> bool {
+            let caller = self.env().caller();
+            let allowance = self.allowance_of_or_zero(&from, &caller);
+            let total_value = match Self::batch_total(&transfers) {
+                Some(total_value) if total_value <= allowance => total_value,
+                _ => return false,
+            };
+            if !self.batch_transfer_from_to(from, transfers) {
+                return false
+            }
+            self.allowances.insert((from, caller), allowance - total_value);
+
+            true
+        }
+
+        /// Return the account allowed to grant and revoke roles.
+        #[ink(message)]
+        pub fn owner(&self) -> AccountId {
+            self.owner
+        }
+
+        #[ink(message)]
+        pub fn has_role(&self, role: Role, account: AccountId) -> bool {
+            *self.roles.get(&(role, account)).unwrap_or(&false)
+        }
+
+        /// Grant a role to an account, only the owner can grant roles.
+        #[ink(message)]
+        pub fn grant_role(&mut self, role: Role, account: AccountId) -> bool {
+            let caller = self.env().caller();
+            assert!(caller == self.owner);
+            if !self.has_role(role, account) {
+                self.roles.insert((role, account), true);
+                self.env().emit_event(RoleGranted {
+                    role,
+                    account,
+                    sender: caller,
+                });
+            }
+
+            true
+        }
+
+        /// Revoke a role from an account, only the owner can revoke roles.
+        #[ink(message)]
+        pub fn revoke_role(&mut self, role: Role, account: AccountId) -> bool {
+            let caller = self.env().caller();
+            assert!(caller == self.owner);
+            if self.has_role(role, account) {
+                self.roles.take(&(role, account));
+                self.env().emit_event(RoleRevoked {
+                    role,
+                    account,
+                    sender: caller,
+                });
+            }
+
+            true
+        }
+
+        /// Pause all transfers, only accounts with the admin role can pause.
+        #[ink(message)]
+        pub fn pause(&mut self) -> bool {
+            let caller = self.env().caller();
+            assert!(self.has_role(Role::Admin, caller));
+            if !self.paused {
+                self.paused = true;
+                self.env().emit_event(Paused { account: caller });
+            }
+
+            true
+        }
+
+        /// Resume transfers, only accounts with the admin role can unpause.
+        #[ink(message)]
+        pub fn unpause(&mut self) -> bool {
+            let caller = self.env().caller();
+            assert!(self.has_role(Role::Admin, caller));
+            if self.paused {
+                self.paused = false;
+                self.env().emit_event(Unpaused { account: caller });
+            }
+
+            true
+        }
+
+        #[ink(message)]
+        pub fn is_paused(&self) -> bool {
+            self.paused
+        }
+
+        /// Block an account from sending and receiving tokens, only accounts with the admin role can freeze.
+        #[ink(message)]
+        pub fn freeze(&mut self, account: AccountId) -> bool {
+            let caller = self.env().caller();
+            assert!(self.has_role(Role::Admin, caller));
+            if !self.is_frozen(account) {
+                self.frozen.insert(account, true);
+                self.env().emit_event(Frozen {
+                    account,
+                    sender: caller,
+                });
+            }
+
+            true
+        }
+
+        /// Allow a frozen account to send and receive tokens again.
+        #[ink(message)]
+        pub fn unfreeze(&mut self, account: AccountId) -> bool {
+            let caller = self.env().caller();
+            assert!(self.has_role(Role::Admin, caller));
+            if self.is_frozen(account) {
+                self.frozen.take(&account);
+                self.env().emit_event(Unfrozen {
+                    account,
+                    sender: caller,
+                });
+            }
+
+            true
+        }
+
+        #[ink(message)]
+        pub fn is_frozen(&self, account: AccountId) -> bool {
+            *self.frozen.get(&account).unwrap_or(&false)
+        }
+
+        /// Record the current balances and total supply, only accounts with the admin role can take snapshots.
+        ///
+        /// Return the id of the new snapshot.
+        #[ink(message)]
+        pub fn snapshot(&mut self) -> u64 {
+            assert!(self.has_role(Role::Admin, self.env().caller()));
+            self.current_snapshot_id += 1;
+            self.env().emit_event(Snapshot {
+                id: self.current_snapshot_id,
+            });
+
+            self.current_snapshot_id
+        }
+
+        /// Return the balance of `owner` at the time snapshot `snapshot_id` was taken.
+        #[ink(message)]
+        pub fn balance_of_at(&self, owner: AccountId, snapshot_id: u64) -> Balance {
+            assert!(snapshot_id > 0 && snapshot_id <= self.current_snapshot_id);
+            self.balance_snapshots
+                .get(&owner)
+                .and_then(|snapshots| Self::snapshot_value_at(snapshots, snapshot_id))
+                .unwrap_or_else(|| self.balance_of_or_zero(&owner))
+        }
+
+        /// Return the total supply at the time snapshot `snapshot_id` was taken.
+        #[ink(message)]
+        pub fn total_supply_at(&self, snapshot_id: u64) -> Balance {
+            assert!(snapshot_id > 0 && snapshot_id <= self.current_snapshot_id);
+            Self::snapshot_value_at(&self.total_supply_snapshots, snapshot_id).unwrap_or(self.total_supply)
+        }
+
+        /// Delegate the voting power of the caller to `delegatee`.
+        ///
+        /// Tokens only count as votes once delegated, an account delegates to itself to vote directly.
+        #[ink(message)]
+        pub fn delegate(&mut self, delegatee: AccountId) -> bool {
+            let delegator = self.env().caller();
+            let from_delegate = self.delegates(delegator);
+            self.delegates.insert(delegator, delegatee);
+
+            self.env().emit_event(DelegateChanged {
+                delegator,
+                from_delegate,
+                to_delegate: Some(delegatee),
+            });
+
+            let balance = self.balance_of_or_zero(&delegator);
+            self.move_voting_power(from_delegate, Some(delegatee), balance);
+
+            true
+        }
+
+        /// Return the account `account` delegates its voting power to.
+        #[ink(message)]
+        pub fn delegates(&self, account: AccountId) -> Option<AccountId> {
+            self.delegates.get(&account).cloned()
+        }
+
+        /// Return the current voting power of `account`.
+        #[ink(message)]
+        pub fn get_votes(&self, account: AccountId) -> Balance {
+            self.vote_checkpoints
+                .get(&account)
+                .and_then(|checkpoints| checkpoints.last())
+                .map(|(_, votes)| *votes)
+                .unwrap_or(0)
+        }
+
+        /// Return the voting power of `account` at the end of block `block`, which must be already mined.
+        #[ink(message)]
+        pub fn get_past_votes(&self, account: AccountId, block: BlockNumber) -> Balance {
+            assert!(block < self.env().block_number());
+            let checkpoints = match self.vote_checkpoints.get(&account) {
+                Some(checkpoints) => checkpoints,
+                None => return 0,
+            };
+            match checkpoints.binary_search_by_key(&block, |(block_number, _)| *block_number) {
+                Ok(index) => checkpoints[index].1,
+                Err(0) => 0,
+                Err(index) => checkpoints[index - 1].1,
+            }
+        }
+
+        /// Create `value` new tokens for `to`, only accounts with the minter role can mint.
+        #[ink(message)]
+        pub fn mint(&mut self, to: AccountId, value: Balance) -> bool {
+            assert!(self.has_role(Role::Minter, self.env().caller()));
+            self.mint_to(to, value)
+        }
+
+        /// Return the supply cap, `None` if the supply is not capped.
+        #[ink(message)]
+        pub fn max_supply(&self) -> Option<Balance> {
+            self.max_supply
+        }
+
+        /// Let minters claim `tokens_per_block` new tokens for every block from `start_block` on,
+        /// only the owner can set the emission schedule. A rate of 0 stops the emission. Blocks which were
+        /// already claimed are not emitted again when the schedule is changed.
+        #[ink(message)]
+        pub fn set_emission_schedule(&mut self, start_block: BlockNumber, tokens_per_block: Balance) -> bool {
+            assert!(self.env().caller() == self.owner);
+            self.emission_start_block = start_block;
+            self.emission_per_block = tokens_per_block;
+            self.emission_claimed_block = start_block.max(self.emission_claimed_block);
+
+            self.env().emit_event(EmissionScheduled {
+                start_block,
+                tokens_per_block,
+            });
+
+            true
+        }
+
+        /// Return the emission start block and the tokens emitted per block.
+        #[ink(message)]
+        pub fn emission_schedule(&self) -> (BlockNumber, Balance) {
+            (self.emission_start_block, self.emission_per_block)
+        }
+
+        /// Return the emitted tokens which are not claimed yet, limited by the supply cap.
+        #[ink(message)]
+        pub fn claimable_emission(&self) -> Balance {
+            let block_number = self.env().block_number();
+            if self.emission_per_block == 0 || block_number <= self.emission_claimed_block {
+                return 0
+            }
+            let blocks = (block_number - self.emission_claimed_block) as Balance;
+            let emission = blocks.saturating_mul(self.emission_per_block);
+            match self.max_supply {
+                Some(max_supply) => emission.min(max_supply.saturating_sub(self.total_supply)),
+                None => emission,
+            }
+        }
+
+        /// Mint the emitted tokens which are not claimed yet to `to`, only accounts with the minter role can claim.
+        ///
+        /// Return the amount of tokens minted.
+        #[ink(message)]
+        pub fn claim_emission(&mut self, to: AccountId) -> Balance {
+            let minter = self.env().caller();
+            assert!(self.has_role(Role::Minter, minter));
+            let value = self.claimable_emission();
+            if value == 0 || !self.mint_to(to, value) {
+                return 0
+            }
+            let block = self.env().block_number();
+            self.emission_claimed_block = block;
+
+            self.env().emit_event(Emission {
+                minter,
+                to,
+                value,
+                block,
+            });
+
+            value
+        }
+
+        /// Destroy `value` tokens of the caller.
+        #[ink(message)]
+        pub fn burn(&mut self, value: Balance) -> bool {
+            self.burn_of(self.env().caller(), value)
+        }
+
+        /// Destroy `value` tokens of `from`, spending the caller's allowance.
+        #[ink(message)]
+        pub fn burn_from(&mut self, from: AccountId, value: Balance) -> bool {
+            let caller = self.env().caller();
+            let allowance = self.allowance_of_or_zero(&from, &caller);
+            if allowance < value {
+                return false
+            }
+            if !self.burn_of(from, value) {
+                return false
+            }
+            self.allowances.insert((from, caller), allowance - value);
+
+            true
+        }
+
+        /// Start indexing the accounts holding tokens, only accounts with the admin role can enable it.
+        ///
+        /// NOTE: The current holders are indexed in this call, its cost grows with the number of accounts
+        /// which ever held tokens.
+        #[ink(message)]
+        pub fn enable_holder_index(&mut self) -> bool {
+            assert!(self.has_role(Role::Admin, self.env().caller()));
+            if self.holder_index_enabled {
+                return false
+            }
+            self.holder_index_enabled = true;
+            let accounts: Vec<AccountId> = self.balances.keys().cloned().collect();
+            for account in accounts {
+                self.update_holder(account);
+            }
+
+            true
+        }
+
+        #[ink(message)]
+        pub fn is_holder_index_enabled(&self) -> bool {
+            self.holder_index_enabled
+        }
+
+        /// Return the number of accounts with a non-zero balance, 0 if the holder index is not enabled.
+        #[ink(message)]
+        pub fn holder_count(&self) -> u32 {
+            self.holders.len()
+        }
+
+        /// Return up to `limit` holders and their balances, starting at position `start` of the index.
+        ///
+        /// NOTE: The order of the holders changes when an account stops holding tokens.
+        #[ink(message)]
+        pub fn holders(&self, start: u32, limit: u32) -> Vec<(AccountId, Balance)> {
+            self.holders
+                .iter()
+                .skip(start as usize)
+                .take(limit as usize)
+                .map(|holder| (*holder, self.balance_of_or_zero(holder)))
+                .collect()
+        }
+
+        /// Return up to `limit` holders with the largest balances, largest first.
+        ///
+        /// NOTE: All holders are read, this is meant for off-chain queries.
+        #[ink(message)]
+        pub fn top_holders(&self, limit: u32) -> Vec<(AccountId, Balance)> {
+            let mut holders = self.holders(0, self.holder_count());
+            holders.sort_by(|a, b| b.1.cmp(&a.1));
+            holders.truncate(limit as usize);
+            holders
+        }
+
+        fn permit_hash(&self, owner: AccountId, spender: AccountId, value: Balance, nonce: u64, deadline: Timestamp) -> [u8; 32] {
+            let mut output = <Blake2x256 as HashOutput>::Type::default();
+            ink_env::hash_encoded::<Blake2x256, _>(
+                &(PERMIT_DOMAIN, self.env().account_id(), owner, spender, value, nonce, deadline),
+                &mut output,
+            );
+            output
+        }
+
+        fn set_allowance(&mut self, owner: AccountId, spender: AccountId, value: Balance) -> bool {
+            self.allowances.insert((owner, spender), value);
+            self.env().emit_event(Approval{
+                owner: owner,
+                spender: spender,
+                value
+            });
+
+            true
+        }
+
+        fn mint_to(&mut self, to: AccountId, value: Balance) -> bool {
+            let total_supply = match self.total_supply.checked_add(value) {
+                Some(total_supply) if total_supply <= self.max_supply.unwrap_or(Balance::MAX) => total_supply,
+                _ => return false,
+            };
+            self.update_account_snapshot(to);
+            self.update_total_supply_snapshot();
+            self.total_supply = total_supply;
+
+            let to_balance = self.balance_of_or_zero(&to);
+            self.balances.insert(to, to_balance + value);
+            self.update_holder(to);
+            self.move_voting_power(None, self.delegates(to), value);
+
+            self.env().emit_event(Transfer {
+                from: None,
+                to: Some(to),
+                value,
+            });
+
+            true
+        }
+
+        fn burn_of(&mut self, from: AccountId, value: Balance) -> bool {
+            let from_balance = self.balance_of_or_zero(&from);
+            if from_balance < value {
+                return false
+            }
+
+            self.update_account_snapshot(from);
+            self.update_total_supply_snapshot();
+            self.balances.insert(from, from_balance - value);
+            self.update_holder(from);
+            self.total_supply -= value;
+            self.move_voting_power(self.delegates(from), None, value);
+
+            self.env().emit_event(Transfer {
+                from: Some(from),
+                to: None,
+                value,
+            });
+
+            true
+        }
+
+        fn batch_transfer_from_to(&mut self, from: AccountId, transfers: Vec<(AccountId, Balance)>) -> bool {
+            if self.paused || self.is_frozen(from) {
+                return false
+            }
+            if transfers.iter().any(|(to, _)| self.is_frozen(*to)) {
+                return false
+            }
+            match Self::batch_total(&transfers) {
+                Some(total_value) if total_value <= self.balance_of_or_zero(&from) => {}
+                _ => return false,
+            }
+
+            for (to, value) in transfers {
+                assert!(self.transfer_from_to(from, to, value));
+            }
+
+            true
+        }
+
+        #[cfg(not(test))]
+        fn notify_token_received(&self, to: AccountId, from: AccountId, value: Balance, data: Vec<u8>, transferred_value: Balance) -> bool {
+            common::receiver::notify_token_received(to, from, value, data, transferred_value)
+        }
+
+        /// The off-chain environment can not call contracts, the tests set the answer of the receiver instead.
+        #[cfg(test)]
+        fn notify_token_received(&self, _to: AccountId, _from: AccountId, _value: Balance, _data: Vec<u8>, _transferred_value: Balance) -> bool {
+            tests::RECEIVER_ACCEPTS.with(|accepts| accepts.get())
+        }
+
+        /// Return the sum of the values of a batch, `None` if it overflows.
+        fn batch_total(transfers: &[(AccountId, Balance)]) -> Option<Balance> {
+            transfers.iter().try_fold(0 as Balance, |total, (_, value)| total.checked_add(*value))
+        }
+
         fn transfer_from_to(&mut self, from: AccountId, to: AccountId, value: Balance) -> bool {
+            if self.paused || self.is_frozen(from) || self.is_frozen(to) {
+                return false
+            }
             let from_balance = self.balance_of_or_zero(&from);
             if from_balance < value {
                 return false
             }
+
+            self.update_account_snapshot(from);
+            self.update_account_snapshot(to);
             
             // Update the sender's balance.
             self.balances.insert(from, from_balance - value);
@@ -122,6 +878,11 @@ pub mod erc20 {
             let to_balance = self.balance_of_or_zero(&to);
             self.balances.insert(to, to_balance + value);
 
+            self.update_holder(from);
+            self.update_holder(to);
+
+            self.move_voting_power(self.delegates(from), self.delegates(to), value);
+
             self.env().emit_event(Transfer {
                 from: Some(from),
                 to: Some(to),
@@ -131,6 +892,97 @@ pub mod erc20 {
             true
         }
 
+        /// Add the account to the holder index if it holds tokens, remove it otherwise.
+        fn update_holder(&mut self, account: AccountId) {
+            if !self.holder_index_enabled {
+                return
+            }
+            let holds_tokens = self.balance_of_or_zero(&account) > 0;
+            match (self.holder_positions.get(&account).cloned(), holds_tokens) {
+                (None, true) => {
+                    self.holder_positions.insert(account, self.holders.len());
+                    self.holders.push(account);
+                }
+                (Some(position), false) => {
+                    // Move the last holder into the freed position.
+                    self.holder_positions.take(&account);
+                    let last = self.holders.pop().expect("holder index is not empty");
+                    if last != account {
+                        *self.holders.get_mut(position).expect("holder position is in the index") = last;
+                        self.holder_positions.insert(last, position);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        fn update_account_snapshot(&mut self, account: AccountId) {
+            let balance = self.balance_of_or_zero(&account);
+            let mut snapshots = self.balance_snapshots.get(&account).cloned().unwrap_or_default();
+            if Self::update_snapshots(&mut snapshots, self.current_snapshot_id, balance) {
+                self.balance_snapshots.insert(account, snapshots);
+            }
+        }
+
+        fn update_total_supply_snapshot(&mut self) {
+            let total_supply = self.total_supply;
+            let current_snapshot_id = self.current_snapshot_id;
+            Self::update_snapshots(&mut self.total_supply_snapshots, current_snapshot_id, total_supply);
+        }
+
+        /// Record `value` for the current snapshot, unless a value was already recorded since it was taken.
+        fn update_snapshots(snapshots: &mut Vec<(u64, Balance)>, current_snapshot_id: u64, value: Balance) -> bool {
+            if current_snapshot_id == 0 {
+                return false
+            }
+            match snapshots.last() {
+                Some((snapshot_id, _)) if *snapshot_id >= current_snapshot_id => false,
+                _ => {
+                    snapshots.push((current_snapshot_id, value));
+                    true
+                }
+            }
+        }
+
+        /// The value at `snapshot_id` is the first value recorded at or after it, `None` if the value did not change since.
+        fn snapshot_value_at(snapshots: &[(u64, Balance)], snapshot_id: u64) -> Option<Balance> {
+            let index = match snapshots.binary_search_by_key(&snapshot_id, |(id, _)| *id) {
+                Ok(index) => index,
+                Err(index) => index,
+            };
+            snapshots.get(index).map(|(_, value)| *value)
+        }
+
+        fn move_voting_power(&mut self, src: Option<AccountId>, dst: Option<AccountId>, value: Balance) {
+            if src == dst || value == 0 {
+                return
+            }
+            if let Some(src) = src {
+                let votes = self.get_votes(src);
+                self.write_vote_checkpoint(src, votes, votes - value);
+            }
+            if let Some(dst) = dst {
+                let votes = self.get_votes(dst);
+                self.write_vote_checkpoint(dst, votes, votes + value);
+            }
+        }
+
+        fn write_vote_checkpoint(&mut self, delegate: AccountId, previous_votes: Balance, new_votes: Balance) {
+            let block_number = self.env().block_number();
+            let mut checkpoints = self.vote_checkpoints.get(&delegate).cloned().unwrap_or_default();
+            match checkpoints.last_mut() {
+                Some((checkpoint_block, votes)) if *checkpoint_block == block_number => *votes = new_votes,
+                _ => checkpoints.push((block_number, new_votes)),
+            }
+            self.vote_checkpoints.insert(delegate, checkpoints);
+
+            self.env().emit_event(DelegateVotesChanged {
+                delegate,
+                previous_votes,
+                new_votes,
+            });
+        }
+
         fn balance_of_or_zero(&self, owner: &AccountId) -> Balance {
             *self.balances.get(owner).unwrap_or(&0)
         }
@@ -153,6 +1005,24 @@ pub mod erc20 {
 
         type Event = <Erc20 as ::ink_lang::BaseEvent>::Type;
 
+        thread_local! {
+            /// What the receiver of `transfer_and_call` answers.
+            pub(super) static RECEIVER_ACCEPTS: core::cell::Cell<bool> = core::cell::Cell::new(true);
+        }
+
+        fn set_caller(caller: AccountId) {
+            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().unwrap_or([0x0; 32].into());
+            let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+            data.push_arg(&caller);
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller,
+                callee,
+                1000000,
+                1000000,
+                data,
+            );
+        }
+
         fn assert_transfer_event(
             event: &ink_env::test::EmittedEvent,
             expected_from: Option<AccountId>,
@@ -210,7 +1080,7 @@ pub mod erc20 {
 
         #[ink::test]
         fn new_works() {
-            let contract = Erc20::new(777);
+            let contract = Erc20::new(777, None, None, 18);
             assert_eq!(contract.total_supply(), 777);
 
             let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
@@ -223,9 +1093,22 @@ pub mod erc20 {
             );
         }
 
+        #[ink::test]
+        fn metadata_works() {
+            let contract = Erc20::new(100, Some(String::from("Zenlink")), Some(String::from("ZLK")), 12);
+            assert_eq!(contract.token_name(), Some(String::from("Zenlink")));
+            assert_eq!(contract.token_symbol(), Some(String::from("ZLK")));
+            assert_eq!(contract.token_decimals(), 12);
+
+            let contract = Erc20::new(100, None, None, 18);
+            assert_eq!(contract.token_name(), None);
+            assert_eq!(contract.token_symbol(), None);
+            assert_eq!(contract.token_decimals(), 18);
+        }
+
         #[ink::test]
         fn balance_works() {
-            let contract = Erc20::new(100);
+            let contract = Erc20::new(100, None, None, 18);
             assert_eq!(contract.total_supply(), 100);
             assert_eq!(contract.balance_of(AccountId::from([0x1; 32])), 100);
             assert_eq!(contract.balance_of(AccountId::from([0x0; 32])), 0);
@@ -242,7 +1125,7 @@ pub mod erc20 {
 
         #[ink::test]
         fn transfer_works() {
-            let mut contract = Erc20::new(100);
+            let mut contract = Erc20::new(100, None, None, 18);
             assert_eq!(contract.balance_of(AccountId::from([0x1; 32])), 100);
             assert!(contract.transfer(AccountId::from([0x0; 32]), 10));
             assert_eq!(contract.balance_of(AccountId::from([0x0; 32])), 10);
@@ -260,10 +1143,15 @@ pub mod erc20 {
 
         #[ink::test]
         fn transfer_from_works() {
-            let mut contract = Erc20::new(100);
+            let mut contract = Erc20::new(100, None, None, 18);
             assert_eq!(contract.balance_of(AccountId::from([0x1; 32])), 100);
             contract.approve(AccountId::from([0x1; 32]), 20);
-            contract.transfer_from(AccountId::from([0x1; 32]), AccountId::from([0x0; 32]), 10);
+            assert!(contract.transfer_from(AccountId::from([0x1; 32]), AccountId::from([0x0; 32]), 10));
+            assert_eq!(contract.balance_of(AccountId::from([0x0; 32])), 10);
+            assert_eq!(contract.allowance(AccountId::from([0x1; 32]), AccountId::from([0x1; 32])), 10);
+
+            // The allowance left does not cover the transfer.
+            assert!(!contract.transfer_from(AccountId::from([0x1; 32]), AccountId::from([0x0; 32]), 11));
             assert_eq!(contract.balance_of(AccountId::from([0x0; 32])), 10);
 
             let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
@@ -275,5 +1163,437 @@ pub mod erc20 {
                 10,
             );
         }
+
+        #[ink::test]
+        fn transfer_from_without_allowance_fails() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            set_caller(AccountId::from([0x2; 32]));
+            assert!(!contract.transfer_from(AccountId::from([0x1; 32]), AccountId::from([0x2; 32]), 10));
+            assert_eq!(contract.balance_of(AccountId::from([0x1; 32])), 100);
+            assert_eq!(contract.balance_of(AccountId::from([0x2; 32])), 0);
+        }
+
+        #[ink::test]
+        fn mint_works() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            assert!(contract.has_role(Role::Minter, AccountId::from([0x1; 32])));
+            assert!(contract.mint(AccountId::from([0x2; 32]), 50));
+            assert_eq!(contract.balance_of(AccountId::from([0x2; 32])), 50);
+            assert_eq!(contract.total_supply(), 150);
+            assert!(!contract.mint(AccountId::from([0x2; 32]), Balance::MAX));
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(2, emitted_events.len());
+            assert_transfer_event(
+                &emitted_events[1],
+                None,
+                Some(AccountId::from([0x2; 32])),
+                50,
+            );
+        }
+
+        #[ink::test]
+        #[should_panic]
+        fn mint_without_minter_role_fails() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            set_caller(AccountId::from([0x2; 32]));
+            contract.mint(AccountId::from([0x2; 32]), 50);
+        }
+
+        #[ink::test]
+        fn grant_and_revoke_role_works() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            assert!(contract.grant_role(Role::Minter, AccountId::from([0x2; 32])));
+            assert!(contract.has_role(Role::Minter, AccountId::from([0x2; 32])));
+            assert!(contract.revoke_role(Role::Minter, AccountId::from([0x2; 32])));
+            assert!(!contract.has_role(Role::Minter, AccountId::from([0x2; 32])));
+        }
+
+        #[ink::test]
+        fn burn_works() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            assert!(contract.burn(30));
+            assert_eq!(contract.balance_of(AccountId::from([0x1; 32])), 70);
+            assert_eq!(contract.total_supply(), 70);
+            assert!(!contract.burn(71));
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(2, emitted_events.len());
+            assert_transfer_event(
+                &emitted_events[1],
+                Some(AccountId::from([0x1; 32])),
+                None,
+                30,
+            );
+        }
+
+        #[ink::test]
+        fn burn_from_requires_allowance() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            contract.approve(AccountId::from([0x2; 32]), 20);
+            set_caller(AccountId::from([0x2; 32]));
+            assert!(!contract.burn_from(AccountId::from([0x1; 32]), 30));
+            assert!(contract.burn_from(AccountId::from([0x1; 32]), 20));
+            assert_eq!(contract.allowance(AccountId::from([0x1; 32]), AccountId::from([0x2; 32])), 0);
+            assert_eq!(contract.total_supply(), 80);
+        }
+
+        #[ink::test]
+        fn increase_allowance_works() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            assert!(contract.increase_allowance(AccountId::from([0x2; 32]), 20));
+            assert!(contract.increase_allowance(AccountId::from([0x2; 32]), 5));
+            assert_eq!(contract.allowance(AccountId::from([0x1; 32]), AccountId::from([0x2; 32])), 25);
+            assert!(!contract.increase_allowance(AccountId::from([0x2; 32]), Balance::MAX));
+            assert_eq!(contract.allowance(AccountId::from([0x1; 32]), AccountId::from([0x2; 32])), 25);
+        }
+
+        #[ink::test]
+        fn decrease_allowance_underflow_fails() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            contract.approve(AccountId::from([0x2; 32]), 20);
+            assert!(contract.decrease_allowance(AccountId::from([0x2; 32]), 15));
+            assert_eq!(contract.allowance(AccountId::from([0x1; 32]), AccountId::from([0x2; 32])), 5);
+            assert!(!contract.decrease_allowance(AccountId::from([0x2; 32]), 6));
+            assert_eq!(contract.allowance(AccountId::from([0x1; 32]), AccountId::from([0x2; 32])), 5);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(3, emitted_events.len());
+        }
+
+        fn signer_account(secret_key: &secp256k1::SecretKey) -> AccountId {
+            let public_key = secp256k1::PublicKey::from_secret_key(secret_key);
+            common::ecdsa::public_key_account(&public_key.serialize_compressed())
+        }
+
+        fn sign(message_hash: &[u8; 32], secret_key: &secp256k1::SecretKey) -> Vec<u8> {
+            let (signature, recovery_id) = secp256k1::sign(&secp256k1::Message::parse(message_hash), secret_key);
+            let mut signature = signature.serialize().to_vec();
+            signature.push(recovery_id.serialize());
+            signature
+        }
+
+        #[ink::test]
+        fn permit_works() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            let secret_key = secp256k1::SecretKey::parse(&[0x11; 32]).unwrap();
+            let owner = signer_account(&secret_key);
+            let spender = AccountId::from([0x2; 32]);
+
+            let signature = sign(&contract.permit_hash(owner, spender, 50, 0, 1000), &secret_key);
+            assert!(contract.permit(owner, spender, 50, 1000, signature.clone()));
+            assert_eq!(contract.allowance(owner, spender), 50);
+            assert_eq!(contract.nonces(owner), 1);
+
+            // The nonce is consumed, the same signature can not be replayed.
+            assert!(!contract.permit(owner, spender, 50, 1000, signature));
+        }
+
+        #[ink::test]
+        fn permit_with_wrong_signer_fails() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            let secret_key = secp256k1::SecretKey::parse(&[0x11; 32]).unwrap();
+            let other_key = secp256k1::SecretKey::parse(&[0x22; 32]).unwrap();
+            let owner = signer_account(&secret_key);
+            let spender = AccountId::from([0x2; 32]);
+
+            let signature = sign(&contract.permit_hash(owner, spender, 50, 0, 1000), &other_key);
+            assert!(!contract.permit(owner, spender, 50, 1000, signature));
+            assert!(!contract.permit(owner, spender, 50, 1000, Vec::new()));
+            assert_eq!(contract.allowance(owner, spender), 0);
+            assert_eq!(contract.nonces(owner), 0);
+        }
+
+        #[ink::test]
+        fn pause_blocks_transfers() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            assert!(contract.pause());
+            assert!(contract.is_paused());
+            assert!(!contract.transfer(AccountId::from([0x2; 32]), 10));
+            assert!(!contract.transfer_from(AccountId::from([0x1; 32]), AccountId::from([0x2; 32]), 10));
+            assert!(contract.unpause());
+            assert!(contract.transfer(AccountId::from([0x2; 32]), 10));
+            assert_eq!(contract.balance_of(AccountId::from([0x2; 32])), 10);
+        }
+
+        #[ink::test]
+        fn freeze_blocks_sender_and_receiver() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            assert!(contract.transfer(AccountId::from([0x2; 32]), 10));
+            assert!(contract.freeze(AccountId::from([0x2; 32])));
+            assert!(contract.is_frozen(AccountId::from([0x2; 32])));
+            assert!(!contract.transfer(AccountId::from([0x2; 32]), 10));
+            assert!(!contract.transfer_from(AccountId::from([0x2; 32]), AccountId::from([0x3; 32]), 10));
+            assert!(contract.unfreeze(AccountId::from([0x2; 32])));
+            assert!(contract.transfer(AccountId::from([0x2; 32]), 10));
+            assert_eq!(contract.balance_of(AccountId::from([0x2; 32])), 20);
+        }
+
+        #[ink::test]
+        #[should_panic]
+        fn pause_without_admin_role_fails() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            set_caller(AccountId::from([0x2; 32]));
+            contract.pause();
+        }
+
+        #[ink::test]
+        fn snapshot_works() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            assert!(contract.transfer(AccountId::from([0x2; 32]), 10));
+            assert_eq!(contract.snapshot(), 1);
+            assert!(contract.transfer(AccountId::from([0x2; 32]), 20));
+            assert!(contract.mint(AccountId::from([0x3; 32]), 50));
+            assert_eq!(contract.snapshot(), 2);
+            assert!(contract.burn(30));
+
+            assert_eq!(contract.balance_of_at(AccountId::from([0x1; 32]), 1), 90);
+            assert_eq!(contract.balance_of_at(AccountId::from([0x2; 32]), 1), 10);
+            assert_eq!(contract.balance_of_at(AccountId::from([0x3; 32]), 1), 0);
+            assert_eq!(contract.total_supply_at(1), 100);
+
+            assert_eq!(contract.balance_of_at(AccountId::from([0x1; 32]), 2), 70);
+            assert_eq!(contract.balance_of_at(AccountId::from([0x2; 32]), 2), 30);
+            assert_eq!(contract.balance_of_at(AccountId::from([0x3; 32]), 2), 50);
+            assert_eq!(contract.total_supply_at(2), 150);
+            assert_eq!(contract.total_supply(), 120);
+        }
+
+        #[ink::test]
+        #[should_panic]
+        fn balance_of_at_future_snapshot_fails() {
+            let contract = Erc20::new(100, None, None, 18);
+            contract.balance_of_at(AccountId::from([0x1; 32]), 1);
+        }
+
+        #[ink::test]
+        fn delegate_works() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            assert_eq!(contract.get_votes(AccountId::from([0x1; 32])), 0);
+            assert!(contract.delegate(AccountId::from([0x1; 32])));
+            assert_eq!(contract.delegates(AccountId::from([0x1; 32])), Some(AccountId::from([0x1; 32])));
+            assert_eq!(contract.get_votes(AccountId::from([0x1; 32])), 100);
+
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+            assert!(contract.transfer(AccountId::from([0x2; 32]), 30));
+            assert_eq!(contract.get_votes(AccountId::from([0x1; 32])), 70);
+            assert_eq!(contract.get_votes(AccountId::from([0x2; 32])), 0);
+
+            assert!(contract.delegate(AccountId::from([0x3; 32])));
+            assert_eq!(contract.get_votes(AccountId::from([0x1; 32])), 0);
+            assert_eq!(contract.get_votes(AccountId::from([0x3; 32])), 70);
+
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+            assert_eq!(contract.get_past_votes(AccountId::from([0x1; 32]), 0), 100);
+            assert_eq!(contract.get_past_votes(AccountId::from([0x1; 32]), 1), 0);
+            assert_eq!(contract.get_past_votes(AccountId::from([0x3; 32]), 0), 0);
+            assert_eq!(contract.get_past_votes(AccountId::from([0x3; 32]), 1), 70);
+        }
+
+        #[ink::test]
+        #[should_panic]
+        fn get_past_votes_of_current_block_fails() {
+            let contract = Erc20::new(100, None, None, 18);
+            contract.get_past_votes(AccountId::from([0x1; 32]), 0);
+        }
+
+        #[ink::test]
+        fn transfer_and_call_works() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            let receiver = AccountId::from([0x5; 32]);
+            assert!(contract.transfer_and_call(receiver, 10, vec![0x01, 0x02]));
+            assert_eq!(contract.balance_of(AccountId::from([0x1; 32])), 90);
+            assert_eq!(contract.balance_of(receiver), 10);
+        }
+
+        #[ink::test]
+        fn transfer_and_call_refunds_when_transfer_fails() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            let caller = AccountId::from([0x1; 32]);
+            let receiver = AccountId::from([0x5; 32]);
+            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().expect("cannot get contract account");
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(callee, 2000000)
+                .expect("cannot set account balance");
+            // The caller sends 1000000 with the call.
+            set_caller(caller);
+            let caller_balance = ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(caller)
+                .expect("cannot get account balance");
+
+            assert!(!contract.transfer_and_call(receiver, 101, Vec::new()));
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(caller).expect("cannot get account balance"),
+                caller_balance + 1000000
+            );
+            assert_eq!(contract.balance_of(caller), 100);
+            assert_eq!(contract.balance_of(receiver), 0);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "receiver refused the tokens")]
+        fn transfer_and_call_reverts_when_receiver_refuses() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            RECEIVER_ACCEPTS.with(|accepts| accepts.set(false));
+            contract.transfer_and_call(AccountId::from([0x5; 32]), 10, Vec::new());
+        }
+
+        #[ink::test]
+        fn batch_transfer_works() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            assert!(contract.batch_transfer(vec![
+                (AccountId::from([0x2; 32]), 10),
+                (AccountId::from([0x3; 32]), 20),
+            ]));
+            assert_eq!(contract.balance_of(AccountId::from([0x1; 32])), 70);
+            assert_eq!(contract.balance_of(AccountId::from([0x2; 32])), 10);
+            assert_eq!(contract.balance_of(AccountId::from([0x3; 32])), 20);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(3, emitted_events.len());
+            assert_transfer_event(
+                &emitted_events[2],
+                Some(AccountId::from([0x1; 32])),
+                Some(AccountId::from([0x3; 32])),
+                20,
+            );
+        }
+
+        #[ink::test]
+        fn batch_transfer_is_atomic() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            assert!(!contract.batch_transfer(vec![
+                (AccountId::from([0x2; 32]), 60),
+                (AccountId::from([0x3; 32]), 50),
+            ]));
+            assert!(!contract.batch_transfer(vec![
+                (AccountId::from([0x2; 32]), 1),
+                (AccountId::from([0x3; 32]), Balance::MAX),
+            ]));
+            assert!(contract.approve(AccountId::from([0x1; 32]), 100));
+            assert!(contract.freeze(AccountId::from([0x3; 32])));
+            assert!(!contract.batch_transfer_from(AccountId::from([0x1; 32]), vec![
+                (AccountId::from([0x2; 32]), 10),
+                (AccountId::from([0x3; 32]), 10),
+            ]));
+            assert_eq!(contract.balance_of(AccountId::from([0x1; 32])), 100);
+            assert_eq!(contract.balance_of(AccountId::from([0x2; 32])), 0);
+            assert_eq!(contract.allowance(AccountId::from([0x1; 32]), AccountId::from([0x1; 32])), 100);
+        }
+
+        #[ink::test]
+        fn batch_transfer_from_without_allowance_fails() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            set_caller(AccountId::from([0x2; 32]));
+            assert!(!contract.batch_transfer_from(AccountId::from([0x1; 32]), vec![
+                (AccountId::from([0x2; 32]), 10),
+                (AccountId::from([0x3; 32]), 10),
+            ]));
+            assert_eq!(contract.balance_of(AccountId::from([0x1; 32])), 100);
+            assert_eq!(contract.balance_of(AccountId::from([0x2; 32])), 0);
+        }
+
+        #[ink::test]
+        fn batch_transfer_from_spends_allowance() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            assert!(contract.approve(AccountId::from([0x2; 32]), 50));
+            set_caller(AccountId::from([0x2; 32]));
+            assert!(contract.batch_transfer_from(AccountId::from([0x1; 32]), vec![
+                (AccountId::from([0x2; 32]), 10),
+                (AccountId::from([0x3; 32]), 20),
+            ]));
+            assert_eq!(contract.allowance(AccountId::from([0x1; 32]), AccountId::from([0x2; 32])), 20);
+            assert_eq!(contract.balance_of(AccountId::from([0x1; 32])), 70);
+            assert_eq!(contract.balance_of(AccountId::from([0x3; 32])), 20);
+
+            // The allowance left does not cover the whole batch, nothing is transferred.
+            assert!(!contract.batch_transfer_from(AccountId::from([0x1; 32]), vec![
+                (AccountId::from([0x2; 32]), 10),
+                (AccountId::from([0x3; 32]), 11),
+            ]));
+            assert_eq!(contract.allowance(AccountId::from([0x1; 32]), AccountId::from([0x2; 32])), 20);
+            assert_eq!(contract.balance_of(AccountId::from([0x1; 32])), 70);
+        }
+
+        #[ink::test]
+        fn max_supply_caps_mint() {
+            let mut contract = Erc20::new_capped(100, None, None, 18, Some(150));
+            assert_eq!(contract.max_supply(), Some(150));
+            assert!(contract.mint(AccountId::from([0x2; 32]), 50));
+            assert!(!contract.mint(AccountId::from([0x2; 32]), 1));
+            assert_eq!(contract.total_supply(), 150);
+        }
+
+        #[ink::test]
+        fn claim_emission_works() {
+            let mut contract = Erc20::new_capped(100, None, None, 18, Some(125));
+            assert_eq!(contract.claim_emission(AccountId::from([0x2; 32])), 0);
+            assert!(contract.set_emission_schedule(1, 10));
+
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+            assert_eq!(contract.claimable_emission(), 0);
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+            assert_eq!(contract.claimable_emission(), 20);
+            assert_eq!(contract.claim_emission(AccountId::from([0x2; 32])), 20);
+            assert_eq!(contract.balance_of(AccountId::from([0x2; 32])), 20);
+            assert_eq!(contract.claimable_emission(), 0);
+
+            // The emission stops at the supply cap.
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+            assert_eq!(contract.claim_emission(AccountId::from([0x2; 32])), 5);
+            assert_eq!(contract.total_supply(), 125);
+        }
+
+        #[ink::test]
+        fn reschedule_emission_does_not_emit_claimed_blocks_again() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            assert!(contract.set_emission_schedule(1, 10));
+            for _ in 0..3 {
+                ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+            }
+            assert_eq!(contract.claim_emission(AccountId::from([0x2; 32])), 20);
+
+            // Moving the start back does not emit the blocks claimed at the old rate again.
+            assert!(contract.set_emission_schedule(0, 5));
+            assert_eq!(contract.claimable_emission(), 0);
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+            assert_eq!(contract.claim_emission(AccountId::from([0x2; 32])), 5);
+            assert_eq!(contract.total_supply(), 125);
+        }
+
+        #[ink::test]
+        #[should_panic]
+        fn claim_emission_without_minter_role_fails() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            assert!(contract.set_emission_schedule(0, 10));
+            set_caller(AccountId::from([0x2; 32]));
+            contract.claim_emission(AccountId::from([0x2; 32]));
+        }
+
+        #[ink::test]
+        fn holder_index_works() {
+            let mut contract = Erc20::new(100, None, None, 18);
+            assert!(contract.transfer(AccountId::from([0x2; 32]), 30));
+            assert_eq!(contract.holder_count(), 0);
+
+            assert!(contract.enable_holder_index());
+            assert!(!contract.enable_holder_index());
+            assert_eq!(contract.holder_count(), 2);
+            assert!(contract.transfer(AccountId::from([0x3; 32]), 50));
+            assert_eq!(contract.top_holders(2), vec![
+                (AccountId::from([0x3; 32]), 50),
+                (AccountId::from([0x2; 32]), 30),
+            ]);
+
+            // An account leaves the index once its balance is zero.
+            assert!(contract.burn(20));
+            assert_eq!(contract.holder_count(), 2);
+            assert_eq!(contract.holders(0, 1).len(), 1);
+            let mut holders = contract.holders(0, 10);
+            holders.sort();
+            assert_eq!(holders, vec![
+                (AccountId::from([0x2; 32]), 30),
+                (AccountId::from([0x3; 32]), 50),
+            ]);
+        }
     }
 }
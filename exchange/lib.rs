@@ -1,6 +1,6 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
-pub use self::exchange::Exchange;
+pub use self::exchange::{Exchange, TokenReceivedAction};
 
 use ink_lang as ink;
 
@@ -10,6 +10,7 @@ mod exchange {
     #[cfg(not(feature = "ink-as-dependency"))]
     use ink_storage::{
         collections::HashMap as StorageHashMap,
+        collections::Vec as StorageVec,
     };
 
     #[cfg(not(feature = "ink-as-dependency"))]
@@ -19,6 +20,41 @@ mod exchange {
          call::{ build_call, ExecutionInput, utils::ReturnType,}
     };
 
+    use ink_env::hash::{
+        Blake2x256,
+        HashOutput,
+    };
+
+    use primitive_types::U256;
+
+    use ink_prelude::{
+        format,
+        string::String,
+        vec::Vec,
+    };
+
+    /// Domain separator of the payload signed for `permit`.
+    const PERMIT_DOMAIN: &[u8] = b"zenlink:lp:permit";
+
+    /// The swap fees per liquidity share are multiplied by it.
+    pub const FEE_PER_SHARE_PRECISION : u128 = 1_000_000_000_000;
+
+    /// Swap fees are in basis points of the input.
+    pub const FEE_DENOMINATOR : u128 = 10000;
+    /// The flat swap fee, 0.3%.
+    pub const DEFAULT_FEE_BPS : u32 = 30;
+    /// Highest fee the dynamic fee can be set to, 10%.
+    pub const MAX_FEE_BPS : u32 = 1000;
+    /// The Dot price of a token is multiplied by it to measure the price changes. Tokens with more decimals than Dot
+    /// have a price far below one, so the precision is high.
+    pub const PRICE_PRECISION : u128 = 1_000_000_000_000_000_000;
+    /// Price changes are measured in millionths of the price, and capped at the whole price.
+    pub const PRICE_CHANGE_DENOMINATOR : u128 = 1_000_000;
+    /// Number of swaps the volatility is averaged over.
+    pub const VOLATILITY_EMA_PERIOD : u128 = 10;
+    /// Volatility at which the dynamic fee reaches its maximum, a 1% average price change between swaps.
+    pub const VOLATILITY_AT_MAX_FEE : u128 = 10_000;
+
     #[ink(event)]
     pub struct CreateExchange {
         #[ink(topic)]
@@ -65,6 +101,12 @@ mod exchange {
         token_ammount : Balance,
     }
 
+    #[ink(event)]
+    pub struct FeeAdjusted{
+        fee_bps : u32,
+        volatility : u128,
+    }
+
     #[ink(event)]
     pub struct Transfer{
         #[ink(topic)]
@@ -84,17 +126,64 @@ mod exchange {
         #[ink(topic)]
         value : Balance,
     }
-    
+
+    pub use common::token::{Error, Result};
+
+    /// What the Exchange does with tokens received through the token `transfer_and_call`.
+    /// It is SCALE encoded in the `data` argument.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum TokenReceivedAction {
+        /// Sell the received tokens, the bought Dot is sent to `recipient`.
+        TokenToDot {
+            min_dot : Balance,
+            deadline : Timestamp,
+            recipient : AccountId,
+        },
+        /// Deposit the received tokens and the Dot forwarded by the token at current ratio.
+        /// Tokens above the current ratio are sent back.
+        AddLiquidity {
+            min_liquidity : Balance,
+            deadline : Timestamp,
+        },
+    }
+
     #[ink(storage)]
     pub struct Exchange {
-        pub name : Hash,
-        pub symbol : Hash,
-        pub decimals : u32,
+        pub name : String,
+        pub symbol : String,
+        pub decimals : u8,
         //total liquidity
         pub total_supply : Balance,
 
         pub balances : StorageHashMap<AccountId, Balance>, 
         allowances: StorageHashMap<(AccountId, AccountId), Balance>,
+        //next permit nonce of each liquidity owner
+        nonces: StorageHashMap<AccountId, u64>,
+        //MultiToken contract and token id holding the liquidity instead of `balances`, if any
+        lp_token : Option<(AccountId, u128)>,
+        //whether the liquidity providers are indexed
+        holder_index_enabled : bool,
+        //the accounts with non-zero liquidity, only maintained once the holder index is enabled
+        holders : StorageVec<AccountId>,
+        //the position of each account in `holders`
+        holder_positions : StorageHashMap<AccountId, u32>,
+        //swap fees earned per liquidity share since the creation of the Exchange, in Dot and in tokens,
+        //multiplied by FEE_PER_SHARE_PRECISION
+        dot_fee_per_share : u128,
+        token_fee_per_share : u128,
+        //the fees per share at the last liquidity change of each provider
+        fee_snapshots : StorageHashMap<AccountId, (u128, u128)>,
+        //the fees each provider earned before its last liquidity change
+        fees_accrued : StorageHashMap<AccountId, (Balance, Balance)>,
+        //the swap fee in basis points, DEFAULT_FEE_BPS unless the dynamic fee is enabled
+        fee_bps : u32,
+        //the minimum and maximum dynamic fee in basis points, None for the flat fee
+        dynamic_fee : Option<(u32, u32)>,
+        //EMA of the absolute price changes between swaps, in millionths of the price
+        volatility : u128,
+        //the Dot price of a token after the last swap, multiplied by PRICE_PRECISION. Only tracked with the dynamic fee
+        last_price : u128,
         //address of the ERC20 token traded on this contract
         pub token : AccountId,
         //the address of factory contract.
@@ -116,15 +205,45 @@ mod exchange {
         /// - `factory_account_id`: AccountId of the Factory which instantiate this  contract
         /// - `deployer`: Account deploy this contract and provide initial liquidity
         /// - `token_ammount`: Ammount of token the deployer will transfer from token_account_id to this contract account
+        /// - `token_symbol`: Symbol of the Erc20 token queried by the Factory, the lp token name and symbol are derived from it.
+        ///   None gives the default "Zenlink LP" and "ZLP"
         #[ink(constructor)]
-        pub fn new(token_account_id: AccountId, factory_account_id : AccountId, deployer : AccountId,token_ammount : Balance) -> Self {
+        pub fn new(token_account_id: AccountId, factory_account_id : AccountId, deployer : AccountId,token_ammount : Balance,
+            token_symbol : Option<String>) -> Self {
+            Self::new_with_lp_token(token_account_id, factory_account_id, deployer, token_ammount, token_symbol, None)
+        }
+
+        /// Same as `new`, but if `lp_token` is set the liquidity lives in that MultiToken contract under the given token id.
+        /// 
+        /// NOTE: With a MultiToken the Factory creates the token id and mints the initial liquidity to the deployer.
+        /// 
+        /// #Params
+        /// 
+        /// - `lp_token`: AccountId of the MultiToken contract and the token id of this trading pair
+        #[ink(constructor)]
+        pub fn new_with_lp_token(token_account_id: AccountId, factory_account_id : AccountId, deployer : AccountId,token_ammount : Balance,
+            token_symbol : Option<String>, lp_token : Option<(AccountId, u128)>) -> Self {
+            let (name, symbol) = Self::lp_metadata(token_symbol);
             let mut instance = Self{
-                name : Hash::default(), 
-                symbol : Hash::default(),
+                name, 
+                symbol,
                 decimals : 18,
                 total_supply : Self::env().balance(),
                 balances :StorageHashMap::new(),
                 allowances : StorageHashMap::new(),
+                nonces : StorageHashMap::new(),
+                lp_token,
+                holder_index_enabled : false,
+                holders : StorageVec::new(),
+                holder_positions : StorageHashMap::new(),
+                dot_fee_per_share : 0,
+                token_fee_per_share : 0,
+                fee_snapshots : StorageHashMap::new(),
+                fees_accrued : StorageHashMap::new(),
+                fee_bps : DEFAULT_FEE_BPS,
+                dynamic_fee : None,
+                volatility : 0,
+                last_price : 0,
                 token : token_account_id,  
                 factory : factory_account_id,         
                 gas_limit : 507085500000,
@@ -133,16 +252,26 @@ mod exchange {
 
             //The contract deployer show transfer some token to this contract. if success, the contract deployer become first liquidity provider.
             if token_ammount > 0{
-                instance.token_transfer_from(deployer, instance.exchange_account_id, token_ammount);
+                instance.token_transfer_from(deployer, instance.exchange_account_id, token_ammount).expect("token transfer failed");
+            }
+            if instance.lp_token.is_none() {
+                instance.balances.insert(deployer, instance.total_supply);
             }
-            instance.balances.insert(deployer, instance.total_supply);
 
             instance
         }
 
         #[ink(constructor)]
         pub fn default() -> Self {
-            Self::new(Default::default(), AccountId::default(), AccountId::default(), Balance::default())
+            Self::new(Default::default(), AccountId::default(), AccountId::default(), Balance::default(), None)
+        }
+
+        /// Derive the lp token name and symbol from the symbol of the traded token.
+        fn lp_metadata(token_symbol : Option<String>) -> (String, String){
+            match token_symbol {
+                Some(token_symbol) => (format!("Zenlink LP {}", token_symbol), format!("ZLP-{}", token_symbol)),
+                None => (String::from("Zenlink LP"), String::from("ZLP")),
+            }
         }
 
         /// Deposit Dot and Tokens (self.token) at current ratio to mint lp tokens.
@@ -154,8 +283,10 @@ mod exchange {
         /// - `min_liquidity`:  Minimum number of lp sender will mint if total lp supply is greater than 0.
         /// - `max_tokens`: Maximum number of tokens deposited. Deposits max amount if total lp supply is 0.
         /// - `deadline`: Time after which this transaction can no longer be executed.
+        /// 
+        /// NOTE: If the token transfer fails the transferred Dot is refunded and `TokenTransferFailed` is returned.
         #[ink(message, payable,selector = "0xDEADBEEF")]
-        pub fn add_liquidity(&mut self, min_liquidity: u128, max_tokens: u128, deadline: Timestamp) ->u128{
+        pub fn add_liquidity(&mut self, min_liquidity: u128, max_tokens: u128, deadline: Timestamp) ->Result<u128>{
             let transfferred_value = self.env().transferred_balance();
             assert!(deadline >= self.env().block_timestamp() && max_tokens > 0 && transfferred_value > 0);
             let total_liquidity = self.total_supply;
@@ -169,11 +300,12 @@ mod exchange {
 
                 assert!(max_tokens >= token_ammount && liquidity_minted >= min_liquidity);
 
-                let caller_luquidity = self.balances.get(&caller).unwrap_or(&0u128).clone();
-                self.balances.insert(caller, caller_luquidity + liquidity_minted);
+                if let Err(error) = self.token_transfer_from(caller, self.exchange_account_id, token_ammount) {
+                    self.env().transfer(caller, transfferred_value).expect("transfer error");
+                    return Err(error)
+                }
 
-                self.total_supply = total_liquidity + liquidity_minted;
-                assert!(self.token_transfer_from(caller, self.exchange_account_id, token_ammount));
+                self.mint_liquidity(caller, liquidity_minted);
 
                 self.env().emit_event( AddLiquidity {
                     provider : caller,
@@ -181,21 +313,17 @@ mod exchange {
                     token_ammount: token_ammount,
                 });
 
-                self.env().emit_event( Transfer {
-                    from : AccountId::default(),
-                    to : caller,
-                    value : liquidity_minted,
-                });
-
-                liquidity_minted
+                Ok(liquidity_minted)
             }else{
                 let token_ammount = max_tokens;
 
-                let initial_liquidity = self.env().balance();
-                self.total_supply = initial_liquidity;
-                self.balances.insert(caller, initial_liquidity);
+                if let Err(error) = self.token_transfer_from(caller, self.exchange_account_id, token_ammount) {
+                    self.env().transfer(caller, transfferred_value).expect("transfer error");
+                    return Err(error)
+                }
 
-                assert!(self.token_transfer_from(caller, self.exchange_account_id, token_ammount));
+                let initial_liquidity = self.env().balance();
+                self.mint_liquidity(caller, initial_liquidity);
 
                 self.env().emit_event( AddLiquidity {
                     provider : caller,
@@ -203,14 +331,74 @@ mod exchange {
                     token_ammount: token_ammount,
                 });
 
-                self.env().emit_event( Transfer {
-                    from : AccountId::default(),
-                    to : caller,
-                    value : initial_liquidity,
-                });
+                Ok(initial_liquidity)
+            }
+        }
 
-                initial_liquidity 
+        /// Same as `add_liquidity`, but for tokens which take a fee on transfer or rebase. The liquidity minted
+        /// is limited by the amount of tokens the Exchange actually received.
+        /// 
+        /// Return The amount of lp minted 
+        /// 
+        /// #Params
+        /// 
+        /// - `min_liquidity`:  Minimum number of lp sender will mint if total lp supply is greater than 0.
+        /// - `max_tokens`: Maximum number of tokens deposited, before the transfer fee. Deposits max amount if total lp supply is 0.
+        /// - `deadline`: Time after which this transaction can no longer be executed.
+        #[ink(message, payable)]
+        pub fn add_liquidity_supporting_fee(&mut self, min_liquidity: u128, max_tokens: u128, deadline: Timestamp) ->Result<u128>{
+            let transfferred_value = self.env().transferred_balance();
+            assert!(deadline >= self.env().block_timestamp() && max_tokens > 0 && transfferred_value > 0);
+            let total_liquidity = self.total_supply;
+            let caller = self.env().caller();
+            let dot_reserve = self.env().balance() - transfferred_value;
+            let token_reserve = self.token_balance();
+            let token_ammount = if total_liquidity > 0 {
+                assert!(min_liquidity > 0);
+                transfferred_value * token_reserve / dot_reserve + 1
+            }else{
+                max_tokens
+            };
+            assert!(max_tokens >= token_ammount);
+
+            if let Err(error) = self.token_transfer_from(caller, self.exchange_account_id, token_ammount) {
+                self.env().transfer(caller, transfferred_value).expect("transfer error");
+                return Err(error)
             }
+            let token_balance = self.token_balance();
+            let (tokens_received, liquidity_minted) = if total_liquidity > 0 {
+                Self::liquidity_minted_supporting_fee(transfferred_value, dot_reserve, token_reserve, token_balance, total_liquidity)
+            }else{
+                (token_balance.saturating_sub(token_reserve), self.env().balance())
+            };
+            assert!(tokens_received > 0);
+            assert!(liquidity_minted >= min_liquidity);
+
+            self.mint_liquidity(caller, liquidity_minted);
+
+            self.env().emit_event( AddLiquidity {
+                provider : caller,
+                dot_ammount : transfferred_value,
+                token_ammount : tokens_received,
+            });
+
+            Ok(liquidity_minted)
+        }
+
+        /// Return the tokens received and the liquidity minted for a deposit of tokens which take a fee on transfer, from
+        /// the token balance of the Exchange before (`token_reserve`) and after the transfer.
+        pub fn liquidity_minted_supporting_fee(dot_ammount : Balance, dot_reserve : Balance, token_reserve : Balance,
+            token_balance : Balance, total_liquidity : Balance) -> (Balance, Balance){
+            let tokens_received = token_balance.saturating_sub(token_reserve);
+            (tokens_received, Self::liquidity_minted(dot_ammount, tokens_received, dot_reserve, token_reserve, total_liquidity))
+        }
+
+        /// Return the liquidity minted for a deposit, limited by the smaller of the Dot and the token share of the reserves.
+        pub fn liquidity_minted(dot_ammount : Balance, token_ammount : Balance, dot_reserve : Balance, token_reserve : Balance,
+            total_liquidity : Balance) -> Balance{
+            let dot_liquidity = dot_ammount * total_liquidity / dot_reserve;
+            let token_liquidity = token_ammount * total_liquidity / token_reserve;
+            core::cmp::min(dot_liquidity, token_liquidity)
         }
 
         /// Burn lp tokens to withdraw Dot and Tokens at current ratio.
@@ -223,8 +411,8 @@ mod exchange {
         /// - `min_dot`: Minimum Dot withdrawn.
         /// - `min_tokens`: Minimum Tokens withdrawn
         /// - `deadline`: Time after which this transaction can no longer be executed.
-        #[ink(message)]
-        pub fn remove_liquidity(&mut self, ammount : Balance,min_dot : Balance, min_token : Balance, deadline : Timestamp ) ->(Balance, Balance){
+        #[ink(message, selector = "0x78025f59")]
+        pub fn remove_liquidity(&mut self, ammount : Balance,min_dot : Balance, min_token : Balance, deadline : Timestamp ) ->Result<(Balance, Balance)>{
             assert!((ammount > 0 && deadline >= self.env().block_timestamp()) && (min_dot > 0  && min_token > 0));
             let caller = self.env().caller();
             let total_liquidity = self.total_supply;
@@ -236,14 +424,13 @@ mod exchange {
             let token_ammount = ammount * token_reserve / total_liquidity;
 
             assert!((dot_ammount > min_dot) && (token_ammount > min_token));
-            let caller_luquidity = *self.balances.get(&caller).unwrap();
-            assert!(caller_luquidity >= ammount);
+            assert!(self.balance_of(caller) >= ammount);
+
+            self.token_transfer(caller, token_ammount)?;
 
-            self.balances.insert(caller, caller_luquidity - ammount);
-            self.total_supply = total_liquidity - ammount;
+            self.burn_liquidity(caller, ammount);
 
             self.env().transfer(caller, dot_ammount).expect("transfer error");
-            self.token_transfer(caller, token_ammount);
 
             self.env().emit_event( RemoveLiquidity {
                 provider : caller,
@@ -251,43 +438,335 @@ mod exchange {
                 token_ammount,
             });
 
+            Ok((dot_ammount, token_ammount))
+        }   
+
+        fn mint_liquidity(&mut self, to : AccountId, value : Balance){
+            if let Some((lp_token, lp_token_id)) = self.lp_token {
+                //mint selector in multi_token 0x23f2c9ae
+                let selector_mint = call::Selector::new([0x23, 0xf2, 0xc9, 0xae]);
+                let minted = build_call::<DefaultEnvironment>()
+                    .callee(lp_token)
+                    .gas_limit(self.gas_limit / 2)
+                    .transferred_value(0)
+                    .exec_input(
+                        ExecutionInput::new(selector_mint.into())
+                        .push_arg(&to)
+                        .push_arg(lp_token_id)
+                        .push_arg(value),
+                    ).returns::<ReturnType<bool>>().fire().unwrap();
+                assert!(minted);
+                self.total_supply += value;
+                return
+            }
+            self.update_fees(to);
+            let to_liquidity = self.balances.get(&to).unwrap_or(&0u128).clone();
+            self.balances.insert(to, to_liquidity + value);
+            self.update_holder(to);
+            self.total_supply += value;
+
             self.env().emit_event( Transfer {
-                from :  caller,
+                from : AccountId::default(),
+                to,
+                value,
+            });
+        }
+
+        fn burn_liquidity(&mut self, from : AccountId, value : Balance){
+            if let Some((lp_token, lp_token_id)) = self.lp_token {
+                //burn selector in multi_token 0xee56cc65
+                let selector_burn = call::Selector::new([0xee, 0x56, 0xcc, 0x65]);
+                let burned = build_call::<DefaultEnvironment>()
+                    .callee(lp_token)
+                    .gas_limit(self.gas_limit / 2)
+                    .transferred_value(0)
+                    .exec_input(
+                        ExecutionInput::new(selector_burn.into())
+                        .push_arg(&from)
+                        .push_arg(lp_token_id)
+                        .push_arg(value),
+                    ).returns::<ReturnType<bool>>().fire().unwrap();
+                assert!(burned);
+                self.total_supply -= value;
+                return
+            }
+            self.update_fees(from);
+            let from_liquidity = self.balances.get(&from).unwrap_or(&0u128).clone();
+            assert!(from_liquidity >= value);
+            self.balances.insert(from, from_liquidity - value);
+            self.update_holder(from);
+            self.total_supply -= value;
+
+            self.env().emit_event( Transfer {
+                from,
                 to : AccountId::default(),
-                value : ammount,
+                value,
             });
+        }
 
-            (dot_ammount, token_ammount)
-        }   
+        /// Called by the token (self.token) after `transfer_and_call` transferred tokens to this contract,
+        /// so a single token transfer can swap or add liquidity.
+        /// 
+        /// Return true if the tokens are accepted, the whole transfer is reverted otherwise.
+        /// 
+        /// #Params
+        /// 
+        /// - `from`: The account which sent the tokens.
+        /// - `value`: Amount of tokens received.
+        /// - `data`: SCALE encoded `TokenReceivedAction`.
+        /// NOTE: The selector is `common::receiver::ON_TOKEN_RECEIVED_SELECTOR`, which the token calls.
+        #[ink(message, payable, selector = "0xe1936155")]
+        pub fn on_token_received(&mut self, from : AccountId, value : Balance, data : Vec<u8>) -> bool{
+            assert!(self.env().caller() == self.token);
+            let action = <TokenReceivedAction as scale::Decode>::decode(&mut &data[..]).expect("invalid token received data");
+            match action {
+                TokenReceivedAction::TokenToDot { min_dot, deadline, recipient } => {
+                    assert!(recipient != self.exchange_account_id && recipient != AccountId::default());
+                    self.token_to_dot_received(value, min_dot, deadline, from, recipient);
+                }
+                TokenReceivedAction::AddLiquidity { min_liquidity, deadline } => {
+                    self.add_liquidity_received(value, min_liquidity, deadline, from);
+                }
+            }
+            true
+        }
+
+        fn token_to_dot_received(&mut self, tokens_sold : Balance, min_dot : Balance, deadline : Timestamp, buyer: AccountId, recipient: AccountId){
+            assert!(deadline >= self.env().block_timestamp() && tokens_sold > 0 && min_dot > 0);
+            assert!(self.env().transferred_balance() == 0);
+            //The tokens are already transferred, the reserve before the sale excludes them.
+            let token_reserve = self.token_balance() - tokens_sold;
+            let dot_bought = self.input_price(tokens_sold, token_reserve, self.env().balance());
+            assert!(dot_bought >= min_dot);
+
+            self.env().transfer(recipient, dot_bought).expect("transfer error");
+
+            self.accrue_swap_fee(0, tokens_sold);
+            self.env().emit_event( DotPurchase {
+                buyer,
+                tokens_sold,
+                dot_bought,
+            });
+        }
+
+        fn add_liquidity_received(&mut self, tokens_received : Balance, min_liquidity : Balance, deadline : Timestamp, provider : AccountId){
+            let transfferred_value = self.env().transferred_balance();
+            assert!(deadline >= self.env().block_timestamp() && tokens_received > 0 && transfferred_value > 0);
+            let total_liquidity = self.total_supply;
+            if total_liquidity > 0{
+                assert!(min_liquidity > 0);
+                let dot_reserve = self.env().balance() - transfferred_value;
+                let token_reserve = self.token_balance() - tokens_received;
+                let token_ammount = transfferred_value * token_reserve / dot_reserve + 1;
+                let liquidity_minted = transfferred_value * total_liquidity / dot_reserve;
+
+                assert!(tokens_received >= token_ammount && liquidity_minted >= min_liquidity);
+                if tokens_received > token_ammount {
+                    self.token_transfer(provider, tokens_received - token_ammount).expect("token transfer failed");
+                }
+
+                self.mint_liquidity(provider, liquidity_minted);
+
+                self.env().emit_event( AddLiquidity {
+                    provider,
+                    dot_ammount : transfferred_value,
+                    token_ammount,
+                });
+            }else{
+                let initial_liquidity = self.env().balance();
+                self.mint_liquidity(provider, initial_liquidity);
+
+                self.env().emit_event( AddLiquidity {
+                    provider,
+                    dot_ammount : transfferred_value,
+                    token_ammount : tokens_received,
+                });
+            }
+        }
 
         pub fn input_price(&self, input_amount : Balance, input_reserve : Balance, output_reserve : Balance) -> Balance{
             assert!(input_reserve > 0 && output_reserve > 0);
-            let input_ammount_with_fee = input_amount * 997u128;
+            let input_ammount_with_fee = input_amount * (FEE_DENOMINATOR - self.fee_bps as u128);
             let numerator = input_ammount_with_fee * output_reserve;
-            let denominator = (input_reserve * 1000u128) + input_ammount_with_fee;
+            let denominator = (input_reserve * FEE_DENOMINATOR) + input_ammount_with_fee;
             numerator / denominator
         }
 
         pub fn output_price(&self, output_ammount: Balance, input_reserve : Balance, output_reserve : Balance) -> Balance{
             assert!(input_reserve > 0 && output_reserve > 0);
-            let numerator = input_reserve * output_ammount * 1000u128;
-            let denomiator = (output_reserve - output_ammount) *997u128;
+            let numerator = input_reserve * output_ammount * FEE_DENOMINATOR;
+            let denomiator = (output_reserve - output_ammount) * (FEE_DENOMINATOR - self.fee_bps as u128);
             numerator / denomiator + 1
         }
   
+        /// Return the fees earned by a liquidity provider since it first provided liquidity, in Dot and in tokens.
+        /// They are the fee taken on the input of each swap, shared by the liquidity at the time of the swap.
+        ///
+        /// NOTE: The fees are part of the reserves, they are withdrawn with the liquidity by `remove_liquidity`.
+        /// They are not tracked if the liquidity lives in a MultiToken contract.
+        ///
+        /// #params
+        ///
+        /// -`owner`: The account of a liquidity provider
+        #[ink(message)]
+        pub fn fees_earned(&self, owner : AccountId) -> (Balance, Balance){
+            let liquidity = self.balances.get(&owner).unwrap_or(&0u128).clone();
+            let (dot_snapshot, token_snapshot) = self.fee_snapshots.get(&owner).cloned().unwrap_or((0, 0));
+            let (dot_accrued, token_accrued) = self.fees_accrued.get(&owner).cloned().unwrap_or((0, 0));
+            (dot_accrued + Self::fees_owed(liquidity, self.dot_fee_per_share, dot_snapshot),
+                token_accrued + Self::fees_owed(liquidity, self.token_fee_per_share, token_snapshot))
+        }
+
+        /// Return the fees earned by `liquidity` shares while the fees per share grew from `snapshot` to `fee_per_share`.
+        pub fn fees_owed(liquidity : Balance, fee_per_share : u128, snapshot : u128) -> Balance{
+            (U256::from(liquidity) * U256::from(fee_per_share - snapshot) / U256::from(FEE_PER_SHARE_PRECISION)).as_u128()
+        }
+
+        /// Return the growth of the fees per share for a swap input of `input_amount` at `fee_bps`, shared by `total_liquidity`.
+        pub fn fee_per_share_increase(input_amount : Balance, fee_bps : u32, total_liquidity : Balance) -> u128{
+            (U256::from(input_amount) * U256::from(fee_bps) * U256::from(FEE_PER_SHARE_PRECISION)
+                / (U256::from(total_liquidity) * U256::from(FEE_DENOMINATOR))).as_u128()
+        }
+
+        /// Add the fee taken on a swap input to the fees per share, then adjust the dynamic fee to the price after the swap.
+        fn accrue_swap_fee(&mut self, dot_input : Balance, token_input : Balance){
+            if self.total_supply > 0 {
+                self.dot_fee_per_share += Self::fee_per_share_increase(dot_input, self.fee_bps, self.total_supply);
+                self.token_fee_per_share += Self::fee_per_share_increase(token_input, self.fee_bps, self.total_supply);
+            }
+            self.update_dynamic_fee();
+        }
+
+        /// Return the swap fee in basis points of the input.
+        #[ink(message, selector = "0xd8e36575")]
+        pub fn fee_bps(&self) -> u32{
+            self.fee_bps
+        }
+
+        /// Return the minimum and maximum dynamic fee in basis points, None if the fee is flat.
+        #[ink(message)]
+        pub fn dynamic_fee(&self) -> Option<(u32, u32)>{
+            self.dynamic_fee
+        }
+
+        /// Return the EMA of the absolute price changes between swaps, in millionths of the price.
+        #[ink(message)]
+        pub fn volatility(&self) -> u128{
+            self.volatility
+        }
+
+        /// Scale the swap fee between `min_fee_bps` and `max_fee_bps` with the volatility of the price, or go back to the
+        /// flat 0.3% fee with `None`.
+        ///
+        /// NOTE: Only the Factory can set it.
+        ///
+        /// #Params
+        ///
+        /// - `dynamic_fee`: The minimum and maximum fee in basis points, at most MAX_FEE_BPS.
+        #[ink(message, selector = "0x7f7c1be1")]
+        pub fn set_dynamic_fee(&mut self, dynamic_fee : Option<(u32, u32)>){
+            assert!(self.env().caller() == self.factory);
+            let fee_bps = match dynamic_fee {
+                Some((min_fee_bps, max_fee_bps)) => {
+                    assert!(min_fee_bps <= max_fee_bps && max_fee_bps <= MAX_FEE_BPS);
+                    Self::dynamic_fee_bps(self.volatility, min_fee_bps, max_fee_bps)
+                }
+                None => {
+                    //The volatility is measured again once the dynamic fee is enabled again.
+                    self.volatility = 0;
+                    self.last_price = 0;
+                    DEFAULT_FEE_BPS
+                }
+            };
+            self.dynamic_fee = dynamic_fee;
+            self.set_fee_bps(fee_bps);
+        }
+
+        /// Return the volatility after a swap moved the price from `last_price` to `price`.
+        pub fn next_volatility(volatility : u128, last_price : u128, price : u128) -> u128{
+            let price_change = if price > last_price { price - last_price } else { last_price - price };
+            let price_change = (U256::from(core::cmp::min(price_change, last_price)) * U256::from(PRICE_CHANGE_DENOMINATOR)
+                / U256::from(last_price)).as_u128();
+            (volatility * (VOLATILITY_EMA_PERIOD - 1) + price_change) / VOLATILITY_EMA_PERIOD
+        }
+
+        /// Return the fee between `min_fee_bps` and `max_fee_bps` in proportion to the volatility, up to VOLATILITY_AT_MAX_FEE.
+        pub fn dynamic_fee_bps(volatility : u128, min_fee_bps : u32, max_fee_bps : u32) -> u32{
+            let volatility = core::cmp::min(volatility, VOLATILITY_AT_MAX_FEE);
+            min_fee_bps + ((max_fee_bps - min_fee_bps) as u128 * volatility / VOLATILITY_AT_MAX_FEE) as u32
+        }
+
+        /// Return the Dot price of a token for the reserves, multiplied by PRICE_PRECISION and capped at u128::MAX.
+        pub fn spot_price(dot_reserve : Balance, token_reserve : Balance) -> u128{
+            let price = U256::from(dot_reserve) * U256::from(PRICE_PRECISION) / U256::from(token_reserve);
+            if price > U256::from(u128::MAX) { u128::MAX } else { price.as_u128() }
+        }
+
+        /// Measure the price change of the last swap, and scale the fee of the next swaps with the volatility.
+        fn update_dynamic_fee(&mut self){
+            if self.dynamic_fee.is_none() {
+                return
+            }
+            let token_reserve = self.token_balance();
+            self.adjust_dynamic_fee(self.env().balance(), token_reserve);
+        }
+
+        /// Adjust the dynamic fee to the reserves after a swap. Does nothing with the flat fee.
+        pub fn adjust_dynamic_fee(&mut self, dot_reserve : Balance, token_reserve : Balance){
+            let (min_fee_bps, max_fee_bps) = match self.dynamic_fee {
+                Some(bounds) => bounds,
+                None => return,
+            };
+            if token_reserve == 0 {
+                return
+            }
+            let price = Self::spot_price(dot_reserve, token_reserve);
+            if price == 0 {
+                return
+            }
+            if self.last_price > 0 {
+                self.volatility = Self::next_volatility(self.volatility, self.last_price, price);
+                self.set_fee_bps(Self::dynamic_fee_bps(self.volatility, min_fee_bps, max_fee_bps));
+            }
+            self.last_price = price;
+        }
+
+        fn set_fee_bps(&mut self, fee_bps : u32){
+            if fee_bps == self.fee_bps {
+                return
+            }
+            self.fee_bps = fee_bps;
+
+            self.env().emit_event( FeeAdjusted {
+                fee_bps,
+                volatility : self.volatility,
+            });
+        }
+
+        /// Move the fees earned by `account` since its last snapshot to its accrued fees, before its liquidity changes.
+        fn update_fees(&mut self, account : AccountId){
+            let fees = self.fees_earned(account);
+            self.fees_accrued.insert(account, fees);
+            self.fee_snapshots.insert(account, (self.dot_fee_per_share, self.token_fee_per_share));
+        }
+
         fn dot_to_token_input(&mut self, dot_sold: Balance, min_tokens: Balance, deadline: Timestamp, 
-                                buyer: AccountId, recipient: AccountId) -> Balance{
+                                buyer: AccountId, recipient: AccountId) -> Result<Balance>{
             assert!(deadline >= self.env().block_timestamp() && (dot_sold > 0) && (min_tokens > 0));
             let token_reserve = self.token_balance();
             let tokens_bought = self.input_price(dot_sold, self.env().balance() - dot_sold, token_reserve);
             assert!(tokens_bought >= min_tokens);
-            assert!(self.token_transfer(recipient, tokens_bought));
+            if let Err(error) = self.token_transfer(recipient, tokens_bought) {
+                self.env().transfer(buyer, dot_sold).expect("transfer error");
+                return Err(error)
+            }
+            self.accrue_swap_fee(dot_sold, 0);
             self.env().emit_event( TokenPurchase {
                 buyer,
                 dot_sold,
                 tokens_bought,
             });
-            tokens_bought
+            Ok(tokens_bought)
         }
 
         /// Convert Dot to Tokens.
@@ -299,7 +778,7 @@ mod exchange {
         /// - `min_token`: Minimum Tokens bought
         /// - `deadline ` : Time after which this transaction can no longer be executed
         #[ink(message, payable)]
-        pub fn dot_to_token_swap_input(&mut self, min_tokens : Balance,deadline :Timestamp) ->Balance{
+        pub fn dot_to_token_swap_input(&mut self, min_tokens : Balance,deadline :Timestamp) ->Result<Balance>{
             let caller = self.env().caller();
             let transferred_balance = self.env().transferred_balance();
             self.dot_to_token_input(transferred_balance, min_tokens, deadline, caller, caller)
@@ -315,28 +794,32 @@ mod exchange {
         /// - `deadline ` : Time after which this transaction can no longer be executed
         /// - 'recipient' : AcccountId will get the transferred token
         #[ink(message, payable, selector = "0xa0a8e619")]
-        pub fn dot_to_token_transfer_input(&mut self,min_tokens : Balance, deadline : Timestamp, recipient: AccountId) ->Balance{
+        pub fn dot_to_token_transfer_input(&mut self,min_tokens : Balance, deadline : Timestamp, recipient: AccountId) ->Result<Balance>{
             assert!(recipient != self.exchange_account_id && recipient != AccountId::default());
             let transferred_balance = self.env().transferred_balance();
             self.dot_to_token_input(transferred_balance, min_tokens, deadline, self.env().caller(), recipient)
         }
         
-        fn dot_to_token_output(&mut self, tokens_bought : Balance, max_dot :Balance, deadline : Timestamp, buyer: AccountId, recipient: AccountId) -> Balance{
+        fn dot_to_token_output(&mut self, tokens_bought : Balance, max_dot :Balance, deadline : Timestamp, buyer: AccountId, recipient: AccountId) -> Result<Balance>{
             assert!(deadline >= self.env().block_timestamp() && tokens_bought >0 && max_dot > 0);
 
             let tokens_reserve = self.token_balance();
             let dot_sold = self.output_price(tokens_bought, self.env().balance() - max_dot, tokens_reserve);
+            if let Err(error) = self.token_transfer(recipient, tokens_bought) {
+                self.env().transfer(buyer, max_dot).expect("transfer error");
+                return Err(error)
+            }
             let dot_refund = max_dot - dot_sold;
             if dot_refund > 0 {
                 self.env().transfer(buyer, dot_refund).expect("transfer error");
             }
-            assert!(self.token_transfer(recipient, tokens_bought));
+            self.accrue_swap_fee(dot_sold, 0);
             self.env().emit_event( TokenPurchase {
                 buyer,
                 dot_sold,
                 tokens_bought,
             });
-            dot_sold
+            Ok(dot_sold)
         }
 
         /// Convert Dot to Tokens
@@ -348,7 +831,7 @@ mod exchange {
         /// - `tokens_bought`: Amount of tokens bought.
         /// - `deadline ` : Time after which this transaction can no longer be executed
         #[ink(message, payable)]
-        pub fn dot_to_token_swap_output(&mut self, tokens_bought : Balance, deadline : Timestamp) -> Balance{
+        pub fn dot_to_token_swap_output(&mut self, tokens_bought : Balance, deadline : Timestamp) -> Result<Balance>{
             let caller = self.env().caller(); 
             let transferred_balance = self.env().transferred_balance();
             self.dot_to_token_output(tokens_bought,transferred_balance, deadline, caller, caller)
@@ -364,28 +847,29 @@ mod exchange {
         /// - `deadline ` : Time after which this transaction can no longer be executed
         /// - `recipient`: AcccountId will get the transferred token
         #[ink(message, payable, selector="0x0783f403")]
-        pub fn dot_to_token_transfer_output(&mut self, tokens_bought : Balance, deadline : Timestamp, recipient: AccountId) ->Balance{
+        pub fn dot_to_token_transfer_output(&mut self, tokens_bought : Balance, deadline : Timestamp, recipient: AccountId) ->Result<Balance>{
             assert!(recipient != self.exchange_account_id && recipient != AccountId::default());
             let transferred_balance = self.env().transferred_balance();
             self.dot_to_token_output(tokens_bought, transferred_balance, deadline,  self.env().caller(), recipient)
         }
 
-        fn token_to_dot_input(&mut self, tokens_sold : Balance, min_dot : Balance, deadline : Timestamp, buyer: AccountId, recipient: AccountId)->Balance{
+        fn token_to_dot_input(&mut self, tokens_sold : Balance, min_dot : Balance, deadline : Timestamp, buyer: AccountId, recipient: AccountId)->Result<Balance>{
             assert!(deadline >= self.env().block_timestamp() && tokens_sold > 0 && min_dot > 0);
             let token_reserve = self.token_balance();
             let dot_bought = self.input_price(tokens_sold, token_reserve, self.env().balance());
             assert!(dot_bought > min_dot);
 
-            self.env().transfer(recipient, dot_bought).expect("transfer error");
+            self.token_transfer_from(buyer, self.exchange_account_id, tokens_sold)?;
 
-            assert!(self.token_transfer_from(buyer, self.exchange_account_id, tokens_sold));
+            self.env().transfer(recipient, dot_bought).expect("transfer error");
 
+            self.accrue_swap_fee(0, tokens_sold);
             self.env().emit_event( DotPurchase {
                 buyer,
                 tokens_sold,
                 dot_bought,
             });
-            dot_bought
+            Ok(dot_bought)
         }
 
         /// Convert Tokens to Dot.
@@ -395,7 +879,7 @@ mod exchange {
         /// - `min_dot`: Minimum Dot purchased.
         /// - `deadline`: Time after which this transaction can no longer be executed
         #[ink(message)]
-        pub fn token_to_dot_swap_input(&mut self, tokens_sold : Balance, min_dot : Balance, deadline : Timestamp) -> Balance{
+        pub fn token_to_dot_swap_input(&mut self, tokens_sold : Balance, min_dot : Balance, deadline : Timestamp) -> Result<Balance>{
             let caller = self.env().caller();
             self.token_to_dot_input(tokens_sold, min_dot, deadline, caller, caller)
         }
@@ -409,27 +893,87 @@ mod exchange {
         /// - `deadline`: Time after which this transaction can no longer be executed.
         /// - `recipient`: AcccountId will get the transferred dot.
         #[ink(message)]
-        pub fn token_to_dot_transfer_input(&mut self, tokens_sold : Balance, min_dot : Balance, deadline : Timestamp, recipient: AccountId) ->Balance{
+        pub fn token_to_dot_transfer_input(&mut self, tokens_sold : Balance, min_dot : Balance, deadline : Timestamp, recipient: AccountId) ->Result<Balance>{
             assert!(self.exchange_account_id != recipient);
             self.token_to_dot_input(tokens_sold, min_dot, deadline, self.env().caller(), recipient)
         }
 
-        fn token_to_dot_output(&mut self, dot_bought : Balance, max_tokens : Balance, deadline : Timestamp, buyer: AccountId, recipient: AccountId)->Balance{
+        fn token_to_dot_input_supporting_fee(&mut self, tokens_sold : Balance, min_dot : Balance, deadline : Timestamp, buyer: AccountId, recipient: AccountId)->Result<Balance>{
+            assert!(deadline >= self.env().block_timestamp() && tokens_sold > 0 && min_dot > 0);
+            let token_reserve = self.token_balance();
+
+            self.token_transfer_from(buyer, self.exchange_account_id, tokens_sold)?;
+
+            let token_balance = self.token_balance();
+            let (tokens_received, dot_bought) = self.input_price_supporting_fee(token_reserve, token_balance, self.env().balance());
+            assert!(dot_bought >= min_dot);
+
+            self.env().transfer(recipient, dot_bought).expect("transfer error");
+
+            self.accrue_swap_fee(0, tokens_received);
+            self.env().emit_event( DotPurchase {
+                buyer,
+                tokens_sold : tokens_received,
+                dot_bought,
+            });
+            Ok(dot_bought)
+        }
+
+        /// Return the tokens received and the Dot bought by a sale of tokens which take a fee on transfer, from the token
+        /// balance of the Exchange before (`token_reserve`) and after the transfer. The price is on the tokens actually
+        /// received, not on the amount sent.
+        pub fn input_price_supporting_fee(&self, token_reserve : Balance, token_balance : Balance, dot_reserve : Balance) -> (Balance, Balance){
+            let tokens_received = token_balance.saturating_sub(token_reserve);
+            if tokens_received == 0 {
+                return (0, 0)
+            }
+            (tokens_received, self.input_price(tokens_received, token_reserve, dot_reserve))
+        }
+
+        /// Convert Tokens which take a fee on transfer or rebase to Dot. The Dot bought is priced on the amount of tokens
+        /// the Exchange actually received.
+        /// 
+        /// #params:
+        /// - `tokens_sold`:Amount of Tokens sold, before the transfer fee.
+        /// - `min_dot`: Minimum Dot purchased.
+        /// - `deadline`: Time after which this transaction can no longer be executed
+        #[ink(message)]
+        pub fn token_to_dot_swap_input_supporting_fee(&mut self, tokens_sold : Balance, min_dot : Balance, deadline : Timestamp) -> Result<Balance>{
+            let caller = self.env().caller();
+            self.token_to_dot_input_supporting_fee(tokens_sold, min_dot, deadline, caller, caller)
+        }
+
+        /// Convert Tokens which take a fee on transfer or rebase to Dot and transfer the Dot to recipient.
+        /// 
+        /// #params:
+        /// 
+        /// - `tokens_sold`:Amount of Tokens sold, before the transfer fee.
+        /// - `min_dot`: Minimum Dot purchased.
+        /// - `deadline`: Time after which this transaction can no longer be executed.
+        /// - `recipient`: AcccountId will get the transferred dot.
+        #[ink(message)]
+        pub fn token_to_dot_transfer_input_supporting_fee(&mut self, tokens_sold : Balance, min_dot : Balance, deadline : Timestamp, recipient: AccountId) ->Result<Balance>{
+            assert!(self.exchange_account_id != recipient);
+            self.token_to_dot_input_supporting_fee(tokens_sold, min_dot, deadline, self.env().caller(), recipient)
+        }
+
+        fn token_to_dot_output(&mut self, dot_bought : Balance, max_tokens : Balance, deadline : Timestamp, buyer: AccountId, recipient: AccountId)->Result<Balance>{
             assert!(deadline >= self.env().block_timestamp() && dot_bought > 0);
             let token_reserve = self.token_balance();
             let tokens_sold = self.output_price(dot_bought, token_reserve, self.env().balance());
             assert!(max_tokens >= tokens_sold);
 
-            self.env().transfer(recipient, dot_bought).expect("transfer error");
+            self.token_transfer_from(buyer, self.exchange_account_id, tokens_sold)?;
 
-            assert!(self.token_transfer_from(buyer, self.exchange_account_id, tokens_sold));
+            self.env().transfer(recipient, dot_bought).expect("transfer error");
 
+            self.accrue_swap_fee(0, tokens_sold);
             self.env().emit_event( DotPurchase {
                 buyer,
                 tokens_sold,
                 dot_bought,
             });
-            tokens_sold
+            Ok(tokens_sold)
         }
 
         /// Convert Tokens to Dot.
@@ -442,7 +986,7 @@ mod exchange {
         /// - `max_tokens`: Maximum Tokens sold.
         /// - `deadline`: Time after which this transaction can no longer be executed.
         #[ink(message)]
-        pub fn token_to_dot_swap_output(&mut self, dot_bought : Balance, max_tokens : Balance, deadline : Timestamp)->Balance{
+        pub fn token_to_dot_swap_output(&mut self, dot_bought : Balance, max_tokens : Balance, deadline : Timestamp)->Result<Balance>{
             let caller = self.env().caller();
             self.token_to_dot_output(dot_bought, max_tokens, deadline, caller, caller)
         }
@@ -458,21 +1002,21 @@ mod exchange {
         /// - `deadline`: Time after which this transaction can no longer be executed.
         /// - `recipient`: AcccountId will get the transferred dot.
         #[ink(message)]
-        pub fn token_to_dot_transfer_output(&mut self, dot_bought : Balance, max_tokens : Balance, deadline : Timestamp, recipient: AccountId) ->Balance{
+        pub fn token_to_dot_transfer_output(&mut self, dot_bought : Balance, max_tokens : Balance, deadline : Timestamp, recipient: AccountId) ->Result<Balance>{
             assert!(recipient != self.exchange_account_id && recipient != AccountId::default());
             let caller = self.env().caller();
             self.token_to_dot_output(dot_bought, max_tokens, deadline, caller, recipient)
         }
 
         fn token_to_token_input(&mut self, tokens_sold : Balance, min_tokens_bought : Balance, min_dot_bought : Balance,
-             deadline : Timestamp, buyer : AccountId, recipient :AccountId, exchange_addr : AccountId) ->Balance{
+             deadline : Timestamp, buyer : AccountId, recipient :AccountId, exchange_addr : AccountId) ->Result<Balance>{
             assert!(deadline >= self.env().block_timestamp() && tokens_sold > 0 && (min_dot_bought > 0 && min_tokens_bought > 0));
 
             let token_reserve =self.token_balance();
             let dot_bought =  self.input_price(tokens_sold, token_reserve, self.env().balance());
             
             assert!(dot_bought >= min_dot_bought);
-            assert!(self.token_transfer_from(buyer, self.exchange_account_id, tokens_sold));
+            self.token_transfer_from(buyer, self.exchange_account_id, tokens_sold)?;
             
             //Exchange(exchange_addr) call dot_to_token_transfer_input function //0xa0a8e619
             let selector_balance_of = call::Selector::new([0xa0, 0xa8,0xe6, 0x19]);
@@ -482,15 +1026,24 @@ mod exchange {
                 .transferred_value(dot_bought)
                 .exec_input(
                     ExecutionInput::new(selector_balance_of.into()).push_arg(min_tokens_bought).push_arg(deadline).push_arg(recipient),
-                ).returns::<ReturnType<Balance>>().fire().unwrap();
+                ).returns::<ReturnType<Result<Balance>>>().fire().unwrap();
+            //The other exchange refunded the dot, give the sold tokens back to the buyer.
+            let tokens_bought = match tokens_bought {
+                Ok(tokens_bought) => tokens_bought,
+                Err(error) => {
+                    self.token_transfer(buyer, tokens_sold).expect("token transfer failed");
+                    return Err(error)
+                }
+            };
             
+            self.accrue_swap_fee(0, tokens_sold);
             self.env().emit_event( DotPurchase {
                 buyer,
                 tokens_sold,
                 dot_bought,
             });
 
-            tokens_bought
+            Ok(tokens_bought)
         }
         
         /// Convert Tokens (self.token) to Tokens (token_addr).
@@ -505,7 +1058,7 @@ mod exchange {
         /// - `token_addr`: token_addr The address of the token being purchased.
         #[ink(message)]
         pub fn token_to_token_swap_input(&mut self,tokens_sold: Balance, min_tokens_bought : Balance, 
-            min_dot_bought : Balance, deadline : Timestamp, token_addr : AccountId) ->Balance{
+            min_dot_bought : Balance, deadline : Timestamp, token_addr : AccountId) ->Result<Balance>{
                 let exchange_addr = self.exchange_from_factory(token_addr);
                 let caller = self.env().caller();
                 self.token_to_token_input(tokens_sold, min_tokens_bought, min_dot_bought, deadline,
@@ -525,14 +1078,14 @@ mod exchange {
         /// - `recipient`: AcccountId will get the transferred dot.
         #[ink(message)]
         pub fn token_to_token_transfer_input(&mut self,tokens_sold: Balance, min_tokens_bought : Balance, 
-            min_dot_bought : Balance, deadline : Timestamp, recipient : AccountId,token_addr : AccountId) ->Balance{
+            min_dot_bought : Balance, deadline : Timestamp, recipient : AccountId,token_addr : AccountId) ->Result<Balance>{
                 let exchange_addr = self.exchange_from_factory(token_addr);
                 self.token_to_token_input(tokens_sold, min_tokens_bought, min_dot_bought, deadline,
                     self.env().caller(), recipient, exchange_addr)
         }
 
         fn token_to_token_output(&mut self, tokens_bought : Balance, max_tokens_sold : Balance, max_dot_sold : Balance,
-            deadline : Timestamp, buyer : AccountId, recipient : AccountId, exchange_addr : AccountId) -> Balance{
+            deadline : Timestamp, buyer : AccountId, recipient : AccountId, exchange_addr : AccountId) -> Result<Balance>{
             assert!(exchange_addr != self.exchange_account_id && exchange_addr != AccountId::default());
             //call dot_to_token_output_price
             let selector_dot_to_token_output_price = call::Selector::new([0x69, 0xde,0xb0, 0x15]);
@@ -548,24 +1101,30 @@ mod exchange {
             let tokens_sold = self.output_price(dot_bought, token_reserve, self.env().balance());
             // tokens sold is always > 0
             assert!( max_tokens_sold >= tokens_sold && max_dot_sold >= dot_bought);
-            assert!(self.token_transfer_from(buyer, self.exchange_account_id, tokens_sold));
+            self.token_transfer_from(buyer, self.exchange_account_id, tokens_sold)?;
             //call dot_to_token_transfer_output
             let selector_dot_to_token_transfer_output= call::Selector::new([0x07, 0x83,0xf4, 0x03]);
-            build_call::<DefaultEnvironment>()
+            let result = build_call::<DefaultEnvironment>()
                 .callee(exchange_addr)
                 .gas_limit(self.gas_limit)
                 .transferred_value(dot_bought)
                 .exec_input(
                     ExecutionInput::new(selector_dot_to_token_transfer_output.into()).push_arg(tokens_bought).push_arg(deadline).push_arg(recipient),
-                ).returns::<ReturnType<Balance>>().fire().unwrap();
+                ).returns::<ReturnType<Result<Balance>>>().fire().unwrap();
+            //The other exchange refunded the dot, give the sold tokens back to the buyer.
+            if let Err(error) = result {
+                self.token_transfer(buyer, tokens_sold).expect("token transfer failed");
+                return Err(error)
+            }
 
+            self.accrue_swap_fee(0, tokens_sold);
             self.env().emit_event( DotPurchase {
                 buyer,
                 tokens_sold,
                 dot_bought,
             });
             
-            tokens_sold
+            Ok(tokens_sold)
         }
 
         /// Convert Tokens (self.token) to Tokens (token_addr).
@@ -581,7 +1140,7 @@ mod exchange {
         /// - `token_addr`: token_addr The address of the token being purchased.
         #[ink(message)]
         pub fn token_to_token_swap_output(&mut self, tokens_bought : Balance, max_tokens_sold : Balance,
-            max_dot_sold: Balance,  deadline : Timestamp, token_addr : AccountId) -> Balance{
+            max_dot_sold: Balance,  deadline : Timestamp, token_addr : AccountId) -> Result<Balance>{
             let exchange_addr = self.exchange_from_factory(token_addr);
             let caller = self.env().caller();
             self.token_to_token_output(tokens_bought, max_tokens_sold, max_dot_sold, deadline, caller, caller, exchange_addr)
@@ -601,7 +1160,7 @@ mod exchange {
         /// - `recipient`: AcccountId will get the transferred tokens.
         #[ink(message)]
         pub fn token_to_token_transfer_output(&mut self, tokens_bought : Balance, max_tokens_sold : Balance,
-            max_dot_sold: Balance,  deadline : Timestamp,recipient : AccountId,  token_addr : AccountId) -> Balance{
+            max_dot_sold: Balance,  deadline : Timestamp,recipient : AccountId,  token_addr : AccountId) -> Result<Balance>{
             let exchange_addr = self.exchange_from_factory(token_addr);
             let caller = self.env().caller();
             self.token_to_token_output(tokens_bought, max_tokens_sold, 
@@ -623,7 +1182,7 @@ mod exchange {
         /// - `exchange_addr` The address of the exchange for the token being purchased.
         #[ink(message)]
         pub fn token_to_exchange_swap_input(&mut self, tokens_bought : Balance, max_tokens_sold : Balance,
-            max_dot_sold: Balance,  deadline : Timestamp, exchange_addr : AccountId)->Balance{
+            max_dot_sold: Balance,  deadline : Timestamp, exchange_addr : AccountId)->Result<Balance>{
             let caller = self.env().caller();
             self.token_to_token_input( tokens_bought, max_tokens_sold, max_dot_sold, deadline, caller, caller, exchange_addr)
         }
@@ -644,7 +1203,7 @@ mod exchange {
         /// - `exchange_addr` The address of the exchange for the token being purchased.
         #[ink(message)]
         pub fn token_to_exchange_transfer_input(&mut self, tokens_bought : Balance, max_tokens_sold : Balance,
-            max_dot_sold: Balance,  deadline : Timestamp, recipient : AccountId,exchange_addr : AccountId)->Balance{
+            max_dot_sold: Balance,  deadline : Timestamp, recipient : AccountId,exchange_addr : AccountId)->Result<Balance>{
             let caller = self.env().caller();
             self.token_to_token_input(tokens_bought, max_tokens_sold, max_dot_sold, deadline, caller, recipient, exchange_addr)
         }
@@ -664,7 +1223,7 @@ mod exchange {
         /// - `exchange_addr`: The address of the exchange for the token being purchased.
         #[ink(message)]
         pub fn token_to_exchange_swap_output(&mut self, tokens_bought : Balance, max_tokens_sold : Balance,
-            max_dot_sold: Balance,  deadline : Timestamp, exchange_addr : AccountId)->Balance{
+            max_dot_sold: Balance,  deadline : Timestamp, exchange_addr : AccountId)->Result<Balance>{
             let caller = self.env().caller();
             self.token_to_token_output(tokens_bought, max_tokens_sold,max_dot_sold, deadline, caller, caller, exchange_addr)
         }
@@ -685,7 +1244,7 @@ mod exchange {
         /// - `exchange_addr`: The address of the exchange for the token being purchased.
         #[ink(message)]
         pub fn token_to_exchange_transfer_output(&mut self, tokens_bought : Balance, max_tokens_sold : Balance,
-            max_dot_sold: Balance,  deadline : Timestamp, recipient : AccountId,exchange_addr : AccountId)->Balance{
+            max_dot_sold: Balance,  deadline : Timestamp, recipient : AccountId,exchange_addr : AccountId)->Result<Balance>{
             let caller = self.env().caller();
             self.token_to_token_output( tokens_bought, max_tokens_sold, 
                 max_dot_sold, deadline, caller, recipient, exchange_addr)
@@ -698,7 +1257,7 @@ mod exchange {
         /// #Params
         /// 
         /// - `dot_sold`: Amount of dot sold.
-        #[ink(message)]
+        #[ink(message, selector="0xc10785bc")]
         pub fn dot_to_token_input_price(&mut self, dot_sold : Balance)->Balance{
             assert!(dot_sold > 0);
 
@@ -728,7 +1287,7 @@ mod exchange {
         /// #Params
         /// 
         /// - `tokens_bought`: Amount of token bought.
-        #[ink(message)]
+        #[ink(message, selector="0xe486494d")]
         pub fn token_to_dot_input_price(&mut self, tokens_sold : Balance)->Balance{
             assert!(tokens_sold > 0);
 
@@ -752,6 +1311,30 @@ mod exchange {
             dot_bought
         }
 
+        /// Return the lp token name.
+        #[ink(message, selector = "0xba2d1c0b")]
+        pub fn token_name(&self) -> Option<String>{
+            Some(self.name.clone())
+        }
+
+        /// Return the lp token symbol.
+        #[ink(message, selector = "0x58af15a8")]
+        pub fn token_symbol(&self) -> Option<String>{
+            Some(self.symbol.clone())
+        }
+
+        /// Return the number of decimals used to display the liquidity.
+        #[ink(message, selector = "0x55d2c725")]
+        pub fn token_decimals(&self) -> u8{
+            self.decimals
+        }
+
+        /// Return the Dot and the token reserves of this trading pair.
+        #[ink(message, selector = "0x84393fdc")]
+        pub fn reserves(&mut self) -> (Balance, Balance){
+            (self.env().balance(), self.token_balance())
+        }
+
         /// Return the total liqudity in this trading pair.
         #[ink(message)]
         pub fn total_supply(&self) ->Balance{
@@ -763,17 +1346,129 @@ mod exchange {
         /// #params
         /// 
         /// -`owner`: The account of a liquidity provider 
-        #[ink(message)]
+        #[ink(message, selector = "0x859bd6d2")]
         pub fn balance_of(&mut self, owner : AccountId) -> Balance{
+            if let Some((lp_token, lp_token_id)) = self.lp_token {
+                //balance_of selector in multi_token 0x4abb36a8
+                let selector_balance_of = call::Selector::new([0x4a, 0xbb, 0x36, 0xa8]);
+                return build_call::<DefaultEnvironment>()
+                    .callee(lp_token)
+                    .gas_limit(self.gas_limit / 2)
+                    .transferred_value(0)
+                    .exec_input(
+                        ExecutionInput::new(selector_balance_of.into())
+                        .push_arg(&owner)
+                        .push_arg(lp_token_id),
+                    ).returns::<ReturnType<Balance>>().fire().unwrap()
+            }
             self.balances.get(&owner).unwrap_or(&0u128).clone()
         }
 
+        /// Start indexing the liquidity providers, the current providers are indexed in this call.
+        /// 
+        /// NOTE: Only the Factory can enable it, and only if the liquidity lives in this contract.
+        #[ink(message, selector = "0xf7c594fa")]
+        pub fn enable_holder_index(&mut self) -> bool{
+            assert!(self.env().caller() == self.factory && self.lp_token.is_none());
+            if self.holder_index_enabled {
+                return false
+            }
+            self.holder_index_enabled = true;
+            let providers : Vec<AccountId> = self.balances.keys().cloned().collect();
+            for provider in providers {
+                self.update_holder(provider);
+            }
+            true
+        }
+
+        #[ink(message)]
+        pub fn is_holder_index_enabled(&self) -> bool{
+            self.holder_index_enabled
+        }
+
+        /// Return the number of liquidity providers, 0 if the holder index is not enabled.
+        #[ink(message)]
+        pub fn holder_count(&self) -> u32{
+            self.holders.len()
+        }
+
+        /// Return up to `limit` liquidity providers and their liquidity, starting at position `start` of the index.
+        /// 
+        /// NOTE: The order of the providers changes when an account removes all its liquidity.
+        #[ink(message)]
+        pub fn holders(&self, start : u32, limit : u32) -> Vec<(AccountId, Balance)>{
+            self.holders.iter()
+                .skip(start as usize)
+                .take(limit as usize)
+                .map(|holder| (*holder, self.balances.get(holder).unwrap_or(&0u128).clone()))
+                .collect()
+        }
+
+        /// Return up to `limit` liquidity providers with the largest liquidity, largest first.
+        /// 
+        /// NOTE: All providers are read, this is meant for off-chain queries.
+        #[ink(message)]
+        pub fn top_holders(&self, limit : u32) -> Vec<(AccountId, Balance)>{
+            let mut holders = self.holders(0, self.holder_count());
+            holders.sort_by(|a, b| b.1.cmp(&a.1));
+            holders.truncate(limit as usize);
+            holders
+        }
+
+        /// Add the account to the holder index if it holds liquidity, remove it otherwise.
+        fn update_holder(&mut self, account : AccountId){
+            if !self.holder_index_enabled {
+                return
+            }
+            let holds_liquidity = self.balances.get(&account).unwrap_or(&0u128).clone() > 0;
+            match (self.holder_positions.get(&account).cloned(), holds_liquidity) {
+                (None, true) => {
+                    self.holder_positions.insert(account, self.holders.len());
+                    self.holders.push(account);
+                }
+                (Some(position), false) => {
+                    // Move the last provider into the freed position.
+                    self.holder_positions.take(&account);
+                    let last = self.holders.pop().expect("holder index is not empty");
+                    if last != account {
+                        *self.holders.get_mut(position).expect("holder position is in the index") = last;
+                        self.holder_positions.insert(last, position);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        /// Return the MultiToken contract and the token id holding the liquidity, if the liquidity does not live in this contract.
+        #[ink(message)]
+        pub fn lp_token(&self) -> Option<(AccountId, u128)>{
+            self.lp_token
+        }
+
         fn transfer_from_to(&mut self, from: AccountId, to: AccountId, value: Balance) -> bool {
+            if let Some((lp_token, lp_token_id)) = self.lp_token {
+                //safe_transfer_from selector in multi_token 0x1ab270b2, this contract transfers as the minter of the token id
+                let selector_safe_transfer_from = call::Selector::new([0x1a, 0xb2, 0x70, 0xb2]);
+                return build_call::<DefaultEnvironment>()
+                    .callee(lp_token)
+                    .gas_limit(self.gas_limit / 2)
+                    .transferred_value(0)
+                    .exec_input(
+                        ExecutionInput::new(selector_safe_transfer_from.into())
+                        .push_arg(&from)
+                        .push_arg(&to)
+                        .push_arg(lp_token_id)
+                        .push_arg(value)
+                        .push_arg(Vec::<u8>::new()),
+                    ).returns::<ReturnType<bool>>().fire().unwrap()
+            }
             let from_balance = self.balance_of(from);
             if from_balance < value {
                 return false
             }
-            
+            self.update_fees(from);
+            self.update_fees(to);
+
             // Update the sender's balance.
             self.balances.insert(from, from_balance - value);
 
@@ -781,6 +1476,9 @@ mod exchange {
             let to_balance = self.balance_of(to);
             self.balances.insert(to, to_balance + value);
 
+            self.update_holder(from);
+            self.update_holder(to);
+
             self.env().emit_event(Transfer {
                 from,
                 to,
@@ -792,6 +1490,9 @@ mod exchange {
 
         /// The Contract caller transfer some liquidity to another account
         /// 
+        /// NOTE: If the liquidity lives in a MultiToken contract it is transferred there by this contract, the minter of
+        /// its token id. Swap fees are then not tracked per provider.
+        /// 
         /// #Params:
         /// 
         /// - `to`: An account receive the transferred liquidity
@@ -809,6 +1510,8 @@ mod exchange {
         /// - `from`: An account pay transferred liquidity.
         /// - `to`: An account receive the transferred liquidity
         /// - `value`: Amount of liquidity will be transferred.
+        ///
+        /// NOTE: The allowance is only spent if the transfer succeeds.
         #[ink(message, selector = "0xfcfb2ccd")]
         pub fn transfer_from(&mut self,from : AccountId, to : AccountId, value : Balance) -> bool{
             let caller = self.env().caller();
@@ -816,9 +1519,12 @@ mod exchange {
             if allowance < value {
                  return false
             }
+            if !self.transfer_from_to(from, to, value) {
+                return false
+            }
             self.allowances.insert((from, caller), allowance - value);
 
-            self.transfer_from_to(from, to, value)
+            true
         }
         
         /// Approve spender can transfer liquidity from the caller account
@@ -829,6 +1535,92 @@ mod exchange {
         #[ink(message)]
         pub fn approve(&mut self, spender: AccountId, value: Balance) -> bool {
             let owner = self.env().caller();
+            self.set_allowance(owner, spender, value)
+        }
+
+        /// Increase the liquidity spender can transfer from the caller account
+        ///
+        /// #Params 
+        /// - `spender`: The account can transfer liquidity.
+        /// - `delta_value`: The amount added to the current allowance.
+        #[ink(message)]
+        pub fn increase_allowance(&mut self, spender: AccountId, delta_value: Balance) -> bool {
+            let owner = self.env().caller();
+            let allowance = self.allowance(owner, spender);
+            match allowance.checked_add(delta_value) {
+                Some(value) => self.set_allowance(owner, spender, value),
+                None => false,
+            }
+        }
+
+        /// Decrease the liquidity spender can transfer from the caller account
+        ///
+        /// #Params 
+        /// - `spender`: The account can transfer liquidity.
+        /// - `delta_value`: The amount subtracted from the current allowance.
+        #[ink(message)]
+        pub fn decrease_allowance(&mut self, spender: AccountId, delta_value: Balance) -> bool {
+            let owner = self.env().caller();
+            let allowance = self.allowance(owner, spender);
+            match allowance.checked_sub(delta_value) {
+                Some(value) => self.set_allowance(owner, spender, value),
+                None => false,
+            }
+        }
+
+        /// Approve spender can transfer liquidity from the owner account with a signature of the owner,
+        /// so the spender does not need a separate `approve` transaction.
+        ///
+        /// NOTE: `signature` is a 65 bytes ECDSA signature of `permit_hash`, the signer account is the
+        /// blake2 hash of the compressed public key. The owner nonce is consumed on success.
+        ///
+        /// #Params 
+        /// - `owner`: The account owning the liquidity.
+        /// - `spender`: The account can transfer liquidity.
+        /// - `value`: The amount liquidity can be transferred.
+        /// - `deadline`: Time after which the signature can no longer be used.
+        /// - `signature`: The signature of the owner.
+        #[ink(message)]
+        pub fn permit(&mut self, owner: AccountId, spender: AccountId, value: Balance, deadline: Timestamp, signature: Vec<u8>) -> bool {
+            if deadline < self.env().block_timestamp() {
+                return false
+            }
+            let nonce = self.nonces(owner);
+            let message_hash = self.permit_hash(owner, spender, value, nonce, deadline);
+            if common::ecdsa::recover_signer(&message_hash, &signature) != Some(owner) {
+                return false
+            }
+            self.nonces.insert(owner, nonce + 1);
+            self.set_allowance(owner, spender, value)
+        }
+
+        /// Return the nonce the next permit of the owner must be signed with.
+        #[ink(message)]
+        pub fn nonces(&self, owner: AccountId) -> u64 {
+            self.nonces.get(&owner).unwrap_or(&0u64).clone()
+        }
+
+        /// Return the hash a liquidity owner signs to permit a spender.
+        pub fn permit_hash(&self, owner: AccountId, spender: AccountId, value: Balance, nonce: u64, deadline: Timestamp) -> [u8; 32] {
+            let mut output = <Blake2x256 as HashOutput>::Type::default();
+            ink_env::hash_encoded::<Blake2x256, _>(
+                &(PERMIT_DOMAIN, self.exchange_account_id, owner, spender, value, nonce, deadline),
+                &mut output,
+            );
+            output
+        }
+
+        /// Return the liquidity spender can transfer from the owner account
+        ///
+        /// #Params 
+        /// - `owner`: The account owning the liquidity.
+        /// - `spender`: The account can transfer liquidity.
+        #[ink(message)]
+        pub fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+            self.allowances.get(&(owner, spender)).unwrap_or(&0u128).clone()
+        }
+
+        fn set_allowance(&mut self, owner: AccountId, spender: AccountId, value: Balance) -> bool {
             self.allowances.insert((owner, spender), value);
             self.env().emit_event(Approval {
                 owner,
@@ -840,47 +1632,24 @@ mod exchange {
 
         ///Return the token amount in liqudity pool
         #[ink(message)]
-        pub fn token_balance(&mut self) -> Balance{                                                                          
-            let selector_balance_of = call::Selector::new([0x56, 0xe9,0x29, 0xb2]);
-            build_call::<DefaultEnvironment>()
-                .callee(self.token)
-                .gas_limit(self.gas_limit / 2)
-                .transferred_value(0)
-                .exec_input(
-                    ExecutionInput::new(selector_balance_of.into()).push_arg(&self.exchange_account_id),
-                ).returns::<ReturnType<Balance>>().fire().unwrap()
+        pub fn token_balance(&mut self) -> Balance{
+            self.token_balance_of_exchange()
+        }
+
+        #[cfg(not(test))]
+        fn token_balance_of_exchange(&self) -> Balance{
+            common::token::balance_of(self.token, self.exchange_account_id, self.gas_limit / 2)
+        }
+
+        /// The off-chain environment can not call contracts, the tests set the token balance of the Exchange instead.
+        #[cfg(test)]
+        fn token_balance_of_exchange(&self) -> Balance{
+            crate::tests::TOKEN_BALANCE.with(|balance| balance.get())
         }
 
         ///#[ink(message)]
-        fn token_transfer(&mut self, to : AccountId, value : Balance)->bool{
-            //transfer function seletor from metadata.json 0xfae3a09d
-            let selector_transfer  = call::Selector::new([0xfa, 0xe3,0xa0, 0x9d]);
-            build_call::<DefaultEnvironment>()
-                .callee(self.token)
-                .gas_limit(self.gas_limit / 2)
-                .transferred_value(0)
-                .exec_input(
-                    ExecutionInput::new(selector_transfer.into())
-                    .push_arg(&to)
-                    .push_arg(value),
-                ).returns::<ReturnType<bool>>().fire().unwrap()
-        }  
-
-        ///Transfer token 
-        //#[ink(message)]
-        fn token_transfer_from(&mut self, from : AccountId, to : AccountId, value : u128) ->bool{
-            //selector transfer_from in erc20 metadata.json 0xfcfb2ccd
-            let selector_transfer_from = call::Selector::new([0xfc, 0xfb,0x2c, 0xcd]);
-            build_call::<DefaultEnvironment>()
-                .callee(self.token)
-                .gas_limit(self.gas_limit / 2)
-                .transferred_value(0)
-                .exec_input(
-                    ExecutionInput::new(selector_transfer_from.into())
-                    .push_arg(&from)
-                    .push_arg(&to)
-                    .push_arg(value),
-                ).returns::<ReturnType<bool>>().fire().unwrap()
+        fn token_transfer(&mut self, to : AccountId, value : Balance)->Result<()>{
+            common::token::transfer(self.token, to, value, self.gas_limit / 2)
         }
 
         ///Get exchange account in tradint by token account
@@ -890,7 +1659,7 @@ mod exchange {
         /// -`token account`: The token account in the trading pair.
         #[ink(message)]
         pub fn exchange_from_factory(&mut self, token_account: AccountId) -> AccountId{
-            //get_exchange seletor from metadata.json 0xce34755e
+            //get_exchange selector from metadata.json 0xce34755e
             let selector_transfer_from = call::Selector::new([0xce, 0x34,0x75, 0x5e]);
             build_call::<DefaultEnvironment>()
                 .callee(self.factory)
@@ -933,9 +1702,37 @@ mod tests {
     use super::*;
     use ink_env::{
         AccountId,
+        Balance,
     };
     use ink_lang as ink;
 
+    thread_local! {
+        /// The token balance of the Exchange, as the token would answer `balance_of`.
+        pub(crate) static TOKEN_BALANCE: core::cell::Cell<Balance> = core::cell::Cell::new(0);
+    }
+
+    fn set_caller(caller: AccountId) {
+        set_caller_with_value(caller, 1000000);
+    }
+
+    fn set_caller_with_value(caller: AccountId, transferred_value: Balance) {
+        let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().unwrap_or([0x0; 32].into());
+        let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+        data.push_arg(&caller);
+        ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+            caller,
+            callee,
+            1000000,
+            transferred_value,
+            data,
+        );
+    }
+
+    fn set_exchange_balance(balance: Balance) {
+        let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().expect("cannot get contract account");
+        ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(callee, balance).expect("cannot set account balance");
+    }
+
     #[ink::test]
     fn test_transfer_liquidity_should_work(){
         let token_account_id = AccountId::from([0x01; 32]);
@@ -943,7 +1740,7 @@ mod tests {
         let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
         let liquidity_amount = 50000u128;
 
-        let mut contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128);
+        let mut contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, None);
         contract.balances.insert(accounts.alice, liquidity_amount);
 
         assert_eq!(contract.balance_of(accounts.alice), liquidity_amount);
@@ -957,7 +1754,7 @@ mod tests {
         let token_account_id = AccountId::from([0x01; 32]);
         let factory_account_id = AccountId::from([0x02; 32]);
         let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
-        let mut contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128);
+        let mut contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, None);
 
         let liquidity_amount = 50000u128;
         contract.balances.insert(accounts.alice, liquidity_amount);
@@ -967,12 +1764,310 @@ mod tests {
         assert_eq!(contract.balance_of(accounts.bob), 200);
     }
 
+    #[ink::test]
+    fn test_fees_earned_should_follow_liquidity_share(){
+        //A 10000 Dot swap shared by 50000 liquidity, the fee is 30 Dot.
+        let fee_per_share = Exchange::fee_per_share_increase(10000, 30, 50000);
+        assert_eq!(Exchange::fees_owed(50000, fee_per_share, 0), 30);
+        assert_eq!(Exchange::fees_owed(20000, fee_per_share, 0), 12);
+        //Liquidity only earns the fees since its snapshot.
+        assert_eq!(Exchange::fees_owed(20000, 2 * fee_per_share, fee_per_share), 12);
+
+        let token_account_id = AccountId::from([0x01; 32]);
+        let factory_account_id = AccountId::from([0x02; 32]);
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, None);
+        contract.balances.insert(accounts.alice, 50000);
+        assert!(contract.transfer(accounts.bob, 200));
+        assert_eq!(contract.fees_earned(accounts.alice), (0, 0));
+        assert_eq!(contract.fees_earned(accounts.bob), (0, 0));
+    }
+
+    #[ink::test]
+    fn test_fees_earned_should_not_overflow_with_18_decimals(){
+        let unit = 10u128.pow(18);
+        //A 1e9 token swap shared by 1e9 liquidity, input * fee * precision is above u128::MAX.
+        let fee_per_share = Exchange::fee_per_share_increase(1_000_000_000 * unit, 30, 1_000_000_000 * unit);
+        assert_eq!(fee_per_share, 3_000_000_000);
+        assert_eq!(Exchange::fees_owed(1_000_000_000 * unit, fee_per_share, 0), 3_000_000 * unit);
+        //total_liquidity * FEE_DENOMINATOR is above u128::MAX.
+        assert_eq!(Exchange::fee_per_share_increase(u128::MAX / 1000, 30, u128::MAX / 1000), 3_000_000_000);
+        //liquidity * fee_per_share is above u128::MAX once the fees per share grew.
+        assert_eq!(Exchange::fees_owed(1_000_000_000 * unit, 10_000 * FEE_PER_SHARE_PRECISION, 0), 10_000_000_000_000 * unit);
+    }
+
+    #[ink::test]
+    fn test_increase_allowance_should_work(){
+        let token_account_id = AccountId::from([0x01; 32]);
+        let factory_account_id = AccountId::from([0x02; 32]);
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, None);
+
+        assert!(contract.increase_allowance(accounts.bob, 300));
+        assert!(contract.increase_allowance(accounts.bob, 200));
+        assert_eq!(contract.allowance(accounts.alice, accounts.bob), 500);
+        assert!(!contract.increase_allowance(accounts.bob, u128::MAX));
+        assert_eq!(contract.allowance(accounts.alice, accounts.bob), 500);
+    }
+
+    #[ink::test]
+    fn test_decrease_allowance_underflow_should_fail(){
+        let token_account_id = AccountId::from([0x01; 32]);
+        let factory_account_id = AccountId::from([0x02; 32]);
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, None);
+
+        contract.approve(accounts.bob, 500);
+        assert!(contract.decrease_allowance(accounts.bob, 200));
+        assert_eq!(contract.allowance(accounts.alice, accounts.bob), 300);
+        assert!(!contract.decrease_allowance(accounts.bob, 301));
+        assert_eq!(contract.allowance(accounts.alice, accounts.bob), 300);
+    }
+
+    #[ink::test]
+    fn test_permit_should_work(){
+        let token_account_id = AccountId::from([0x01; 32]);
+        let factory_account_id = AccountId::from([0x02; 32]);
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, None);
+
+        let secret_key = secp256k1::SecretKey::parse(&[0x11; 32]).unwrap();
+        let public_key = secp256k1::PublicKey::from_secret_key(&secret_key);
+        let owner = common::ecdsa::public_key_account(&public_key.serialize_compressed());
+
+        let message_hash = contract.permit_hash(owner, accounts.bob, 500, 0, 1000);
+        let (signature, recovery_id) = secp256k1::sign(&secp256k1::Message::parse(&message_hash), &secret_key);
+        let mut signature = signature.serialize().to_vec();
+        signature.push(recovery_id.serialize());
+
+        assert!(contract.permit(owner, accounts.bob, 500, 1000, signature.clone()));
+        assert_eq!(contract.allowance(owner, accounts.bob), 500);
+        assert_eq!(contract.nonces(owner), 1);
+        assert!(!contract.permit(owner, accounts.bob, 500, 1000, signature));
+    }
+
+    #[ink::test]
+    fn test_on_token_received_should_sell_tokens(){
+        let token_account_id = AccountId::from([0x05; 32]);
+        let factory_account_id = AccountId::from([0x02; 32]);
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        set_exchange_balance(200000);
+        let mut contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, None);
+        //The token transferred 10000 to the Exchange before calling it.
+        TOKEN_BALANCE.with(|balance| balance.set(210000));
+        let dot_bought = contract.input_price(10000, 200000, 200000);
+        let bob_balance = ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.bob).unwrap();
+
+        set_caller_with_value(token_account_id, 0);
+        let data = scale::Encode::encode(&crate::exchange::TokenReceivedAction::TokenToDot {
+            min_dot : dot_bought,
+            deadline : 1000,
+            recipient : accounts.bob,
+        });
+        assert!(contract.on_token_received(accounts.alice, 10000, data));
+        assert_eq!(ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.bob).unwrap(), bob_balance + dot_bought);
+        assert_eq!(contract.reserves(), (200000 - dot_bought, 210000));
+    }
+
+    #[ink::test]
+    fn test_on_token_received_should_add_initial_liquidity(){
+        let token_account_id = AccountId::from([0x05; 32]);
+        let factory_account_id = AccountId::from([0x02; 32]);
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        set_exchange_balance(0);
+        let mut contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, None);
+        assert_eq!(contract.total_supply(), 0);
+
+        //The token forwards the 100000 Dot sent with `transfer_and_call`.
+        set_exchange_balance(100000);
+        TOKEN_BALANCE.with(|balance| balance.set(50000));
+        set_caller_with_value(token_account_id, 100000);
+        let data = scale::Encode::encode(&crate::exchange::TokenReceivedAction::AddLiquidity {
+            min_liquidity : 1,
+            deadline : 1000,
+        });
+        assert!(contract.on_token_received(accounts.bob, 50000, data));
+        assert_eq!(contract.total_supply(), 100000);
+        assert_eq!(contract.balance_of(accounts.bob), 100000);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_on_token_received_from_other_account_should_fail(){
+        let token_account_id = AccountId::from([0x05; 32]);
+        let factory_account_id = AccountId::from([0x02; 32]);
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, None);
+
+        let data = scale::Encode::encode(&crate::exchange::TokenReceivedAction::TokenToDot {
+            min_dot : 1,
+            deadline : 1000,
+            recipient : accounts.alice,
+        });
+        contract.on_token_received(accounts.alice, 100, data);
+    }
+
+    #[ink::test]
+    fn test_lp_metadata_should_derive_from_token_symbol(){
+        let token_account_id = AccountId::from([0x01; 32]);
+        let factory_account_id = AccountId::from([0x02; 32]);
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+
+        let contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, Some(String::from("ZLK")));
+        assert_eq!(contract.token_name(), Some(String::from("Zenlink LP ZLK")));
+        assert_eq!(contract.token_symbol(), Some(String::from("ZLP-ZLK")));
+        assert_eq!(contract.token_decimals(), 18);
+
+        let contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, None);
+        assert_eq!(contract.token_name(), Some(String::from("Zenlink LP")));
+        assert_eq!(contract.token_symbol(), Some(String::from("ZLP")));
+    }
+
+    #[ink::test]
+    fn test_liquidity_in_multi_token_should_not_live_in_exchange(){
+        let token_account_id = AccountId::from([0x01; 32]);
+        let factory_account_id = AccountId::from([0x02; 32]);
+        let multi_token_account_id = AccountId::from([0x03; 32]);
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+
+        let mut contract = Exchange::new_with_lp_token(token_account_id, factory_account_id, accounts.alice, 0u128, None,
+            Some((multi_token_account_id, 1)));
+        assert_eq!(contract.lp_token(), Some((multi_token_account_id, 1)));
+        assert!(contract.balances.get(&accounts.alice).is_none());
+        //The allowance is checked before the transfer is forwarded to the MultiToken contract.
+        set_caller(accounts.bob);
+        assert!(!contract.transfer_from(accounts.alice, accounts.bob, 100));
+        assert_eq!(contract.fees_earned(accounts.alice), (0, 0));
+
+        let contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, None);
+        assert_eq!(contract.lp_token(), None);
+    }
+
+    #[ink::test]
+    fn test_failed_transfer_from_should_keep_allowance(){
+        let token_account_id = AccountId::from([0x01; 32]);
+        let factory_account_id = AccountId::from([0x02; 32]);
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, None);
+        contract.balances.insert(accounts.alice, 100);
+        assert!(contract.approve(accounts.bob, 300));
+
+        set_caller(accounts.bob);
+        assert!(!contract.transfer_from(accounts.alice, accounts.bob, 200));
+        assert_eq!(contract.allowance(accounts.alice, accounts.bob), 300);
+        assert!(contract.transfer_from(accounts.alice, accounts.bob, 100));
+        assert_eq!(contract.allowance(accounts.alice, accounts.bob), 200);
+    }
+
+    #[ink::test]
+    fn test_holder_index_should_track_providers(){
+        let token_account_id = AccountId::from([0x01; 32]);
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        //The caller acts as the factory.
+        let mut contract = Exchange::new(token_account_id, accounts.alice, accounts.alice, 0u128, None);
+        contract.balances.insert(accounts.alice, 50000u128);
+
+        assert!(contract.enable_holder_index());
+        assert!(!contract.enable_holder_index());
+        assert_eq!(contract.holder_count(), 1);
+
+        assert!(contract.transfer(accounts.bob, 20000));
+        assert!(contract.transfer(accounts.charlie, 30000));
+        assert_eq!(contract.holder_count(), 2);
+        assert_eq!(contract.top_holders(1), vec![(accounts.charlie, 30000)]);
+        assert_eq!(contract.holders(1, 5), vec![(accounts.charlie, 30000)]);
+    }
+
+    #[ink::test]
+    fn test_liquidity_minted_should_be_limited_by_tokens_received(){
+        //A token taking a 2% fee on transfer: 1020 sent at the current ratio, 999 received.
+        assert_eq!(Exchange::liquidity_minted(1000, 999, 100000, 102000, 50000), 489);
+        assert_eq!(Exchange::liquidity_minted(1000, 1020, 100000, 102000, 50000), 500);
+    }
+
+    #[ink::test]
+    fn test_dynamic_fee_should_follow_volatility(){
+        //A 2% price change moves the volatility a tenth of the way, to 0.2%.
+        let volatility = Exchange::next_volatility(0, 1_000_000_000, 1_020_000_000);
+        assert_eq!(volatility, 2000);
+        assert_eq!(Exchange::next_volatility(volatility, 1_020_000_000, 1_000_000_000), 3760);
+        //Price changes are capped at the whole price.
+        assert_eq!(Exchange::next_volatility(0, 1_000_000_000, 5_000_000_000), 100_000);
+
+        assert_eq!(Exchange::dynamic_fee_bps(0, 10, 100), 10);
+        assert_eq!(Exchange::dynamic_fee_bps(2000, 10, 100), 28);
+        assert_eq!(Exchange::dynamic_fee_bps(100_000, 10, 100), 100);
+    }
+
+    #[ink::test]
+    fn test_set_dynamic_fee_should_change_prices(){
+        let token_account_id = AccountId::from([0x01; 32]);
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        //The caller is the Factory.
+        let mut contract = Exchange::new(token_account_id, accounts.alice, accounts.alice, 0u128, None);
+        assert_eq!(contract.fee_bps(), 30);
+        let flat_bought = contract.input_price(10000, 200000, 200000);
+
+        contract.set_dynamic_fee(Some((10, 100)));
+        assert_eq!(contract.dynamic_fee(), Some((10, 100)));
+        assert_eq!(contract.fee_bps(), 10);
+        assert!(contract.input_price(10000, 200000, 200000) > flat_bought);
+
+        contract.set_dynamic_fee(None);
+        assert_eq!(contract.fee_bps(), 30);
+        assert_eq!(contract.input_price(10000, 200000, 200000), flat_bought);
+    }
+
+    #[ink::test]
+    fn test_supporting_fee_should_price_on_tokens_received(){
+        let token_account_id = AccountId::from([0x01; 32]);
+        let factory_account_id = AccountId::from([0x02; 32]);
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, None);
+
+        //The token burns 1% on transfer, so selling 10000 tokens adds 9900 to the token balance of the Exchange.
+        let (tokens_received, dot_bought) = contract.input_price_supporting_fee(200000, 209900, 200000);
+        assert_eq!(tokens_received, 9900);
+        assert_eq!(dot_bought, contract.input_price(9900, 200000, 200000));
+        assert!(dot_bought < contract.input_price(10000, 200000, 200000));
+        //A token which took everything buys nothing.
+        assert_eq!(contract.input_price_supporting_fee(200000, 200000, 200000), (0, 0));
+
+        //Depositing 10000 Dot with 10000 tokens, the liquidity is limited by the 9900 tokens received.
+        let (tokens_received, liquidity_minted) = Exchange::liquidity_minted_supporting_fee(10000, 200000, 200000, 209900, 200000);
+        assert_eq!(tokens_received, 9900);
+        assert_eq!(liquidity_minted, 9900);
+        assert_eq!(Exchange::liquidity_minted(10000, 10000, 200000, 200000, 200000), 10000);
+    }
+
+    #[ink::test]
+    fn test_dynamic_fee_with_10_and_18_decimal_reserves(){
+        let dot = 10u128.pow(10);
+        let token = 10u128.pow(18);
+        //1000 Dot against 1000000 tokens, a token is worth 0.001 Dot.
+        assert_eq!(Exchange::spot_price(1000 * dot, 1_000_000 * token), 10_000_000);
+
+        let token_account_id = AccountId::from([0x01; 32]);
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        //The caller is the Factory.
+        let mut contract = Exchange::new(token_account_id, accounts.alice, accounts.alice, 0u128, None);
+        contract.set_dynamic_fee(Some((10, 100)));
+        contract.adjust_dynamic_fee(1000 * dot, 1_000_000 * token);
+        assert_eq!((contract.volatility(), contract.fee_bps()), (0, 10));
+
+        //A 10 Dot swap at 0.1% buys about 9891 tokens and moves the price by about 2%.
+        let tokens_bought = 9_891_187_041_455_855_998_574;
+        contract.adjust_dynamic_fee(1010 * dot, 1_000_000 * token - tokens_bought);
+        assert_eq!(contract.volatility(), 2008);
+        assert_eq!(contract.fee_bps(), 28);
+    }
+
     #[ink::test]
     fn test_output_price(){
         let token_account_id = AccountId::from([0x01; 32]);
         let factory_account_id = AccountId::from([0x02; 32]);
         let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
-        let contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128);
+        let contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, None);
 
         let output_amount = 40;
         let input_reserve = 200000;
@@ -994,7 +2089,7 @@ mod tests {
         let token_account_id = AccountId::from([0x01; 32]);
         let factory_account_id = AccountId::from([0x02; 32]);
         let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
-        let contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128);
+        let contract = Exchange::new(token_account_id, factory_account_id, accounts.alice, 0u128, None);
 
         let intput_amount = 40;
         let input_reserve = 200000;
@@ -7,9 +7,24 @@ mod factory {
     #[cfg(not(feature = "ink-as-dependency"))]
     use ink_storage::{
         collections::HashMap as StorageHashMap,
+        traits::{PackedLayout, SpreadLayout},
+    };
+
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_env::{
+         call,
+         DefaultEnvironment,
+         call::{ build_call, ExecutionInput, utils::ReturnType,}
+    };
+
+    use ink_prelude::{
+        string::String,
+        vec::Vec,
     };
 
     use exchange::Exchange;
+    use stable_pool::StablePool;
+    use weighted_pool::WeightedPool;
 
     #[ink(event)]
     pub struct NewExchange{
@@ -19,6 +34,35 @@ mod factory {
         exchange_contract_account : AccountId,
     }
 
+    /// The kinds of multi-token pools the Factory creates next to the exchanges.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub enum PoolKind {
+        /// A StablePool of pegged tokens.
+        Stable,
+        /// A WeightedPool of tokens with custom weights.
+        Weighted,
+    }
+
+    #[ink(event)]
+    pub struct NewStablePool{
+        #[ink(topic)]
+        stable_pool_account : AccountId,
+        #[ink(topic)]
+        pool_id : u128,
+        coins : Vec<AccountId>,
+    }
+
+    #[ink(event)]
+    pub struct NewWeightedPool{
+        #[ink(topic)]
+        weighted_pool_account : AccountId,
+        #[ink(topic)]
+        pool_id : u128,
+        tokens : Vec<AccountId>,
+        weights : Vec<u128>,
+    }
+
     #[ink(storage)]
     pub struct Factory {
         pub exchange_template : Hash,
@@ -30,6 +74,29 @@ mod factory {
         exchange_to_token : StorageHashMap<AccountId,AccountId>,
 
         id_to_token : StorageHashMap<u128, AccountId>,
+
+        //the account allowed to change the factory options
+        owner : AccountId,
+
+        //MultiToken contract holding the liquidity of the exchanges created from now on, if any
+        multi_token : Option<AccountId>,
+
+        pub stable_pool_template : Hash,
+
+        pub weighted_pool_template : Hash,
+
+        //number of stable and weighted pools, pool ids are shared by both kinds
+        pub pool_count : u128,
+
+        id_to_pool : StorageHashMap<u128, (PoolKind, AccountId)>,
+
+        pool_to_tokens : StorageHashMap<AccountId, Vec<AccountId>>,
+
+        //number of pools trading a token
+        token_pool_count : StorageHashMap<AccountId, u32>,
+
+        //the pools trading a token by index, in the order of creation
+        token_pools : StorageHashMap<(AccountId, u32), AccountId>,
     }
 
     impl Factory {
@@ -42,7 +109,15 @@ mod factory {
                 token_to_exchange : StorageHashMap::new(),
                 exchange_to_token : StorageHashMap::new(),
                 id_to_token : StorageHashMap::new(),
-                
+                owner : Self::env().caller(),
+                multi_token : None,
+                stable_pool_template : Hash::default(),
+                weighted_pool_template : Hash::default(),
+                pool_count : 0,
+                id_to_pool : StorageHashMap::new(),
+                pool_to_tokens : StorageHashMap::new(),
+                token_pool_count : StorageHashMap::new(),
+                token_pools : StorageHashMap::new(),
             }
         }
 
@@ -66,9 +141,79 @@ mod factory {
         }
 
 
+        /// Return the account allowed to change the factory options.
+        #[ink(message)]
+        pub fn owner(&self) -> AccountId{
+            self.owner
+        }
+
+        /// Keep the liquidity of the exchanges created from now on in a MultiToken contract, under the token id of
+        /// the traded token, instead of in each Exchange. `None` keeps the liquidity in the Exchange.
+        /// 
+        /// NOTE: Only the owner can set it. The Factory must be the owner of the MultiToken contract.
+        /// 
+        /// #Params
+        /// - `multi_token`: The MultiToken contract account
+        #[ink(message)]
+        pub fn set_multi_token(&mut self, multi_token : Option<AccountId>){
+            assert!(self.env().caller() == self.owner);
+            self.multi_token = multi_token;
+        }
+
+        /// Return the MultiToken contract holding the liquidity of new exchanges, if any.
+        #[ink(message)]
+        pub fn multi_token(&self) -> Option<AccountId>{
+            self.multi_token
+        }
+
+        /// Start indexing the liquidity providers of an Exchange, so its providers can be enumerated.
+        /// 
+        /// NOTE: Only the owner can enable it.
+        /// 
+        /// #Params
+        /// - `erc20_token_account`: The erc20 token account of the trading pair
+        #[ink(message)]
+        pub fn enable_holder_index(&mut self, erc20_token_account : AccountId) -> bool{
+            assert!(self.env().caller() == self.owner);
+            let exchange_contract_account = self.get_exchange(erc20_token_account);
+            //enable_holder_index selector in exchange 0xf7c594fa
+            let selector_enable_holder_index = call::Selector::new([0xf7, 0xc5, 0x94, 0xfa]);
+            build_call::<DefaultEnvironment>()
+                .callee(exchange_contract_account)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(selector_enable_holder_index.into()),
+                ).returns::<ReturnType<bool>>().fire().unwrap()
+        }
+
+        /// Scale the swap fee of an Exchange between a minimum and a maximum with the volatility of its price, or go
+        /// back to the flat 0.3% fee with `None`.
+        /// 
+        /// NOTE: Only the owner can set it.
+        /// 
+        /// #Params
+        /// - `erc20_token_account`: The erc20 token account of the trading pair
+        /// - `dynamic_fee`: The minimum and maximum fee in basis points
+        #[ink(message)]
+        pub fn set_dynamic_fee(&mut self, erc20_token_account : AccountId, dynamic_fee : Option<(u32, u32)>){
+            assert!(self.env().caller() == self.owner);
+            let exchange_contract_account = self.get_exchange(erc20_token_account);
+            //set_dynamic_fee selector in exchange 0x7f7c1be1
+            let selector_set_dynamic_fee = call::Selector::new([0x7f, 0x7c, 0x1b, 0xe1]);
+            build_call::<DefaultEnvironment>()
+                .callee(exchange_contract_account)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(selector_set_dynamic_fee.into())
+                    .push_arg(dynamic_fee),
+                ).returns::<ReturnType<()>>().fire().unwrap()
+        }
+
         /// Create trading pair
         /// 
-        /// NOTE: A token account can only create one trading pair.
+        /// NOTE: A token account can only create one trading pair. The caller approves the Factory for `token_ammount` first.
         /// 
         /// #Params
         /// - `erc20_token_address`: The erc20 token account
@@ -83,17 +228,28 @@ mod factory {
             let transferred_balance = self.env().transferred_balance();
             assert!(transferred_balance != 0);
 
-            let exchange = Exchange::new(erc20_token_account, self.env().account_id(), self.env().caller(), token_ammount)
+            let token_id = self.token_count + 1;
+            let lp_token = self.multi_token.map(|multi_token| (multi_token, token_id));
+            let token_symbol = self.token_symbol(erc20_token_account);
+            //The Exchange does not exist yet when the caller approves, so the Factory pulls the initial tokens for it.
+            let exchange = Exchange::new_with_lp_token(erc20_token_account, self.env().account_id(), self.env().caller(), 0,
+                token_symbol, lp_token)
                 .endowment(transferred_balance)
                 .code_hash(self.exchange_template)
                 .instantiate()
                 .expect("instantiate exchange failed"); 
             
             let exchange_contract_account = exchange.get_address();
+            if token_ammount > 0 {
+                assert!(self.token_transfer_from(erc20_token_account, self.env().caller(), exchange_contract_account, token_ammount));
+            }
+            if let Some(multi_token) = self.multi_token {
+                //The Exchange mints the initial liquidity equal to its endowment, here it is minted in the MultiToken.
+                self.create_lp_token(multi_token, token_id, exchange_contract_account, transferred_balance);
+            }
             self.token_to_exchange.insert(erc20_token_account.clone(), exchange_contract_account.clone());
             self.exchange_to_token.insert(exchange_contract_account.clone(), erc20_token_account.clone());
     
-            let token_id = self.token_count + 1;
             self.token_count = token_id;
 
             self.id_to_token.insert(token_id, erc20_token_account.clone());
@@ -104,6 +260,197 @@ mod factory {
             });
         }
 
+        /// Query the symbol of an erc20 token, the Exchange derives its lp token name and symbol from it.
+        ///
+        /// Return None if the token has no `token_symbol` message or the call fails, the lp token then gets the
+        /// default name and symbol.
+        fn token_symbol(&self, erc20_token_account : AccountId) -> Option<String>{
+            //token_symbol selector in erc20 0x58af15a8
+            let selector_token_symbol = call::Selector::new([0x58, 0xaf, 0x15, 0xa8]);
+            build_call::<DefaultEnvironment>()
+                .callee(erc20_token_account)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(selector_token_symbol.into()),
+                ).returns::<ReturnType<Option<String>>>().fire().unwrap_or(None)
+        }
+
+        fn token_transfer_from(&self, erc20_token_account : AccountId, from : AccountId, to : AccountId, value : Balance) -> bool{
+            common::token::transfer_from(erc20_token_account, from, to, value, 0).is_ok()
+        }
+
+        /// Create the lp token id of an Exchange in the MultiToken contract and mint the initial liquidity to the caller.
+        fn create_lp_token(&self, multi_token : AccountId, token_id : u128, exchange_contract_account : AccountId, initial_liquidity : Balance){
+            //create_token selector in multi_token 0x648ab1de
+            let selector_create_token = call::Selector::new([0x64, 0x8a, 0xb1, 0xde]);
+            build_call::<DefaultEnvironment>()
+                .callee(multi_token)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(selector_create_token.into())
+                    .push_arg(token_id)
+                    .push_arg(&exchange_contract_account)
+                    .push_arg(&self.env().caller())
+                    .push_arg(initial_liquidity),
+                ).returns::<()>().fire().unwrap()
+        }
+
+        ///Set the StablePool wasm hashcode on the chain. Factory will use it to instantiate StablePool
+        /// 
+        /// NOTE: Only the owner can set it, once.
+        /// 
+        /// #Params
+        /// - `stable_pool_template_address`: StablePool wasm hashcode on the chain
+        #[ink(message)]
+        pub fn initialize_stable_pool_template(&mut self, stable_pool_template_address : Hash){
+            assert!(self.env().caller() == self.owner);
+            assert!(self.stable_pool_template == Hash::default());
+            assert!(stable_pool_template_address != Hash::default());
+
+            self.stable_pool_template = stable_pool_template_address;
+        }
+
+        /// Create a stable pool of N pegged tokens.
+        /// 
+        /// NOTE: Anyone can create stable pools, like weighted pools and exchanges. The Factory owner can ramp their
+        /// amplification coefficient.
+        /// 
+        /// #Params
+        /// - `coins`: The erc20 token accounts traded in the pool.
+        /// - `initial_a`: Amplification coefficient of the pool.
+        /// - `fee`: Swap fee of the pool, in units of 1 / 10^10.
+        #[ink(message,payable)]
+        pub fn create_stable_pool(&mut self, coins : Vec<AccountId>, initial_a : u128, fee : u128) -> AccountId{
+            assert!(self.stable_pool_template != Hash::default());
+
+            // If Caller don't supply enought dot to instantiated the pool. The pool will become tombstone.
+            let transferred_balance = self.env().transferred_balance();
+            assert!(transferred_balance != 0);
+
+            let stable_pool = StablePool::new(coins.clone(), initial_a, fee, self.owner)
+                .endowment(transferred_balance)
+                .code_hash(self.stable_pool_template)
+                .instantiate()
+                .expect("instantiate stable pool failed");
+
+            let stable_pool_account = stable_pool.get_address();
+            let pool_id = self.register_pool(PoolKind::Stable, stable_pool_account, coins.clone());
+
+            self.env().emit_event( NewStablePool {
+                stable_pool_account,
+                pool_id,
+                coins,
+            });
+            stable_pool_account
+        }
+
+        ///Set the WeightedPool wasm hashcode on the chain. Factory will use it to instantiate WeightedPool
+        /// 
+        /// NOTE: Only the owner can set it, once.
+        /// 
+        /// #Params
+        /// - `weighted_pool_template_address`: WeightedPool wasm hashcode on the chain
+        #[ink(message)]
+        pub fn initialize_weighted_pool_template(&mut self, weighted_pool_template_address : Hash){
+            assert!(self.env().caller() == self.owner);
+            assert!(self.weighted_pool_template == Hash::default());
+            assert!(weighted_pool_template_address != Hash::default());
+
+            self.weighted_pool_template = weighted_pool_template_address;
+        }
+
+        /// Create a weighted pool of 2 to 8 tokens, e.g. an 80/20 pool.
+        /// 
+        /// NOTE: Anyone can create weighted pools. The pool is traded once its creator called `initialize` on it.
+        /// 
+        /// #Params
+        /// - `tokens`: The erc20 token accounts traded in the pool.
+        /// - `weights`: Normalized weight of each token, multiplied by 10^18. They must sum to 10^18.
+        /// - `swap_fee`: Swap fee of the pool, multiplied by 10^18.
+        #[ink(message,payable)]
+        pub fn create_weighted_pool(&mut self, tokens : Vec<AccountId>, weights : Vec<u128>, swap_fee : u128) -> AccountId{
+            assert!(self.weighted_pool_template != Hash::default());
+
+            // If Caller don't supply enought dot to instantiated the pool. The pool will become tombstone.
+            let transferred_balance = self.env().transferred_balance();
+            assert!(transferred_balance != 0);
+
+            let weighted_pool = WeightedPool::new(tokens.clone(), weights.clone(), swap_fee)
+                .endowment(transferred_balance)
+                .code_hash(self.weighted_pool_template)
+                .instantiate()
+                .expect("instantiate weighted pool failed");
+
+            let weighted_pool_account = weighted_pool.get_address();
+            let pool_id = self.register_pool(PoolKind::Weighted, weighted_pool_account, tokens.clone());
+
+            self.env().emit_event( NewWeightedPool {
+                weighted_pool_account,
+                pool_id,
+                tokens,
+                weights,
+            });
+            weighted_pool_account
+        }
+
+        fn register_pool(&mut self, kind : PoolKind, pool_account : AccountId, tokens : Vec<AccountId>) -> u128{
+            let pool_id = self.pool_count + 1;
+            self.pool_count = pool_id;
+            self.id_to_pool.insert(pool_id, (kind, pool_account));
+            for token in tokens.iter() {
+                let index = self.token_pool_count.get(token).cloned().unwrap_or(0);
+                self.token_pools.insert((*token, index), pool_account);
+                self.token_pool_count.insert(*token, index + 1);
+            }
+            self.pool_to_tokens.insert(pool_account, tokens);
+            pool_id
+        }
+
+        /// Get the kind and the account of a stable or weighted pool by index.
+        /// 
+        /// #Params
+        /// 
+        /// - `pool_id`: The serial number of the pool, which sort by the order of creation.
+        #[ink(message)]
+        pub fn get_pool_with_id(&self, pool_id : u128)-> (PoolKind, AccountId){
+            *self.id_to_pool.get(&pool_id).unwrap()
+        }
+
+        /// Get the number of stable and weighted pools trading a token.
+        /// 
+        /// #Params
+        /// 
+        /// - `token`: A token account traded in the pools.
+        #[ink(message)]
+        pub fn get_pool_count_of_token(&self, token : AccountId)-> u32{
+            self.token_pool_count.get(&token).cloned().unwrap_or(0)
+        }
+
+        /// Get a page of the stable and weighted pools trading a token, in the order of creation.
+        /// 
+        /// #Params
+        /// 
+        /// - `token`: A token account traded in the pools.
+        /// - `start`: Index of the first pool of the page.
+        /// - `limit`: Maximum number of pools returned.
+        #[ink(message)]
+        pub fn get_pools(&self, token : AccountId, start : u32, limit : u32)-> Vec<AccountId>{
+            let end = self.get_pool_count_of_token(token).min(start.saturating_add(limit));
+            (start..end).map(|index| *self.token_pools.get(&(token, index)).unwrap()).collect()
+        }
+
+        /// Get the token accounts of a stable or weighted pool.
+        /// 
+        /// #Params
+        /// 
+        /// - `pool_account`: A pool created by this Factory.
+        #[ink(message)]
+        pub fn get_pool_tokens(&self, pool_account : AccountId)-> Vec<AccountId>{
+            self.pool_to_tokens.get(&pool_account).unwrap().clone()
+        }
+
         /// Get Exchange account by token from the trading pair.
         /// 
         /// #Params
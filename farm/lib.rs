@@ -0,0 +1,540 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::farm::Farm;
+
+use ink_lang as ink;
+
+#[ink::contract]
+mod farm {
+
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::{
+        collections::HashMap as StorageHashMap,
+        traits::{PackedLayout, SpreadLayout},
+    };
+
+    use primitive_types::U256;
+
+    /// Index of a pool.
+    pub type PoolId = u32;
+
+    /// Accumulated rewards per share are multiplied by it.
+    pub const ACC_REWARD_PRECISION : u128 = 1_000_000_000_000;
+
+    /// A pool staking the LP token of an Exchange.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub struct PoolInfo {
+        //the Exchange whose LP token is staked
+        pub lp_token : AccountId,
+        //share of the reward per block, relative to the total allocation points
+        pub alloc_point : u128,
+        //block up to which the rewards are accumulated
+        pub last_reward_block : BlockNumber,
+        //rewards per staked LP token since the pool was added, multiplied by ACC_REWARD_PRECISION
+        pub acc_reward_per_share : u128,
+        pub total_staked : Balance,
+    }
+
+    /// Stake of an account in a pool.
+    #[derive(Debug, Default, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub struct UserInfo {
+        pub amount : Balance,
+        //rewards of `amount` already paid or not earned, `amount * acc_reward_per_share` when last updated
+        pub reward_debt : Balance,
+    }
+
+    #[ink(event)]
+    pub struct PoolAdded {
+        #[ink(topic)]
+        pool_id : PoolId,
+        #[ink(topic)]
+        lp_token : AccountId,
+        alloc_point : u128,
+    }
+
+    #[ink(event)]
+    pub struct PoolSet {
+        #[ink(topic)]
+        pool_id : PoolId,
+        alloc_point : u128,
+    }
+
+    #[ink(event)]
+    pub struct Deposit {
+        #[ink(topic)]
+        user : AccountId,
+        #[ink(topic)]
+        pool_id : PoolId,
+        amount : Balance,
+    }
+
+    #[ink(event)]
+    pub struct Withdraw {
+        #[ink(topic)]
+        user : AccountId,
+        #[ink(topic)]
+        pool_id : PoolId,
+        amount : Balance,
+    }
+
+    #[ink(event)]
+    pub struct EmergencyWithdraw {
+        #[ink(topic)]
+        user : AccountId,
+        #[ink(topic)]
+        pool_id : PoolId,
+        amount : Balance,
+    }
+
+    #[ink(event)]
+    pub struct Harvest {
+        #[ink(topic)]
+        user : AccountId,
+        #[ink(topic)]
+        pool_id : PoolId,
+        amount : Balance,
+    }
+
+    pub use common::token::{Error, Result};
+
+    /// Liquidity mining for Exchange LP tokens. Stakers earn an Erc20 reward token every block, the reward per block is
+    /// split between the pools by their allocation points and within a pool by stake.
+    #[ink(storage)]
+    pub struct Farm {
+        //the account allowed to add pools and change the rewards
+        owner : AccountId,
+        reward_token : AccountId,
+        reward_per_block : Balance,
+        //block from which the rewards are distributed
+        start_block : BlockNumber,
+        pools : StorageHashMap<PoolId, PoolInfo>,
+        pool_length : PoolId,
+        //pool of each LP token, a token is staked in one pool only
+        lp_token_pools : StorageHashMap<AccountId, PoolId>,
+        users : StorageHashMap<(PoolId, AccountId), UserInfo>,
+        total_alloc_point : u128,
+        gas_limit :u64,
+        farm_account_id : AccountId,
+    }
+
+    impl Farm {
+
+        /// Constructor of the Farm contract
+        ///
+        /// NOTE: The Farm pays rewards from its own reward token balance, e.g. funded with `claim_emission(farm)` by
+        /// an Erc20 minter. Rewards above the balance are not paid.
+        ///
+        /// #Params
+        ///
+        /// - `reward_token`: AccountId of the Erc20 reward token.
+        /// - `reward_per_block`: Reward tokens distributed per block across all pools.
+        /// - `start_block`: Block from which the rewards are distributed.
+        #[ink(constructor)]
+        pub fn new(reward_token : AccountId, reward_per_block : Balance, start_block : BlockNumber) -> Self {
+            Self{
+                owner : Self::env().caller(),
+                reward_token,
+                reward_per_block,
+                start_block,
+                pools : StorageHashMap::new(),
+                pool_length : 0,
+                lp_token_pools : StorageHashMap::new(),
+                users : StorageHashMap::new(),
+                total_alloc_point : 0,
+                gas_limit : 507085500000,
+                farm_account_id : Self::env().account_id(),
+            }
+        }
+
+        #[ink(constructor)]
+        pub fn default() -> Self {
+            Self::new(AccountId::from([0x01; 32]), 100, 0)
+        }
+
+        /// Add a pool for the LP token of an Exchange. Only the owner can add pools. The reward token can not be
+        /// staked, since the stake would be paid out as rewards.
+        ///
+        /// Return The pool id.
+        ///
+        /// #Params
+        ///
+        /// - `lp_token`: The Exchange whose LP token is staked.
+        /// - `alloc_point`: Share of the reward per block, relative to the other pools.
+        #[ink(message)]
+        pub fn add_pool(&mut self, lp_token : AccountId, alloc_point : u128) -> PoolId{
+            assert!(self.env().caller() == self.owner);
+            assert!(lp_token != AccountId::default() && !self.lp_token_pools.contains_key(&lp_token));
+            assert!(lp_token != self.reward_token);
+            self.mass_update_pools();
+
+            let pool_id = self.pool_length;
+            self.pool_length += 1;
+            self.pools.insert(pool_id, PoolInfo {
+                lp_token,
+                alloc_point,
+                last_reward_block : self.env().block_number().max(self.start_block),
+                acc_reward_per_share : 0,
+                total_staked : 0,
+            });
+            self.lp_token_pools.insert(lp_token, pool_id);
+            self.total_alloc_point += alloc_point;
+
+            self.env().emit_event( PoolAdded {
+                pool_id,
+                lp_token,
+                alloc_point,
+            });
+            pool_id
+        }
+
+        /// Change the allocation points of a pool. Only the owner can change them.
+        #[ink(message)]
+        pub fn set_pool(&mut self, pool_id : PoolId, alloc_point : u128){
+            assert!(self.env().caller() == self.owner);
+            self.mass_update_pools();
+            let mut pool = self.pools.get(&pool_id).unwrap().clone();
+            self.total_alloc_point = self.total_alloc_point - pool.alloc_point + alloc_point;
+            pool.alloc_point = alloc_point;
+            self.pools.insert(pool_id, pool);
+
+            self.env().emit_event( PoolSet {
+                pool_id,
+                alloc_point,
+            });
+        }
+
+        /// Change the reward tokens distributed per block. Only the owner can change it.
+        #[ink(message)]
+        pub fn set_reward_per_block(&mut self, reward_per_block : Balance){
+            assert!(self.env().caller() == self.owner);
+            self.mass_update_pools();
+            self.reward_per_block = reward_per_block;
+        }
+
+        /// Accumulate the rewards of every pool up to the current block.
+        #[ink(message)]
+        pub fn mass_update_pools(&mut self){
+            for pool_id in 0..self.pool_length {
+                self.update_pool(pool_id);
+            }
+        }
+
+        /// Accumulate the rewards of a pool up to the current block.
+        #[ink(message)]
+        pub fn update_pool(&mut self, pool_id : PoolId){
+            let mut pool = self.pools.get(&pool_id).unwrap().clone();
+            let block_number = self.env().block_number();
+            if block_number <= pool.last_reward_block {
+                return
+            }
+            pool.acc_reward_per_share = self.acc_reward_per_share_at(&pool, block_number);
+            pool.last_reward_block = block_number;
+            self.pools.insert(pool_id, pool);
+        }
+
+        /// Stake LP tokens in a pool, the pending rewards are harvested. The LP tokens are pulled with `transfer_from`,
+        /// so the caller approves this contract on the Exchange first.
+        ///
+        /// Return The rewards harvested.
+        ///
+        /// #Params
+        ///
+        /// - `pool_id`: The pool.
+        /// - `amount`: Amount of LP tokens staked, 0 to only harvest.
+        #[ink(message)]
+        pub fn deposit(&mut self, pool_id : PoolId, amount : Balance) -> Result<Balance>{
+            let caller = self.env().caller();
+            self.update_pool(pool_id);
+            let mut pool = self.pools.get(&pool_id).unwrap().clone();
+            let mut user = self.user_info(pool_id, caller);
+            let pending = Self::pending(user.amount, pool.acc_reward_per_share, user.reward_debt);
+
+            if amount > 0 {
+                self.lp_transfer_from(pool.lp_token, caller, self.farm_account_id, amount)?;
+            }
+
+            user.amount += amount;
+            user.reward_debt = Self::reward_debt(user.amount, pool.acc_reward_per_share);
+            pool.total_staked += amount;
+            self.users.insert((pool_id, caller), user);
+            self.pools.insert(pool_id, pool);
+
+            let harvested = self.pay_reward(caller, pool_id, pending);
+            self.env().emit_event( Deposit {
+                user : caller,
+                pool_id,
+                amount,
+            });
+            Ok(harvested)
+        }
+
+        /// Unstake LP tokens from a pool, the pending rewards are harvested.
+        ///
+        /// Return The rewards harvested.
+        ///
+        /// #Params
+        ///
+        /// - `pool_id`: The pool.
+        /// - `amount`: Amount of LP tokens unstaked.
+        #[ink(message)]
+        pub fn withdraw(&mut self, pool_id : PoolId, amount : Balance) -> Result<Balance>{
+            let caller = self.env().caller();
+            let mut user = self.user_info(pool_id, caller);
+            assert!(user.amount >= amount);
+            self.update_pool(pool_id);
+            let mut pool = self.pools.get(&pool_id).unwrap().clone();
+            let pending = Self::pending(user.amount, pool.acc_reward_per_share, user.reward_debt);
+
+            if amount > 0 {
+                self.lp_transfer(pool.lp_token, caller, amount)?;
+            }
+
+            user.amount -= amount;
+            user.reward_debt = Self::reward_debt(user.amount, pool.acc_reward_per_share);
+            pool.total_staked -= amount;
+            self.users.insert((pool_id, caller), user);
+            self.pools.insert(pool_id, pool);
+
+            let harvested = self.pay_reward(caller, pool_id, pending);
+            self.env().emit_event( Withdraw {
+                user : caller,
+                pool_id,
+                amount,
+            });
+            Ok(harvested)
+        }
+
+        /// Harvest the pending rewards of the caller in a pool.
+        ///
+        /// Return The rewards harvested.
+        #[ink(message)]
+        pub fn harvest(&mut self, pool_id : PoolId) -> Balance{
+            let caller = self.env().caller();
+            self.update_pool(pool_id);
+            let pool = self.pools.get(&pool_id).unwrap().clone();
+            let mut user = self.user_info(pool_id, caller);
+            let pending = Self::pending(user.amount, pool.acc_reward_per_share, user.reward_debt);
+
+            user.reward_debt = Self::reward_debt(user.amount, pool.acc_reward_per_share);
+            self.users.insert((pool_id, caller), user);
+
+            self.pay_reward(caller, pool_id, pending)
+        }
+
+        /// Unstake all the LP tokens of the caller from a pool without the rewards. Only meant for emergencies, e.g. if
+        /// the reward token stops transferring.
+        ///
+        /// Return The LP tokens unstaked.
+        #[ink(message)]
+        pub fn emergency_withdraw(&mut self, pool_id : PoolId) -> Result<Balance>{
+            let caller = self.env().caller();
+            let user = self.user_info(pool_id, caller);
+            let mut pool = self.pools.get(&pool_id).unwrap().clone();
+            let amount = user.amount;
+
+            self.lp_transfer(pool.lp_token, caller, amount)?;
+
+            pool.total_staked -= amount;
+            self.users.take(&(pool_id, caller));
+            self.pools.insert(pool_id, pool);
+
+            self.env().emit_event( EmergencyWithdraw {
+                user : caller,
+                pool_id,
+                amount,
+            });
+            Ok(amount)
+        }
+
+        /// Return the rewards `user` can harvest from a pool.
+        #[ink(message)]
+        pub fn pending_reward(&self, pool_id : PoolId, user : AccountId) -> Balance{
+            let pool = self.pools.get(&pool_id).unwrap();
+            let user = self.user_info(pool_id, user);
+            let block_number = self.env().block_number();
+            let acc_reward_per_share = if block_number > pool.last_reward_block {
+                self.acc_reward_per_share_at(pool, block_number)
+            } else {
+                pool.acc_reward_per_share
+            };
+            Self::pending(user.amount, acc_reward_per_share, user.reward_debt)
+        }
+
+        /// Return a pool, None if it does not exist.
+        #[ink(message)]
+        pub fn pool_info(&self, pool_id : PoolId) -> Option<PoolInfo>{
+            self.pools.get(&pool_id).cloned()
+        }
+
+        /// Return the number of pools.
+        #[ink(message)]
+        pub fn pool_length(&self) -> PoolId{
+            self.pool_length
+        }
+
+        /// Return the stake of `user` in a pool.
+        #[ink(message)]
+        pub fn user_info(&self, pool_id : PoolId, user : AccountId) -> UserInfo{
+            self.users.get(&(pool_id, user)).cloned().unwrap_or_default()
+        }
+
+        /// Return the sum of the allocation points of all pools.
+        #[ink(message)]
+        pub fn total_alloc_point(&self) -> u128{
+            self.total_alloc_point
+        }
+
+        /// Return the reward tokens distributed per block across all pools.
+        #[ink(message)]
+        pub fn reward_per_block(&self) -> Balance{
+            self.reward_per_block
+        }
+
+        /// Return the reward token.
+        #[ink(message)]
+        pub fn reward_token(&self) -> AccountId{
+            self.reward_token
+        }
+
+        /// Return the owner.
+        #[ink(message)]
+        pub fn owner(&self) -> AccountId{
+            self.owner
+        }
+
+        fn acc_reward_per_share_at(&self, pool : &PoolInfo, block_number : BlockNumber) -> u128{
+            if pool.total_staked == 0 || self.total_alloc_point == 0 {
+                return pool.acc_reward_per_share
+            }
+            let reward = Self::pool_reward(block_number - pool.last_reward_block, self.reward_per_block, pool.alloc_point,
+                self.total_alloc_point);
+            let increase = U256::from(reward) * U256::from(ACC_REWARD_PRECISION) / U256::from(pool.total_staked);
+            pool.acc_reward_per_share + increase.as_u128()
+        }
+
+        /// Return the rewards of a pool over `blocks` blocks.
+        pub fn pool_reward(blocks : BlockNumber, reward_per_block : Balance, alloc_point : u128, total_alloc_point : u128) -> Balance{
+            (U256::from(blocks) * U256::from(reward_per_block) * U256::from(alloc_point) / U256::from(total_alloc_point)).as_u128()
+        }
+
+        /// Return `amount * acc_reward_per_share`, the rewards a stake of `amount` earned since the pool was added.
+        pub fn reward_debt(amount : Balance, acc_reward_per_share : u128) -> Balance{
+            (U256::from(amount) * U256::from(acc_reward_per_share) / U256::from(ACC_REWARD_PRECISION)).as_u128()
+        }
+
+        /// Return the rewards of a stake of `amount` not harvested yet.
+        pub fn pending(amount : Balance, acc_reward_per_share : u128, reward_debt : Balance) -> Balance{
+            Self::reward_debt(amount, acc_reward_per_share) - reward_debt
+        }
+
+        /// Pay `amount` reward tokens, or the reward balance of the Farm if it is lower.
+        fn pay_reward(&mut self, to : AccountId, pool_id : PoolId, amount : Balance) -> Balance{
+            if amount == 0 {
+                return 0
+            }
+            let amount = amount.min(self.reward_balance());
+            if amount > 0 {
+                self.token_transfer(self.reward_token, to, amount).expect("token transfer failed");
+                self.env().emit_event( Harvest {
+                    user : to,
+                    pool_id,
+                    amount,
+                });
+            }
+            amount
+        }
+
+        fn reward_balance(&self) -> Balance{
+            common::token::balance_of(self.reward_token, self.farm_account_id, self.gas_limit / 2)
+        }
+
+        fn lp_transfer(&mut self, lp_token : AccountId, to : AccountId, value : Balance)->Result<()>{
+            self.token_transfer(lp_token, to, value)
+        }
+
+        fn lp_transfer_from(&mut self, lp_token : AccountId, from : AccountId, to : AccountId, value : Balance)->Result<()>{
+            common::token::transfer_from(lp_token, from, to, value, self.gas_limit / 2)
+        }
+
+        fn token_transfer(&mut self, token : AccountId, to : AccountId, value : Balance)->Result<()>{
+            common::token::transfer(token, to, value, self.gas_limit / 2)
+        }
+
+        ///Return the Farm self account id.
+        #[ink(message)]
+        pub fn get_address(&self) ->AccountId{
+            self.farm_account_id
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::farm::ACC_REWARD_PRECISION;
+    use ink_env::{
+        AccountId,
+    };
+    use ink_lang as ink;
+
+    fn set_caller(caller: AccountId) {
+        let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().unwrap_or([0x0; 32].into());
+        let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+        data.push_arg(&caller);
+        ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+            caller,
+            callee,
+            1000000,
+            1000000,
+            data,
+        );
+    }
+
+    #[ink::test]
+    fn test_rewards_should_split_by_alloc_point_and_stake(){
+        //A pool with 1 of 4 allocation points gets 25 of 100 tokens per block.
+        assert_eq!(Farm::pool_reward(10, 100, 1, 4), 250);
+        //Two stakers of 100 and 300 share 250 rewards.
+        let acc_reward_per_share = 250 * ACC_REWARD_PRECISION / 400;
+        assert_eq!(Farm::pending(100, acc_reward_per_share, 0), 62);
+        assert_eq!(Farm::pending(300, acc_reward_per_share, 0), 187);
+        //The reward debt excludes the rewards earned before the deposit.
+        let reward_debt = Farm::reward_debt(300, acc_reward_per_share);
+        assert_eq!(Farm::pending(300, 2 * acc_reward_per_share, reward_debt), 188);
+    }
+
+    #[ink::test]
+    fn test_add_pool_should_split_alloc_points(){
+        let mut farm = Farm::default();
+        assert_eq!(farm.add_pool(AccountId::from([0x02; 32]), 1), 0);
+        assert_eq!(farm.add_pool(AccountId::from([0x03; 32]), 3), 1);
+        assert_eq!(farm.pool_length(), 2);
+        assert_eq!(farm.total_alloc_point(), 4);
+
+        ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+        farm.set_pool(0, 2);
+        assert_eq!(farm.total_alloc_point(), 5);
+        //Without stake the rewards of the past blocks are not accumulated.
+        let pool = farm.pool_info(0).unwrap();
+        assert_eq!((pool.last_reward_block, pool.acc_reward_per_share), (1, 0));
+        assert_eq!(farm.pending_reward(0, AccountId::from([0x04; 32])), 0);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_add_pool_should_fail_for_other_accounts(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut farm = Farm::default();
+        set_caller(accounts.bob);
+        farm.add_pool(AccountId::from([0x02; 32]), 1);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_add_pool_should_fail_for_the_reward_token(){
+        let mut farm = Farm::default();
+        farm.add_pool(AccountId::from([0x01; 32]), 1);
+    }
+}
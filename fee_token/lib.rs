@@ -0,0 +1,182 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::fee_token::FeeToken;
+
+use ink_lang as ink;
+
+#[ink::contract]
+mod fee_token {
+
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::{
+        collections::HashMap as StorageHashMap,
+    };
+
+    use ink_prelude::string::String;
+
+    #[ink(event)]
+    pub struct Transfer{
+        #[ink(topic)]
+        from   : AccountId,
+        #[ink(topic)]
+        to     : AccountId,
+        #[ink(topic)]
+        value  : Balance,
+    }
+
+    #[ink(event)]
+    pub struct Approval{
+        #[ink(topic)]
+        owner : AccountId,
+        #[ink(topic)]
+        spender:AccountId,
+        #[ink(topic)]
+        value : Balance,
+    }
+
+    /// A token burning a share of every transfer, used to test the Exchange with fee-on-transfer tokens.
+    /// It uses the selectors of the Erc20 messages the Exchange calls.
+    #[ink(storage)]
+    pub struct FeeToken {
+        pub total_supply : Balance,
+        balances : StorageHashMap<AccountId, Balance>,
+        allowances : StorageHashMap<(AccountId, AccountId), Balance>,
+        //share of each transfer burned, in basis points
+        pub fee_bps : u16,
+    }
+
+    impl FeeToken {
+
+        /// Constructor of the FeeToken contract, the initial supply is given to the deployer.
+        /// 
+        /// #Params
+        /// 
+        /// - `initial_supply`: Amount of tokens given to the deployer.
+        /// - `fee_bps`: Share of each transfer burned, in basis points.
+        #[ink(constructor)]
+        pub fn new(initial_supply : Balance, fee_bps : u16) -> Self {
+            assert!(fee_bps <= 10000);
+            let caller = Self::env().caller();
+            let mut balances = StorageHashMap::new();
+            balances.insert(caller, initial_supply);
+            Self{
+                total_supply : initial_supply,
+                balances,
+                allowances : StorageHashMap::new(),
+                fee_bps,
+            }
+        }
+
+        #[ink(constructor)]
+        pub fn default() -> Self {
+            Self::new(0, 0)
+        }
+
+        #[ink(message, selector = "0x58af15a8")]
+        pub fn token_symbol(&self) -> Option<String>{
+            Some(String::from("FEE"))
+        }
+
+        #[ink(message)]
+        pub fn total_supply(&self) -> Balance{
+            self.total_supply
+        }
+
+        #[ink(message, selector = "0x56e929b2")]
+        pub fn balance_of(&self, owner : AccountId) -> Balance{
+            self.balances.get(&owner).unwrap_or(&0u128).clone()
+        }
+
+        #[ink(message)]
+        pub fn allowance(&self, owner : AccountId, spender : AccountId) -> Balance{
+            self.allowances.get(&(owner, spender)).unwrap_or(&0u128).clone()
+        }
+
+        /// Transfer tokens of the caller, `to` receives `value` minus the fee.
+        #[ink(message, selector = "0xfae3a09d")]
+        pub fn transfer(&mut self, to : AccountId, value : Balance) -> bool{
+            let caller = self.env().caller();
+            self.transfer_from_to(caller, to, value)
+        }
+
+        /// Transfer tokens of `from` within the allowance of the caller, `to` receives `value` minus the fee.
+        #[ink(message, selector = "0xfcfb2ccd")]
+        pub fn transfer_from(&mut self, from : AccountId, to : AccountId, value : Balance) -> bool{
+            let caller = self.env().caller();
+            let allowance = self.allowance(from, caller);
+            if allowance < value {
+                return false
+            }
+            self.allowances.insert((from, caller), allowance - value);
+            self.transfer_from_to(from, to, value)
+        }
+
+        #[ink(message)]
+        pub fn approve(&mut self, spender : AccountId, value : Balance) -> bool{
+            let owner = self.env().caller();
+            self.allowances.insert((owner, spender), value);
+            self.env().emit_event( Approval {
+                owner,
+                spender,
+                value,
+            });
+            true
+        }
+
+        fn transfer_from_to(&mut self, from : AccountId, to : AccountId, value : Balance) -> bool{
+            let from_balance = self.balance_of(from);
+            if from_balance < value {
+                return false
+            }
+            let fee = value * self.fee_bps as Balance / 10000;
+            self.balances.insert(from, from_balance - value);
+            let to_balance = self.balance_of(to);
+            self.balances.insert(to, to_balance + value - fee);
+            self.total_supply -= fee;
+
+            self.env().emit_event( Transfer {
+                from,
+                to,
+                value : value - fee,
+            });
+            if fee > 0 {
+                self.env().emit_event( Transfer {
+                    from,
+                    to : AccountId::default(),
+                    value : fee,
+                });
+            }
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ink_lang as ink;
+
+    #[ink::test]
+    fn test_transfer_should_burn_fee(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = FeeToken::new(100000, 200);
+
+        assert!(contract.transfer(accounts.bob, 10000));
+        assert_eq!(contract.balance_of(accounts.alice), 90000);
+        assert_eq!(contract.balance_of(accounts.bob), 9800);
+        assert_eq!(contract.total_supply(), 99800);
+        assert!(!contract.transfer(accounts.bob, 90001));
+    }
+
+    #[ink::test]
+    fn test_transfer_from_should_burn_fee(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = FeeToken::new(100000, 200);
+
+        assert!(!contract.transfer_from(accounts.alice, accounts.bob, 10000));
+        assert!(contract.approve(accounts.alice, 10000));
+        assert!(contract.transfer_from(accounts.alice, accounts.bob, 10000));
+        assert_eq!(contract.balance_of(accounts.bob), 9800);
+        assert_eq!(contract.allowance(accounts.alice, accounts.alice), 0);
+    }
+}
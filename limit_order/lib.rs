@@ -0,0 +1,573 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::limit_order::LimitOrder;
+
+use ink_lang as ink;
+
+#[ink::contract]
+mod limit_order {
+
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::{
+        collections::HashMap as StorageHashMap,
+        traits::{PackedLayout, SpreadLayout},
+    };
+
+    #[cfg(all(not(feature = "ink-as-dependency"), not(test)))]
+    use ink_env::{
+         call,
+         DefaultEnvironment,
+         call::{ build_call, ExecutionInput, utils::ReturnType,}
+    };
+
+    #[cfg(not(test))]
+    use exchange::TokenReceivedAction;
+
+    use primitive_types::U256;
+
+    /// Serial number of an order.
+    pub type OrderId = u128;
+
+    /// Limit prices are multiplied by it.
+    pub const PRICE_PRECISION : u128 = 1_000_000_000_000_000_000;
+
+    /// What an order sells on its Exchange.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub enum OrderSide {
+        /// Sell the escrowed Dot for the Exchange token.
+        DotToToken,
+        /// Sell the escrowed Exchange token for Dot.
+        TokenToDot,
+    }
+
+    /// An order selling an escrowed amount on an Exchange at a limit price or better.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub struct Order {
+        //the account which placed the order and receives the proceeds
+        pub maker : AccountId,
+        pub exchange : AccountId,
+        //the Exchange token, escrowed by TokenToDot orders
+        pub token : AccountId,
+        pub side : OrderSide,
+        //amount sold by the order
+        pub amount_in : Balance,
+        //amount still escrowed, 0 once the order is filled or cancelled
+        pub remaining : Balance,
+        //amount bought by the fills so far, as quoted by the Exchange when each fill sold
+        pub amount_out : Balance,
+        //minimum amount bought per amount sold, multiplied by PRICE_PRECISION
+        pub limit_price : u128,
+        //time after which the order can no longer be filled
+        pub expiry : Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct OrderPlaced {
+        #[ink(topic)]
+        order_id : OrderId,
+        #[ink(topic)]
+        maker : AccountId,
+        #[ink(topic)]
+        exchange : AccountId,
+        side : OrderSide,
+        amount_in : Balance,
+        limit_price : u128,
+        expiry : Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct OrderFilled {
+        #[ink(topic)]
+        order_id : OrderId,
+        #[ink(topic)]
+        keeper : AccountId,
+        amount_in : Balance,
+        amount_out : Balance,
+    }
+
+    #[ink(event)]
+    pub struct OrderCancelled {
+        #[ink(topic)]
+        order_id : OrderId,
+        refunded : Balance,
+    }
+
+    pub use common::token::{Error, Result};
+
+    /// Limit orders settled against Exchange liquidity. Makers escrow Dot or tokens with a limit price and an expiry,
+    /// then anyone can fill an order, wholly or partly, once the Exchange price reaches the limit.
+    #[ink(storage)]
+    pub struct LimitOrder {
+        orders : StorageHashMap<OrderId, Order>,
+        order_count : OrderId,
+        //true while a fill calls the Exchange, the orders can not be placed, filled or cancelled meanwhile
+        filling : bool,
+        gas_limit :u64,
+        limit_order_account_id : AccountId,
+    }
+
+    impl LimitOrder {
+
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self{
+                orders : StorageHashMap::new(),
+                order_count : 0,
+                filling : false,
+                gas_limit : 507085500000,
+                limit_order_account_id : Self::env().account_id(),
+            }
+        }
+
+        #[ink(constructor)]
+        pub fn default() -> Self {
+            Self::new()
+        }
+
+        /// Place an order selling the transferred Dot for the Exchange token.
+        ///
+        /// Return The order id.
+        ///
+        /// #Params
+        ///
+        /// - `exchange`: The Exchange the order is filled on.
+        /// - `limit_price`: Minimum tokens bought per Dot sold, multiplied by PRICE_PRECISION.
+        /// - `expiry`: Time after which the order can no longer be filled.
+        #[ink(message, payable)]
+        pub fn place_dot_order(&mut self, exchange : AccountId, limit_price : u128, expiry : Timestamp) -> OrderId{
+            let amount_in = self.env().transferred_balance();
+            self.place_order(exchange, AccountId::default(), OrderSide::DotToToken, amount_in, limit_price, expiry)
+        }
+
+        /// Place an order selling tokens for Dot. The tokens are escrowed with `transfer_from`, so the caller approves
+        /// this contract first.
+        ///
+        /// Return The order id.
+        ///
+        /// #Params
+        ///
+        /// - `exchange`: The Exchange the order is filled on.
+        /// - `token`: The Exchange token.
+        /// - `amount_in`: Amount of tokens sold.
+        /// - `limit_price`: Minimum Dot bought per token sold, multiplied by PRICE_PRECISION.
+        /// - `expiry`: Time after which the order can no longer be filled.
+        #[ink(message)]
+        pub fn place_token_order(&mut self, exchange : AccountId, token : AccountId, amount_in : Balance, limit_price : u128,
+            expiry : Timestamp) -> Result<OrderId>{
+            assert!(!self.filling);
+            assert!(token != AccountId::default());
+            let caller = self.env().caller();
+            self.token_transfer_from(token, caller, self.limit_order_account_id, amount_in)?;
+            Ok(self.place_order(exchange, token, OrderSide::TokenToDot, amount_in, limit_price, expiry))
+        }
+
+        fn place_order(&mut self, exchange : AccountId, token : AccountId, side : OrderSide, amount_in : Balance,
+            limit_price : u128, expiry : Timestamp) -> OrderId{
+            assert!(!self.filling);
+            assert!(exchange != AccountId::default());
+            assert!(amount_in > 0 && limit_price > 0 && expiry > self.env().block_timestamp());
+            let maker = self.env().caller();
+            let order_id = self.order_count + 1;
+            self.order_count = order_id;
+            self.orders.insert(order_id, Order {
+                maker,
+                exchange,
+                token,
+                side,
+                amount_in,
+                remaining : amount_in,
+                amount_out : 0,
+                limit_price,
+                expiry,
+            });
+
+            self.env().emit_event( OrderPlaced {
+                order_id,
+                maker,
+                exchange,
+                side,
+                amount_in,
+                limit_price,
+                expiry,
+            });
+            order_id
+        }
+
+        /// Fill an order, or part of it, through its Exchange. The proceeds are sent to the maker.
+        ///
+        /// Return The amount bought.
+        ///
+        /// #Params
+        ///
+        /// - `order_id`: The order.
+        /// - `amount_in`: Amount of the escrow sold, at most the remaining amount.
+        ///
+        /// NOTE: Anyone can fill an order before its expiry, once the Exchange pays the limit price for `amount_in`.
+        /// The swap is bounded by the limit price, so it fails rather than fill at a worse price. The Exchange pays the
+        /// maker directly, so the amount bought of a TokenToDot fill is the Exchange quote, which the swap sells at.
+        #[ink(message)]
+        pub fn fill(&mut self, order_id : OrderId, amount_in : Balance) -> Result<Balance>{
+            assert!(!self.filling);
+            let mut order = self.orders.get(&order_id).unwrap().clone();
+            assert!(self.env().block_timestamp() <= order.expiry);
+            assert!(amount_in > 0 && amount_in <= order.remaining);
+            let min_amount_out = Self::min_amount_out(amount_in, order.limit_price);
+            assert!(min_amount_out > 0);
+            let quoted = self.quote(order_id, amount_in);
+            assert!(quoted >= min_amount_out);
+
+            order.remaining -= amount_in;
+            self.orders.insert(order_id, order.clone());
+
+            //The Exchange and the token are chosen by the maker and may call back into this contract.
+            self.filling = true;
+            common::storage::flush(self);
+            let amount_out = match order.side {
+                OrderSide::DotToToken => self.exchange_dot_to_token(&order, amount_in, min_amount_out),
+                OrderSide::TokenToDot => self.exchange_token_to_dot(&order, amount_in, min_amount_out).map(|_| quoted),
+            };
+            common::storage::reload(self);
+            self.filling = false;
+
+            let mut order = self.orders.get(&order_id).unwrap().clone();
+            let amount_out = match amount_out {
+                Ok(amount_out) => amount_out,
+                Err(error) => {
+                    order.remaining += amount_in;
+                    self.orders.insert(order_id, order);
+                    return Err(error)
+                }
+            };
+            order.amount_out += amount_out;
+            self.orders.insert(order_id, order);
+
+            self.env().emit_event( OrderFilled {
+                order_id,
+                keeper : self.env().caller(),
+                amount_in,
+                amount_out,
+            });
+            Ok(amount_out)
+        }
+
+        /// Cancel an order and refund the remaining escrow to the maker.
+        ///
+        /// Return The amount refunded.
+        ///
+        /// NOTE: The maker can cancel an order at any time, anyone else once it expired.
+        #[ink(message)]
+        pub fn cancel(&mut self, order_id : OrderId) -> Result<Balance>{
+            assert!(!self.filling);
+            let mut order = self.orders.get(&order_id).unwrap().clone();
+            assert!(self.env().caller() == order.maker || self.env().block_timestamp() > order.expiry);
+            let refunded = order.remaining;
+            assert!(refunded > 0);
+
+            order.remaining = 0;
+            self.orders.insert(order_id, order.clone());
+
+            match order.side {
+                OrderSide::DotToToken => self.env().transfer(order.maker, refunded).expect("transfer error"),
+                OrderSide::TokenToDot => {
+                    common::storage::flush(self);
+                    let transferred = self.token_transfer(order.token, order.maker, refunded);
+                    common::storage::reload(self);
+                    if let Err(error) = transferred {
+                        let mut order = self.orders.get(&order_id).unwrap().clone();
+                        order.remaining = refunded;
+                        self.orders.insert(order_id, order);
+                        return Err(error)
+                    }
+                }
+            }
+
+            self.env().emit_event( OrderCancelled {
+                order_id,
+                refunded,
+            });
+            Ok(refunded)
+        }
+
+        /// Return the amount the Exchange of an order currently pays for `amount_in` of its escrow.
+        #[ink(message)]
+        pub fn quote(&self, order_id : OrderId, amount_in : Balance) -> Balance{
+            self.exchange_quote(self.orders.get(&order_id).unwrap(), amount_in)
+        }
+
+        #[cfg(not(test))]
+        fn exchange_quote(&self, order : &Order, amount_in : Balance) -> Balance{
+            let selector = match order.side {
+                //dot_to_token_input_price selector in exchange 0xc10785bc
+                OrderSide::DotToToken => call::Selector::new([0xc1, 0x07, 0x85, 0xbc]),
+                //token_to_dot_input_price selector in exchange 0xe486494d
+                OrderSide::TokenToDot => call::Selector::new([0xe4, 0x86, 0x49, 0x4d]),
+            };
+            build_call::<DefaultEnvironment>()
+                .callee(order.exchange)
+                .gas_limit(self.gas_limit / 2)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(selector.into())
+                    .push_arg(amount_in),
+                ).returns::<ReturnType<Balance>>().fire().unwrap()
+        }
+
+        /// Return true if `amount_in` of an order can be filled now.
+        #[ink(message)]
+        pub fn is_fillable(&self, order_id : OrderId, amount_in : Balance) -> bool{
+            let order = match self.orders.get(&order_id) {
+                Some(order) => order,
+                None => return false,
+            };
+            if self.env().block_timestamp() > order.expiry || amount_in == 0 || amount_in > order.remaining {
+                return false
+            }
+            let min_amount_out = Self::min_amount_out(amount_in, order.limit_price);
+            min_amount_out > 0 && self.quote(order_id, amount_in) >= min_amount_out
+        }
+
+        /// Return an order, None if it does not exist.
+        #[ink(message)]
+        pub fn order(&self, order_id : OrderId) -> Option<Order>{
+            self.orders.get(&order_id).cloned()
+        }
+
+        /// Return the number of orders placed.
+        #[ink(message)]
+        pub fn order_count(&self) -> OrderId{
+            self.order_count
+        }
+
+        /// Return the minimum amount bought by selling `amount_in` at `limit_price`.
+        pub fn min_amount_out(amount_in : Balance, limit_price : u128) -> Balance{
+            (U256::from(amount_in) * U256::from(limit_price) / U256::from(PRICE_PRECISION)).as_u128()
+        }
+
+        #[cfg(not(test))]
+        fn exchange_dot_to_token(&mut self, order : &Order, amount_in : Balance, min_amount_out : Balance) -> Result<Balance>{
+            //dot_to_token_transfer_input selector in exchange 0xa0a8e619
+            let selector_dot_to_token_transfer_input = call::Selector::new([0xa0, 0xa8, 0xe6, 0x19]);
+            //The Exchange refunds the Dot if it fails.
+            build_call::<DefaultEnvironment>()
+                .callee(order.exchange)
+                .gas_limit(self.gas_limit)
+                .transferred_value(amount_in)
+                .exec_input(
+                    ExecutionInput::new(selector_dot_to_token_transfer_input.into())
+                    .push_arg(min_amount_out)
+                    .push_arg(self.env().block_timestamp())
+                    .push_arg(order.maker),
+                ).returns::<ReturnType<Result<Balance>>>().fire().unwrap()
+        }
+
+        /// Sell the tokens with the token `transfer_and_call`, the Exchange pays at least `min_amount_out` to the maker.
+        #[cfg(not(test))]
+        fn exchange_token_to_dot(&mut self, order : &Order, amount_in : Balance, min_amount_out : Balance) -> Result<()>{
+            let data = scale::Encode::encode(&TokenReceivedAction::TokenToDot {
+                min_dot : min_amount_out,
+                deadline : self.env().block_timestamp(),
+                recipient : order.maker,
+            });
+            //transfer_and_call selector in erc20 0x0e60abc1, the Exchange sells the tokens in `on_token_received`
+            let selector_transfer_and_call = call::Selector::new([0x0e, 0x60, 0xab, 0xc1]);
+            let transferred = build_call::<DefaultEnvironment>()
+                .callee(order.token)
+                .gas_limit(self.gas_limit)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(selector_transfer_and_call.into())
+                    .push_arg(&order.exchange)
+                    .push_arg(amount_in)
+                    .push_arg(data),
+                ).returns::<ReturnType<bool>>().fire().unwrap();
+            if transferred { Ok(()) } else { Err(Error::TokenTransferFailed) }
+        }
+
+        /// The off-chain environment can not call contracts, the tests set the Exchange price instead.
+        #[cfg(test)]
+        fn exchange_quote(&self, _order : &Order, amount_in : Balance) -> Balance{
+            Self::min_amount_out(amount_in, crate::tests::EXCHANGE_PRICE.with(|price| price.get()))
+        }
+
+        #[cfg(test)]
+        fn exchange_dot_to_token(&mut self, order : &Order, amount_in : Balance, _min_amount_out : Balance) -> Result<Balance>{
+            self.exchange_token_to_dot(order, amount_in, 0)?;
+            Ok(self.exchange_quote(order, amount_in))
+        }
+
+        /// The Exchange fails if the tests set it to, and it calls `fill` again if the tests set it to re-enter.
+        #[cfg(test)]
+        fn exchange_token_to_dot(&mut self, _order : &Order, amount_in : Balance, _min_amount_out : Balance) -> Result<()>{
+            if crate::tests::EXCHANGE_REENTERS.with(|reenters| reenters.get()) {
+                self.fill(1, amount_in)?;
+            }
+            if crate::tests::EXCHANGE_FAILS.with(|fails| fails.get()) { Err(Error::TokenTransferFailed) } else { Ok(()) }
+        }
+
+        #[cfg(not(test))]
+        fn token_transfer(&mut self, token : AccountId, to : AccountId, value : Balance)->Result<()>{
+            common::token::transfer(token, to, value, self.gas_limit / 2)
+        }
+
+        #[cfg(not(test))]
+        fn token_transfer_from(&mut self, token : AccountId, from : AccountId, to : AccountId, value : Balance) ->Result<()>{
+            common::token::transfer_from(token, from, to, value, self.gas_limit / 2)
+        }
+
+        /// The tests assume the tokens always transfer.
+        #[cfg(test)]
+        fn token_transfer(&mut self, _token : AccountId, _to : AccountId, _value : Balance)->Result<()>{
+            Ok(())
+        }
+
+        #[cfg(test)]
+        fn token_transfer_from(&mut self, _token : AccountId, _from : AccountId, _to : AccountId, _value : Balance) ->Result<()>{
+            Ok(())
+        }
+
+        ///Return the LimitOrder self account id.
+        #[ink(message)]
+        pub fn get_address(&self) ->AccountId{
+            self.limit_order_account_id
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::limit_order::{Error, OrderSide, PRICE_PRECISION};
+    use ink_env::{
+        AccountId,
+    };
+    use ink_lang as ink;
+
+    thread_local! {
+        /// Tokens bought per Dot, or Dot bought per token, by the Exchange, multiplied by PRICE_PRECISION.
+        pub(crate) static EXCHANGE_PRICE: core::cell::Cell<u128> = core::cell::Cell::new(PRICE_PRECISION);
+        /// Whether the Exchange swap fails.
+        pub(crate) static EXCHANGE_FAILS: core::cell::Cell<bool> = core::cell::Cell::new(false);
+        /// Whether the Exchange calls back into the LimitOrder during a fill.
+        pub(crate) static EXCHANGE_REENTERS: core::cell::Cell<bool> = core::cell::Cell::new(false);
+    }
+
+    fn set_caller(caller: AccountId) {
+        let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().unwrap_or([0x0; 32].into());
+        let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+        data.push_arg(&caller);
+        ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+            caller,
+            callee,
+            1000000,
+            1000000,
+            data,
+        );
+    }
+
+    fn set_exchange(price : u128, fails : bool, reenters : bool) {
+        EXCHANGE_PRICE.with(|cell| cell.set(price));
+        EXCHANGE_FAILS.with(|cell| cell.set(fails));
+        EXCHANGE_REENTERS.with(|cell| cell.set(reenters));
+    }
+
+    #[ink::test]
+    fn test_min_amount_out_should_apply_limit_price(){
+        assert_eq!(LimitOrder::min_amount_out(1_000, 2 * PRICE_PRECISION), 2_000);
+        assert_eq!(LimitOrder::min_amount_out(1_000, PRICE_PRECISION / 4), 250);
+        assert_eq!(LimitOrder::min_amount_out(u128::MAX / 2, 2 * PRICE_PRECISION), u128::MAX - 1);
+    }
+
+    #[ink::test]
+    fn test_place_dot_order_should_escrow_transferred_dot(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut limit_order = LimitOrder::new();
+        set_caller(accounts.alice);
+        let order_id = limit_order.place_dot_order(accounts.django, PRICE_PRECISION, 100);
+        assert_eq!(order_id, 1);
+        assert_eq!(limit_order.order_count(), 1);
+        let order = limit_order.order(order_id).unwrap();
+        assert_eq!(order.maker, accounts.alice);
+        assert_eq!(order.side, OrderSide::DotToToken);
+        assert_eq!((order.amount_in, order.remaining, order.amount_out), (1000000, 1000000, 0));
+        //Unknown orders and amounts above the escrow are never fillable.
+        assert!(!limit_order.is_fillable(2, 1));
+        assert!(!limit_order.is_fillable(order_id, 1000001));
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_cancel_should_fail_for_other_accounts_before_expiry(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut limit_order = LimitOrder::new();
+        set_caller(accounts.alice);
+        let order_id = limit_order.place_dot_order(accounts.django, PRICE_PRECISION, 100);
+        set_caller(accounts.bob);
+        let _ = limit_order.cancel(order_id);
+    }
+
+    #[ink::test]
+    fn test_fill_should_pay_the_maker_at_the_exchange_price(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut limit_order = LimitOrder::new();
+        set_caller(accounts.alice);
+        let order_id = limit_order.place_dot_order(accounts.django, 2 * PRICE_PRECISION, 100);
+        set_exchange(3 * PRICE_PRECISION, false, false);
+        assert!(limit_order.is_fillable(order_id, 400000));
+
+        set_caller(accounts.bob);
+        assert_eq!(limit_order.fill(order_id, 400000), Ok(1200000));
+        let order = limit_order.order(order_id).unwrap();
+        assert_eq!((order.remaining, order.amount_out), (600000, 1200000));
+    }
+
+    #[ink::test]
+    fn test_fill_token_order_should_count_the_quote(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut limit_order = LimitOrder::new();
+        set_caller(accounts.alice);
+        let order_id = limit_order.place_token_order(accounts.django, accounts.eve, 1000, PRICE_PRECISION / 2, 100).unwrap();
+        assert_eq!(limit_order.order(order_id).unwrap().side, OrderSide::TokenToDot);
+        set_exchange(PRICE_PRECISION, false, false);
+
+        set_caller(accounts.bob);
+        assert_eq!(limit_order.fill(order_id, 1000), Ok(1000));
+        let order = limit_order.order(order_id).unwrap();
+        assert_eq!((order.remaining, order.amount_out), (0, 1000));
+    }
+
+    #[ink::test]
+    fn test_failed_fill_should_keep_the_escrow(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut limit_order = LimitOrder::new();
+        set_caller(accounts.alice);
+        let order_id = limit_order.place_dot_order(accounts.django, PRICE_PRECISION, 100);
+        set_exchange(PRICE_PRECISION, true, false);
+        assert_eq!(limit_order.fill(order_id, 1000), Err(Error::TokenTransferFailed));
+        let order = limit_order.order(order_id).unwrap();
+        assert_eq!((order.remaining, order.amount_out), (1000000, 0));
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_fill_below_limit_price_should_panic(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut limit_order = LimitOrder::new();
+        set_caller(accounts.alice);
+        let order_id = limit_order.place_dot_order(accounts.django, 2 * PRICE_PRECISION, 100);
+        set_exchange(PRICE_PRECISION, false, false);
+        let _ = limit_order.fill(order_id, 1000);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_fill_should_not_be_reentered(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut limit_order = LimitOrder::new();
+        set_caller(accounts.alice);
+        let order_id = limit_order.place_dot_order(accounts.django, PRICE_PRECISION, 100);
+        set_exchange(PRICE_PRECISION, false, true);
+        let _ = limit_order.fill(order_id, 1000);
+    }
+}
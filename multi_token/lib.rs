@@ -0,0 +1,561 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::multi_token::MultiToken;
+
+use ink_lang as ink;
+
+#[ink::contract]
+mod multi_token {
+
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::{
+        collections::HashMap as StorageHashMap,
+    };
+
+    #[cfg(all(not(feature = "ink-as-dependency"), not(test)))]
+    use ink_env::{
+         call,
+         DefaultEnvironment,
+         call::{ build_call, ExecutionInput, utils::ReturnType,}
+    };
+
+    use ink_prelude::vec::Vec;
+
+    /// Identifier of a fungible token managed by the contract.
+    pub type TokenId = u128;
+
+    /// Selector of `on_multi_token_received(operator, from, ids, values, data) -> bool`,
+    /// the message a receiver implements to be notified by `safe_transfer_from` and `safe_batch_transfer_from`.
+    pub const ON_MULTI_TOKEN_RECEIVED_SELECTOR : [u8; 4] = [0xd6, 0x2e, 0x11, 0x3b];
+
+    #[ink(event)]
+    pub struct TransferSingle {
+        #[ink(topic)]
+        operator : AccountId,
+        #[ink(topic)]
+        from : AccountId,
+        #[ink(topic)]
+        to : AccountId,
+        id : TokenId,
+        value : Balance,
+    }
+
+    #[ink(event)]
+    pub struct TransferBatch {
+        #[ink(topic)]
+        operator : AccountId,
+        #[ink(topic)]
+        from : AccountId,
+        #[ink(topic)]
+        to : AccountId,
+        ids : Vec<TokenId>,
+        values : Vec<Balance>,
+    }
+
+    #[ink(event)]
+    pub struct ApprovalForAll {
+        #[ink(topic)]
+        owner : AccountId,
+        #[ink(topic)]
+        operator : AccountId,
+        approved : bool,
+    }
+
+    #[ink(storage)]
+    pub struct MultiToken {
+        //the account creating token ids, e.g. the Factory
+        owner : AccountId,
+        //the only account allowed to mint and burn each token id, e.g. the Exchange of a lp token
+        minters : StorageHashMap<TokenId, AccountId>,
+        total_supply : StorageHashMap<TokenId, Balance>,
+        balances : StorageHashMap<(TokenId, AccountId), Balance>,
+        operator_approvals : StorageHashMap<(AccountId, AccountId), bool>,
+    }
+
+    impl MultiToken {
+
+        /// Constructor of the MultiToken contract
+        /// 
+        /// NOTE: The deployer becomes the owner, the Factory should be made owner with `transfer_ownership`
+        /// before it creates lp tokens in this contract.
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self{
+                owner : Self::env().caller(),
+                minters : StorageHashMap::new(),
+                total_supply : StorageHashMap::new(),
+                balances : StorageHashMap::new(),
+                operator_approvals : StorageHashMap::new(),
+            }
+        }
+
+        #[ink(constructor)]
+        pub fn default() -> Self {
+            Self::new()
+        }
+
+        /// Return the account allowed to create token ids.
+        #[ink(message)]
+        pub fn owner(&self) -> AccountId{
+            self.owner
+        }
+
+        /// Transfer the ownership of the contract, only the owner can transfer it.
+        #[ink(message)]
+        pub fn transfer_ownership(&mut self, new_owner : AccountId){
+            assert!(self.env().caller() == self.owner);
+            self.owner = new_owner;
+        }
+
+        /// Create a token id and mint its initial supply.
+        /// 
+        /// NOTE: Only the owner can create token ids, an id can be created only once.
+        /// 
+        /// #Params
+        /// 
+        /// - `id`: The new token id.
+        /// - `minter`: The only account allowed to mint and burn the token.
+        /// - `to`: The account receiving the initial supply.
+        /// - `initial_supply`: Amount of tokens minted to `to`.
+        #[ink(message, selector = "0x648ab1de")]
+        pub fn create_token(&mut self, id : TokenId, minter : AccountId, to : AccountId, initial_supply : Balance){
+            assert!(self.env().caller() == self.owner);
+            assert!(self.minters.get(&id).is_none() && minter != AccountId::default());
+
+            self.minters.insert(id, minter);
+            if initial_supply > 0 {
+                self.mint_to(to, id, initial_supply);
+            }
+        }
+
+        /// Return the account allowed to mint and burn a token id.
+        #[ink(message)]
+        pub fn minter(&self, id : TokenId) -> Option<AccountId>{
+            self.minters.get(&id).cloned()
+        }
+
+        /// Mint tokens of an id, only the minter of the id can mint.
+        /// 
+        /// #Params
+        /// 
+        /// - `to`: The account receiving the tokens.
+        /// - `id`: The token id.
+        /// - `value`: Amount of tokens minted.
+        #[ink(message, selector = "0x23f2c9ae")]
+        pub fn mint(&mut self, to : AccountId, id : TokenId, value : Balance) -> bool{
+            assert!(self.minters.get(&id) == Some(&self.env().caller()));
+            self.mint_to(to, id, value);
+            true
+        }
+
+        /// Burn tokens of an id, only the minter of the id can burn.
+        /// 
+        /// Return false if `from` does not hold enough tokens.
+        /// 
+        /// #Params
+        /// 
+        /// - `from`: The account the tokens are burned from.
+        /// - `id`: The token id.
+        /// - `value`: Amount of tokens burned.
+        #[ink(message, selector = "0xee56cc65")]
+        pub fn burn(&mut self, from : AccountId, id : TokenId, value : Balance) -> bool{
+            let caller = self.env().caller();
+            assert!(self.minters.get(&id) == Some(&caller));
+            let from_balance = self.balance_of(from, id);
+            if from_balance < value {
+                return false
+            }
+            self.balances.insert((id, from), from_balance - value);
+            let total_supply = self.total_supply(id);
+            self.total_supply.insert(id, total_supply - value);
+
+            self.env().emit_event( TransferSingle {
+                operator : caller,
+                from,
+                to : AccountId::default(),
+                id,
+                value,
+            });
+            true
+        }
+
+        /// Return the amount of tokens of an id held by the owner.
+        #[ink(message, selector = "0x4abb36a8")]
+        pub fn balance_of(&self, owner : AccountId, id : TokenId) -> Balance{
+            self.balances.get(&(id, owner)).unwrap_or(&0u128).clone()
+        }
+
+        /// Return the balances of several (owner, id) pairs in one read.
+        #[ink(message)]
+        pub fn balance_of_batch(&self, owners : Vec<AccountId>, ids : Vec<TokenId>) -> Vec<Balance>{
+            assert!(owners.len() == ids.len());
+            owners.iter().zip(ids.iter()).map(|(owner, id)| self.balance_of(*owner, *id)).collect()
+        }
+
+        /// Return the total supply of a token id.
+        #[ink(message)]
+        pub fn total_supply(&self, id : TokenId) -> Balance{
+            self.total_supply.get(&id).unwrap_or(&0u128).clone()
+        }
+
+        /// Allow or disallow an operator to transfer all tokens of the caller.
+        #[ink(message)]
+        pub fn set_approval_for_all(&mut self, operator : AccountId, approved : bool){
+            let owner = self.env().caller();
+            assert!(owner != operator);
+            self.operator_approvals.insert((owner, operator), approved);
+
+            self.env().emit_event( ApprovalForAll {
+                owner,
+                operator,
+                approved,
+            });
+        }
+
+        /// Return whether the operator can transfer all tokens of the owner.
+        #[ink(message)]
+        pub fn is_approved_for_all(&self, owner : AccountId, operator : AccountId) -> bool{
+            *self.operator_approvals.get(&(owner, operator)).unwrap_or(&false)
+        }
+
+        /// Transfer tokens of an id from an account to another account.
+        /// 
+        /// Return false if the caller is neither `from`, an approved operator nor the minter of the id, or `from` does not
+        /// hold enough tokens. The minter can already burn the tokens of any account, it transfers them for its own
+        /// token, e.g. an Exchange moving the liquidity it keeps here.
+        /// 
+        /// NOTE: If `to` is a contract, it is called with `on_multi_token_received` and the transfer is reverted unless
+        /// it returns true, as in ERC-1155. Transfers made by the minter are not notified, since they back the
+        /// erc20-like transfers of the minter, e.g. the liquidity an Exchange moves to a farm.
+        /// 
+        /// #Params
+        /// 
+        /// - `from`: An account paying the tokens.
+        /// - `to`: An account receiving the tokens.
+        /// - `id`: The token id.
+        /// - `value`: Amount of tokens transferred.
+        /// - `data`: Data passed to the receiver.
+        #[ink(message, selector = "0x1ab270b2")]
+        pub fn safe_transfer_from(&mut self, from : AccountId, to : AccountId, id : TokenId, value : Balance, data : Vec<u8>) -> bool{
+            let operator = self.env().caller();
+            let is_minter = self.minters.get(&id) == Some(&operator) && to != AccountId::default();
+            if !(is_minter || self.can_transfer(operator, from, to)) || self.balance_of(from, id) < value {
+                return false
+            }
+            self.transfer_from_to(from, to, id, value);
+
+            self.env().emit_event( TransferSingle {
+                operator,
+                from,
+                to,
+                id,
+                value,
+            });
+
+            if !is_minter && self.is_contract(to) {
+                self.notify_receiver(operator, from, to, ink_prelude::vec![id], ink_prelude::vec![value], data);
+            }
+            true
+        }
+
+        /// Transfer tokens of several ids from an account to another account.
+        /// 
+        /// Either all transfers succeed or none is made.
+        /// 
+        /// NOTE: If `to` is a contract, it is notified as by `safe_transfer_from`, also when the caller is a minter.
+        /// 
+        /// #Params
+        /// 
+        /// - `from`: An account paying the tokens.
+        /// - `to`: An account receiving the tokens.
+        /// - `ids`: The token ids.
+        /// - `values`: Amount of tokens transferred for each id.
+        /// - `data`: Data passed to the receiver.
+        #[ink(message)]
+        pub fn safe_batch_transfer_from(&mut self, from : AccountId, to : AccountId, ids : Vec<TokenId>, values : Vec<Balance>, data : Vec<u8>) -> bool{
+            let operator = self.env().caller();
+            assert!(ids.len() == values.len());
+            if !self.can_transfer(operator, from, to) {
+                return false
+            }
+
+            // The same id may appear several times, so the totals are checked before any balance is moved.
+            let mut totals : Vec<(TokenId, Balance)> = Vec::new();
+            for (id, value) in ids.iter().zip(values.iter()) {
+                match totals.iter_mut().find(|(total_id, _)| total_id == id) {
+                    Some((_, total)) => match total.checked_add(*value) {
+                        Some(sum) => *total = sum,
+                        None => return false,
+                    },
+                    None => totals.push((*id, *value)),
+                }
+            }
+            if totals.iter().any(|(id, total)| self.balance_of(from, *id) < *total) {
+                return false
+            }
+
+            for (id, value) in ids.iter().zip(values.iter()) {
+                self.transfer_from_to(from, to, *id, *value);
+            }
+
+            self.env().emit_event( TransferBatch {
+                operator,
+                from,
+                to,
+                ids : ids.clone(),
+                values : values.clone(),
+            });
+
+            if self.is_contract(to) {
+                self.notify_receiver(operator, from, to, ids, values, data);
+            }
+            true
+        }
+
+        fn can_transfer(&self, operator : AccountId, from : AccountId, to : AccountId) -> bool{
+            to != AccountId::default() && (operator == from || self.is_approved_for_all(from, operator))
+        }
+
+        fn transfer_from_to(&mut self, from : AccountId, to : AccountId, id : TokenId, value : Balance){
+            let from_balance = self.balance_of(from, id);
+            self.balances.insert((id, from), from_balance - value);
+            let to_balance = self.balance_of(to, id);
+            self.balances.insert((id, to), to_balance + value);
+        }
+
+        fn mint_to(&mut self, to : AccountId, id : TokenId, value : Balance){
+            assert!(to != AccountId::default());
+            let total_supply = self.total_supply(id);
+            self.total_supply.insert(id, total_supply.checked_add(value).expect("total supply overflow"));
+            let to_balance = self.balance_of(to, id);
+            self.balances.insert((id, to), to_balance + value);
+
+            self.env().emit_event( TransferSingle {
+                operator : self.env().caller(),
+                from : AccountId::default(),
+                to,
+                id,
+                value,
+            });
+        }
+
+        fn notify_receiver(&mut self, operator : AccountId, from : AccountId, to : AccountId, ids : Vec<TokenId>, values : Vec<Balance>, data : Vec<u8>){
+            // The receiver may already transfer the tokens it received.
+            common::storage::flush(self);
+            let accepted = self.call_receiver(operator, from, to, ids, values, data);
+            assert!(accepted);
+            common::storage::reload(self);
+        }
+
+        #[cfg(not(test))]
+        fn is_contract(&self, account : AccountId) -> bool{
+            self.env().is_contract(&account)
+        }
+
+        #[cfg(not(test))]
+        fn call_receiver(&mut self, operator : AccountId, from : AccountId, to : AccountId, ids : Vec<TokenId>, values : Vec<Balance>, data : Vec<u8>) -> bool{
+            build_call::<DefaultEnvironment>()
+                .callee(to)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(call::Selector::new(ON_MULTI_TOKEN_RECEIVED_SELECTOR))
+                    .push_arg(operator)
+                    .push_arg(from)
+                    .push_arg(ids)
+                    .push_arg(values)
+                    .push_arg(data),
+                ).returns::<ReturnType<bool>>().fire().expect("on_multi_token_received failed")
+        }
+
+        /// The off-chain environment has no contract accounts, the tests set whether the receiver is one instead.
+        #[cfg(test)]
+        fn is_contract(&self, _account : AccountId) -> bool{
+            crate::tests::RECEIVER_IS_CONTRACT.with(|is_contract| is_contract.get())
+        }
+
+        #[cfg(test)]
+        fn call_receiver(&mut self, _operator : AccountId, _from : AccountId, _to : AccountId, _ids : Vec<TokenId>, _values : Vec<Balance>, _data : Vec<u8>) -> bool{
+            crate::tests::RECEIVER_NOTIFICATIONS.with(|notifications| notifications.set(notifications.get() + 1));
+            crate::tests::RECEIVER_ACCEPTS.with(|accepts| accepts.get())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ink_env::{
+        AccountId,
+    };
+    use ink_lang as ink;
+    use ink_prelude::vec;
+
+    thread_local! {
+        /// Whether the receiver of the transfers is a contract.
+        pub(crate) static RECEIVER_IS_CONTRACT: core::cell::Cell<bool> = core::cell::Cell::new(false);
+        /// Whether the receiver accepts the tokens.
+        pub(crate) static RECEIVER_ACCEPTS: core::cell::Cell<bool> = core::cell::Cell::new(true);
+        /// Number of times the receiver was notified.
+        pub(crate) static RECEIVER_NOTIFICATIONS: core::cell::Cell<u32> = core::cell::Cell::new(0);
+    }
+
+    fn set_caller(caller: AccountId) {
+        let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().unwrap_or([0x0; 32].into());
+        let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+        data.push_arg(&caller);
+        ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+            caller,
+            callee,
+            1000000,
+            1000000,
+            data,
+        );
+    }
+
+    fn set_receiver(is_contract : bool, accepts : bool) {
+        RECEIVER_IS_CONTRACT.with(|cell| cell.set(is_contract));
+        RECEIVER_ACCEPTS.with(|cell| cell.set(accepts));
+        RECEIVER_NOTIFICATIONS.with(|cell| cell.set(0));
+    }
+
+    fn notifications() -> u32 {
+        RECEIVER_NOTIFICATIONS.with(|cell| cell.get())
+    }
+
+    #[ink::test]
+    fn test_create_token_should_mint_initial_supply(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = MultiToken::new();
+
+        contract.create_token(1, accounts.alice, accounts.bob, 1000);
+        contract.create_token(2, accounts.alice, accounts.bob, 0);
+        assert_eq!(contract.minter(1), Some(accounts.alice));
+        assert_eq!(contract.balance_of(accounts.bob, 1), 1000);
+        assert_eq!(contract.total_supply(1), 1000);
+        assert_eq!(contract.balance_of_batch(vec![accounts.bob, accounts.bob], vec![1, 2]), vec![1000, 0]);
+
+        assert!(contract.mint(accounts.charlie, 2, 300));
+        assert!(contract.burn(accounts.bob, 1, 400));
+        assert!(!contract.burn(accounts.bob, 1, 601));
+        assert_eq!(contract.total_supply(1), 600);
+        assert_eq!(contract.total_supply(2), 300);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_mint_by_other_account_should_fail(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = MultiToken::new();
+        contract.create_token(1, accounts.bob, accounts.bob, 1000);
+
+        contract.mint(accounts.alice, 1, 100);
+    }
+
+    #[ink::test]
+    fn test_safe_transfer_from_should_require_approval(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = MultiToken::new();
+        contract.create_token(1, accounts.alice, accounts.alice, 1000);
+        set_receiver(false, true);
+
+        assert!(contract.safe_transfer_from(accounts.alice, accounts.bob, 1, 200, vec![]));
+        assert!(!contract.safe_transfer_from(accounts.alice, accounts.bob, 1, 801, vec![]));
+
+        set_caller(accounts.charlie);
+        assert!(!contract.safe_transfer_from(accounts.alice, accounts.charlie, 1, 100, vec![]));
+
+        set_caller(accounts.alice);
+        contract.set_approval_for_all(accounts.charlie, true);
+        assert!(contract.is_approved_for_all(accounts.alice, accounts.charlie));
+
+        set_caller(accounts.charlie);
+        assert!(contract.safe_transfer_from(accounts.alice, accounts.charlie, 1, 100, vec![]));
+        assert_eq!(contract.balance_of(accounts.alice, 1), 700);
+        assert_eq!(contract.balance_of(accounts.bob, 1), 200);
+        assert_eq!(contract.balance_of(accounts.charlie, 1), 100);
+    }
+
+    #[ink::test]
+    fn test_minter_should_transfer_its_token(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = MultiToken::new();
+        contract.create_token(1, accounts.charlie, accounts.alice, 1000);
+        contract.create_token(2, accounts.bob, accounts.alice, 1000);
+        set_receiver(false, true);
+
+        set_caller(accounts.charlie);
+        assert!(contract.safe_transfer_from(accounts.alice, accounts.bob, 1, 300, vec![]));
+        assert!(!contract.safe_transfer_from(accounts.alice, accounts.bob, 2, 300, vec![]));
+        assert!(!contract.safe_transfer_from(accounts.alice, AccountId::default(), 1, 300, vec![]));
+        assert_eq!(contract.balance_of(accounts.alice, 1), 700);
+        assert_eq!(contract.balance_of(accounts.bob, 1), 300);
+        assert_eq!(contract.balance_of(accounts.alice, 2), 1000);
+    }
+
+    #[ink::test]
+    fn test_safe_batch_transfer_from_should_be_atomic(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = MultiToken::new();
+        contract.create_token(1, accounts.alice, accounts.alice, 1000);
+        contract.create_token(2, accounts.alice, accounts.alice, 500);
+        set_receiver(false, true);
+
+        assert!(!contract.safe_batch_transfer_from(accounts.alice, accounts.bob, vec![1, 2, 2], vec![100, 300, 300], vec![]));
+        assert_eq!(contract.balance_of(accounts.bob, 1), 0);
+        assert_eq!(contract.balance_of(accounts.bob, 2), 0);
+
+        assert!(contract.safe_batch_transfer_from(accounts.alice, accounts.bob, vec![1, 2, 2], vec![100, 300, 200], vec![]));
+        assert_eq!(contract.balance_of(accounts.alice, 1), 900);
+        assert_eq!(contract.balance_of(accounts.alice, 2), 0);
+        assert_eq!(contract.balance_of(accounts.bob, 2), 500);
+    }
+
+    #[ink::test]
+    fn test_safe_transfer_to_contract_should_notify_without_data(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = MultiToken::new();
+        contract.create_token(1, accounts.alice, accounts.bob, 1000);
+        set_receiver(false, true);
+
+        set_caller(accounts.bob);
+        assert!(contract.safe_transfer_from(accounts.bob, accounts.charlie, 1, 100, vec![]));
+        assert_eq!(notifications(), 0);
+
+        set_receiver(true, true);
+        assert!(contract.safe_transfer_from(accounts.bob, accounts.django, 1, 200, vec![]));
+        assert!(contract.safe_batch_transfer_from(accounts.bob, accounts.django, vec![1], vec![300], vec![]));
+        assert_eq!(notifications(), 2);
+        assert_eq!(contract.balance_of(accounts.django, 1), 500);
+
+        //The minter moves the token like an erc20 transfer, without notifying.
+        set_caller(accounts.alice);
+        assert!(contract.safe_transfer_from(accounts.bob, accounts.django, 1, 400, vec![]));
+        assert_eq!(notifications(), 2);
+        assert_eq!(contract.balance_of(accounts.bob, 1), 0);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_safe_transfer_to_refusing_contract_should_fail(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = MultiToken::new();
+        contract.create_token(1, accounts.alice, accounts.bob, 1000);
+        set_receiver(true, false);
+
+        set_caller(accounts.bob);
+        contract.safe_transfer_from(accounts.bob, accounts.django, 1, 100, vec![]);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_safe_batch_transfer_to_refusing_contract_should_fail(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut contract = MultiToken::new();
+        contract.create_token(1, accounts.alice, accounts.bob, 1000);
+        set_receiver(true, false);
+
+        set_caller(accounts.bob);
+        contract.safe_batch_transfer_from(accounts.bob, accounts.django, vec![1], vec![100], vec![]);
+    }
+}
@@ -0,0 +1,948 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::stable_exchange::StableExchange;
+
+use ink_lang as ink;
+
+#[ink::contract]
+mod stable_exchange {
+
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::{
+        collections::HashMap as StorageHashMap,
+    };
+
+    use primitive_types::U256;
+
+    /// Precision of the amplification coefficient, `A` is stored multiplied by it.
+    pub const A_PRECISION : u128 = 100;
+    /// Maximum amplification coefficient.
+    pub const MAX_A : u128 = 1_000_000;
+    /// Maximum factor `A` can change by in a single ramp.
+    pub const MAX_A_CHANGE : u128 = 10;
+    /// Minimum duration of a ramp, in milliseconds.
+    pub const MIN_RAMP_TIME : Timestamp = 24 * 3600 * 1000;
+    /// The swap fee is `fee / FEE_DENOMINATOR` of the output.
+    pub const FEE_DENOMINATOR : u128 = 10000;
+    /// Maximum swap fee, 1%.
+    pub const MAX_FEE : u128 = 100;
+    /// Maximum number of Newton iterations of the invariant solvers.
+    pub const MAX_ITERATIONS : u32 = 255;
+
+    #[ink(event)]
+    pub struct TokenPurchase {
+        #[ink(topic)]
+        buyer         : AccountId,
+        #[ink(topic)]
+        dot_sold     : Balance,
+        #[ink(topic)]
+        tokens_bought : Balance,
+    }
+
+    #[ink(event)]
+    pub struct DotPurchase{
+        #[ink(topic)]
+        buyer       : AccountId,
+        #[ink(topic)]
+        tokens_sold : Balance,
+        #[ink(topic)]
+        dot_bought : Balance,
+    }
+
+    #[ink(event)]
+    pub struct AddLiquidity{
+        #[ink(topic)]
+        provider      : AccountId,
+        #[ink(topic)]
+        dot_ammount  : Balance,
+        #[ink(topic)]
+        token_ammount : Balance,
+    }
+
+    #[ink(event)]
+    pub struct RemoveLiquidity{
+        #[ink(topic)]
+        provider      : AccountId,
+        #[ink(topic)]
+        dot_ammount  : Balance,
+        #[ink(topic)]
+        token_ammount : Balance,
+    }
+
+    #[ink(event)]
+    pub struct Transfer{
+        #[ink(topic)]
+        from   : AccountId,
+        #[ink(topic)]
+        to     : AccountId,
+        #[ink(topic)]
+        value  : Balance,
+    }
+
+    #[ink(event)]
+    pub struct Approval{
+        #[ink(topic)]
+        owner : AccountId,
+        #[ink(topic)]
+        spender:AccountId,
+        #[ink(topic)]
+        value : Balance,
+    }
+
+    #[ink(event)]
+    pub struct RampA{
+        #[ink(topic)]
+        old_a : u128,
+        #[ink(topic)]
+        new_a : u128,
+        initial_time : Timestamp,
+        future_time : Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct StopRampA{
+        #[ink(topic)]
+        a : u128,
+        time : Timestamp,
+    }
+
+    pub use common::token::{Error, Result};
+
+    /// A Dot / token trading pair for pegged assets, e.g. a Dot derivative token. It prices with the Curve StableSwap
+    /// invariant instead of the constant product of the Exchange.
+    ///
+    /// NOTE: The token is assumed to have the same decimals as Dot.
+    #[ink(storage)]
+    pub struct StableExchange {
+        //total liquidity
+        pub total_supply : Balance,
+
+        pub balances : StorageHashMap<AccountId, Balance>,
+        allowances: StorageHashMap<(AccountId, AccountId), Balance>,
+        //address of the ERC20 token traded on this contract
+        pub token : AccountId,
+        //the account allowed to ramp the amplification coefficient
+        owner : AccountId,
+        //amplification coefficient at the start of the ramp, multiplied by A_PRECISION
+        initial_a : u128,
+        //amplification coefficient at the end of the ramp, multiplied by A_PRECISION
+        future_a : u128,
+        initial_a_time : Timestamp,
+        future_a_time : Timestamp,
+        //swap fee, in units of 1 / FEE_DENOMINATOR
+        fee : u128,
+        gas_limit :u64,
+        exchange_account_id: AccountId,
+    }
+
+    impl StableExchange {
+
+        /// Constructor of the StableExchange contract
+        ///
+        /// NOTE: The deployer becomes the owner. The first `add_liquidity` must deposit both Dot and tokens.
+        ///
+        /// #Params
+        ///
+        /// - `token_account_id`: AccountId of a Erc20 token which trade on this contract
+        /// - `initial_a`: Amplification coefficient, not multiplied by A_PRECISION
+        /// - `fee`: Swap fee, in units of 1 / FEE_DENOMINATOR.
+        #[ink(constructor)]
+        pub fn new(token_account_id : AccountId, initial_a : u128, fee : u128) -> Self {
+            assert!(initial_a > 0 && initial_a < MAX_A && fee <= MAX_FEE);
+            Self{
+                total_supply : 0,
+                balances : StorageHashMap::new(),
+                allowances : StorageHashMap::new(),
+                token : token_account_id,
+                owner : Self::env().caller(),
+                initial_a : initial_a * A_PRECISION,
+                future_a : initial_a * A_PRECISION,
+                initial_a_time : 0,
+                future_a_time : 0,
+                fee,
+                gas_limit : 507085500000,
+                exchange_account_id : Self::env().account_id(),
+            }
+        }
+
+        #[ink(constructor)]
+        pub fn default() -> Self {
+            Self::new(AccountId::default(), 100, 4)
+        }
+
+        /// Return the swap fee, in units of 1 / FEE_DENOMINATOR.
+        #[ink(message)]
+        pub fn fee(&self) -> u128{
+            self.fee
+        }
+
+        /// Return the current amplification coefficient, multiplied by A_PRECISION.
+        #[ink(message)]
+        pub fn a(&self) -> u128{
+            Self::ramped_a(self.initial_a, self.future_a, self.initial_a_time, self.future_a_time, self.env().block_timestamp())
+        }
+
+        /// Return the ramp of the amplification coefficient as (initial_a, future_a, initial_a_time, future_a_time).
+        #[ink(message)]
+        pub fn a_ramp(&self) -> (u128, u128, Timestamp, Timestamp){
+            (self.initial_a, self.future_a, self.initial_a_time, self.future_a_time)
+        }
+
+        /// Change the amplification coefficient linearly until `future_time`.
+        ///
+        /// NOTE: Only the owner can ramp. A ramp lasts at least MIN_RAMP_TIME, can start MIN_RAMP_TIME after the previous
+        /// one, or at any time if `A` was never ramped, and changes `A` by at most MAX_A_CHANGE times.
+        ///
+        /// #Params
+        ///
+        /// - `future_a`: Amplification coefficient at the end of the ramp, not multiplied by A_PRECISION.
+        /// - `future_time`: Time the ramp ends at.
+        #[ink(message)]
+        pub fn ramp_a(&mut self, future_a : u128, future_time : Timestamp){
+            assert!(self.env().caller() == self.owner);
+            let now = self.env().block_timestamp();
+            assert!(self.initial_a_time == 0 || now >= self.initial_a_time + MIN_RAMP_TIME);
+            assert!(future_time >= now + MIN_RAMP_TIME);
+            assert!(future_a > 0 && future_a < MAX_A);
+
+            let initial_a = self.a();
+            let future_a = future_a * A_PRECISION;
+            if future_a < initial_a {
+                assert!(future_a * MAX_A_CHANGE >= initial_a);
+            }else{
+                assert!(future_a <= initial_a * MAX_A_CHANGE);
+            }
+
+            self.initial_a = initial_a;
+            self.future_a = future_a;
+            self.initial_a_time = now;
+            self.future_a_time = future_time;
+
+            self.env().emit_event( RampA {
+                old_a : initial_a,
+                new_a : future_a,
+                initial_time : now,
+                future_time,
+            });
+        }
+
+        /// Stop the ramp at the current amplification coefficient, only the owner can stop it.
+        #[ink(message)]
+        pub fn stop_ramp_a(&mut self){
+            assert!(self.env().caller() == self.owner);
+            let now = self.env().block_timestamp();
+            let current_a = self.a();
+            self.initial_a = current_a;
+            self.future_a = current_a;
+            self.initial_a_time = now;
+            self.future_a_time = now;
+
+            self.env().emit_event( StopRampA {
+                a : current_a,
+                time : now,
+            });
+        }
+
+        /// Deposit Dot and Tokens in any ratio to mint lp tokens. A deposit away from the current ratio pays the swap fee
+        /// on the imbalanced part.
+        ///
+        /// Return The amount of lp minted
+        ///
+        /// #Params
+        ///
+        /// - `min_liquidity`: Minimum number of lp sender will mint.
+        /// - `token_ammount`: Number of tokens deposited.
+        /// - `deadline`: Time after which this transaction can no longer be executed.
+        ///
+        /// NOTE: If the token transfer fails the transferred Dot is refunded and `TokenTransferFailed` is returned.
+        #[ink(message, payable)]
+        pub fn add_liquidity(&mut self, min_liquidity : Balance, token_ammount : Balance, deadline : Timestamp) ->Result<Balance>{
+            let transfferred_value = self.env().transferred_balance();
+            assert!(deadline >= self.env().block_timestamp() && (transfferred_value > 0 || token_ammount > 0));
+            let caller = self.env().caller();
+            let total_liquidity = self.total_supply;
+            if total_liquidity == 0 {
+                assert!(transfferred_value > 0 && token_ammount > 0);
+            }
+
+            let old_reserves = [self.env().balance() - transfferred_value, self.token_balance()];
+            let new_reserves = [old_reserves[0] + transfferred_value, old_reserves[1] + token_ammount];
+            let liquidity_minted = Self::liquidity_minted(old_reserves, new_reserves, total_liquidity, self.a(), self.fee);
+            assert!(liquidity_minted > 0 && liquidity_minted >= min_liquidity);
+
+            if token_ammount > 0 {
+                if let Err(error) = self.token_transfer_from(caller, self.exchange_account_id, token_ammount) {
+                    if transfferred_value > 0 {
+                        self.env().transfer(caller, transfferred_value).expect("transfer error");
+                    }
+                    return Err(error)
+                }
+            }
+
+            self.mint_liquidity(caller, liquidity_minted);
+
+            self.env().emit_event( AddLiquidity {
+                provider : caller,
+                dot_ammount : transfferred_value,
+                token_ammount,
+            });
+
+            Ok(liquidity_minted)
+        }
+
+        /// Burn lp tokens to withdraw Dot and Tokens at current ratio.
+        ///
+        /// Return The amount of Dot and Tokens withdrawn.
+        ///
+        /// #Params
+        ///
+        /// - `ammount`: Amount of lp burned.
+        /// - `min_dot`: Minimum Dot withdrawn.
+        /// - `min_tokens`: Minimum Tokens withdrawn
+        /// - `deadline`: Time after which this transaction can no longer be executed.
+        #[ink(message)]
+        pub fn remove_liquidity(&mut self, ammount : Balance, min_dot : Balance, min_token : Balance, deadline : Timestamp) ->Result<(Balance, Balance)>{
+            assert!(ammount > 0 && deadline >= self.env().block_timestamp());
+            let caller = self.env().caller();
+            let total_liquidity = self.total_supply;
+            assert!(total_liquidity > 0);
+            assert!(self.balance_of(caller) >= ammount);
+
+            let dot_ammount  = ammount * self.env().balance() / total_liquidity;
+            let token_ammount = ammount * self.token_balance() / total_liquidity;
+            assert!(dot_ammount >= min_dot && token_ammount >= min_token);
+
+            self.token_transfer(caller, token_ammount)?;
+
+            self.burn_liquidity(caller, ammount);
+
+            self.env().transfer(caller, dot_ammount).expect("transfer error");
+
+            self.env().emit_event( RemoveLiquidity {
+                provider : caller,
+                dot_ammount,
+                token_ammount,
+            });
+
+            Ok((dot_ammount, token_ammount))
+        }
+
+        fn mint_liquidity(&mut self, to : AccountId, value : Balance){
+            let to_liquidity = self.balance_of(to);
+            self.balances.insert(to, to_liquidity + value);
+            self.total_supply += value;
+
+            self.env().emit_event( Transfer {
+                from : AccountId::default(),
+                to,
+                value,
+            });
+        }
+
+        fn burn_liquidity(&mut self, from : AccountId, value : Balance){
+            let from_liquidity = self.balance_of(from);
+            assert!(from_liquidity >= value);
+            self.balances.insert(from, from_liquidity - value);
+            self.total_supply -= value;
+
+            self.env().emit_event( Transfer {
+                from,
+                to : AccountId::default(),
+                value,
+            });
+        }
+
+        fn dot_to_token_input(&mut self, dot_sold: Balance, min_tokens: Balance, deadline: Timestamp,
+                                buyer: AccountId, recipient: AccountId) -> Result<Balance>{
+            assert!(deadline >= self.env().block_timestamp() && dot_sold > 0 && min_tokens > 0);
+            let token_reserve = self.token_balance();
+            let tokens_bought = Self::stable_input_price(dot_sold, self.env().balance() - dot_sold, token_reserve, self.a(), self.fee);
+            assert!(tokens_bought >= min_tokens);
+            if let Err(error) = self.token_transfer(recipient, tokens_bought) {
+                self.env().transfer(buyer, dot_sold).expect("transfer error");
+                return Err(error)
+            }
+            self.env().emit_event( TokenPurchase {
+                buyer,
+                dot_sold,
+                tokens_bought,
+            });
+            Ok(tokens_bought)
+        }
+
+        /// Convert Dot to Tokens.
+        ///
+        /// Return bought token
+        ///
+        /// # Params
+        ///
+        /// - `min_token`: Minimum Tokens bought
+        /// - `deadline ` : Time after which this transaction can no longer be executed
+        #[ink(message, payable)]
+        pub fn dot_to_token_swap_input(&mut self, min_tokens : Balance, deadline : Timestamp) ->Result<Balance>{
+            let caller = self.env().caller();
+            let transferred_balance = self.env().transferred_balance();
+            self.dot_to_token_input(transferred_balance, min_tokens, deadline, caller, caller)
+        }
+
+        /// Convert DOT to Tokens and transfer the token to a specified account
+        ///
+        /// Return bought token
+        ///
+        /// # Params
+        ///
+        /// - `min_token`: Minimum Tokens bought
+        /// - `deadline ` : Time after which this transaction can no longer be executed
+        /// - 'recipient' : AcccountId will get the transferred token
+        #[ink(message, payable)]
+        pub fn dot_to_token_transfer_input(&mut self, min_tokens : Balance, deadline : Timestamp, recipient: AccountId) ->Result<Balance>{
+            assert!(recipient != self.exchange_account_id && recipient != AccountId::default());
+            let transferred_balance = self.env().transferred_balance();
+            self.dot_to_token_input(transferred_balance, min_tokens, deadline, self.env().caller(), recipient)
+        }
+
+        fn dot_to_token_output(&mut self, tokens_bought : Balance, max_dot :Balance, deadline : Timestamp, buyer: AccountId, recipient: AccountId) -> Result<Balance>{
+            assert!(deadline >= self.env().block_timestamp() && tokens_bought > 0 && max_dot > 0);
+            let token_reserve = self.token_balance();
+            let dot_sold = Self::stable_output_price(tokens_bought, self.env().balance() - max_dot, token_reserve, self.a(), self.fee);
+            assert!(max_dot >= dot_sold);
+            if let Err(error) = self.token_transfer(recipient, tokens_bought) {
+                self.env().transfer(buyer, max_dot).expect("transfer error");
+                return Err(error)
+            }
+            let dot_refund = max_dot - dot_sold;
+            if dot_refund > 0 {
+                self.env().transfer(buyer, dot_refund).expect("transfer error");
+            }
+            self.env().emit_event( TokenPurchase {
+                buyer,
+                dot_sold,
+                tokens_bought,
+            });
+            Ok(dot_sold)
+        }
+
+        /// Convert Dot to Tokens
+        ///
+        /// NOTE: User specifies maximum input(dot) and exact output.
+        ///
+        /// #Params
+        ///
+        /// - `tokens_bought`: Amount of tokens bought.
+        /// - `deadline ` : Time after which this transaction can no longer be executed
+        #[ink(message, payable)]
+        pub fn dot_to_token_swap_output(&mut self, tokens_bought : Balance, deadline : Timestamp) -> Result<Balance>{
+            let caller = self.env().caller();
+            let transferred_balance = self.env().transferred_balance();
+            self.dot_to_token_output(tokens_bought, transferred_balance, deadline, caller, caller)
+        }
+
+        /// Convert Dot to Tokens and transfer the token to a specified account
+        ///
+        /// NOTE: User specifies maximum input and exact output.
+        ///
+        /// #Params
+        ///
+        /// - `tokens_bought`: Amount of tokens bought.
+        /// - `deadline ` : Time after which this transaction can no longer be executed
+        /// - `recipient`: AcccountId will get the transferred token
+        #[ink(message, payable)]
+        pub fn dot_to_token_transfer_output(&mut self, tokens_bought : Balance, deadline : Timestamp, recipient: AccountId) ->Result<Balance>{
+            assert!(recipient != self.exchange_account_id && recipient != AccountId::default());
+            let transferred_balance = self.env().transferred_balance();
+            self.dot_to_token_output(tokens_bought, transferred_balance, deadline, self.env().caller(), recipient)
+        }
+
+        fn token_to_dot_input(&mut self, tokens_sold : Balance, min_dot : Balance, deadline : Timestamp, buyer: AccountId, recipient: AccountId)->Result<Balance>{
+            assert!(deadline >= self.env().block_timestamp() && tokens_sold > 0 && min_dot > 0);
+            let token_reserve = self.token_balance();
+            let dot_bought = Self::stable_input_price(tokens_sold, token_reserve, self.env().balance(), self.a(), self.fee);
+            assert!(dot_bought >= min_dot);
+
+            self.token_transfer_from(buyer, self.exchange_account_id, tokens_sold)?;
+
+            self.env().transfer(recipient, dot_bought).expect("transfer error");
+
+            self.env().emit_event( DotPurchase {
+                buyer,
+                tokens_sold,
+                dot_bought,
+            });
+            Ok(dot_bought)
+        }
+
+        /// Convert Tokens to Dot.
+        ///
+        /// #params:
+        /// - `tokens_sold`:Amount of Tokens sold.
+        /// - `min_dot`: Minimum Dot purchased.
+        /// - `deadline`: Time after which this transaction can no longer be executed
+        #[ink(message)]
+        pub fn token_to_dot_swap_input(&mut self, tokens_sold : Balance, min_dot : Balance, deadline : Timestamp) -> Result<Balance>{
+            let caller = self.env().caller();
+            self.token_to_dot_input(tokens_sold, min_dot, deadline, caller, caller)
+        }
+
+        /// Convert Tokens to Dot and transfer the Dot to recipient.
+        ///
+        /// #params:
+        ///
+        /// - `tokens_sold`:Amount of Tokens sold.
+        /// - `min_dot`: Minimum Dot purchased.
+        /// - `deadline`: Time after which this transaction can no longer be executed.
+        /// - `recipient`: AcccountId will get the transferred dot.
+        #[ink(message)]
+        pub fn token_to_dot_transfer_input(&mut self, tokens_sold : Balance, min_dot : Balance, deadline : Timestamp, recipient: AccountId) ->Result<Balance>{
+            assert!(self.exchange_account_id != recipient);
+            self.token_to_dot_input(tokens_sold, min_dot, deadline, self.env().caller(), recipient)
+        }
+
+        fn token_to_dot_output(&mut self, dot_bought : Balance, max_tokens : Balance, deadline : Timestamp, buyer: AccountId, recipient: AccountId)->Result<Balance>{
+            assert!(deadline >= self.env().block_timestamp() && dot_bought > 0);
+            let token_reserve = self.token_balance();
+            let tokens_sold = Self::stable_output_price(dot_bought, token_reserve, self.env().balance(), self.a(), self.fee);
+            assert!(max_tokens >= tokens_sold);
+
+            self.token_transfer_from(buyer, self.exchange_account_id, tokens_sold)?;
+
+            self.env().transfer(recipient, dot_bought).expect("transfer error");
+
+            self.env().emit_event( DotPurchase {
+                buyer,
+                tokens_sold,
+                dot_bought,
+            });
+            Ok(tokens_sold)
+        }
+
+        /// Convert Tokens to Dot.
+        ///
+        /// NOTE: User specifies maximum input and exact output.
+        ///
+        /// #params:
+        /// - `dot_bought`: Amount of Dot purchased.
+        /// - `max_tokens`: Maximum Tokens sold.
+        /// - `deadline`: Time after which this transaction can no longer be executed.
+        #[ink(message)]
+        pub fn token_to_dot_swap_output(&mut self, dot_bought : Balance, max_tokens : Balance, deadline : Timestamp)->Result<Balance>{
+            let caller = self.env().caller();
+            self.token_to_dot_output(dot_bought, max_tokens, deadline, caller, caller)
+        }
+
+        /// Convert Tokens to Dot and transfer the Dot to recipient.
+        ///
+        /// NOTE: User specifies maximum input and exact output.
+        ///
+        /// #params:
+        /// - `dot_bought`: Amount of Dot purchased.
+        /// - `max_tokens`: Maximum Tokens sold.
+        /// - `deadline`: Time after which this transaction can no longer be executed.
+        /// - `recipient`: AcccountId will get the transferred dot.
+        #[ink(message)]
+        pub fn token_to_dot_transfer_output(&mut self, dot_bought : Balance, max_tokens : Balance, deadline : Timestamp, recipient: AccountId) ->Result<Balance>{
+            assert!(self.exchange_account_id != recipient);
+            self.token_to_dot_output(dot_bought, max_tokens, deadline, self.env().caller(), recipient)
+        }
+
+        /// Return amount of Tokens that can be bought with input Dot.
+        #[ink(message)]
+        pub fn dot_to_token_input_price(&mut self, dot_sold : Balance)->Balance{
+            assert!(dot_sold > 0);
+            let token_reserve = self.token_balance();
+            Self::stable_input_price(dot_sold, self.env().balance(), token_reserve, self.a(), self.fee)
+        }
+
+        /// Return amount of Dot needed to buy output Tokens.
+        #[ink(message)]
+        pub fn dot_to_token_output_price(&mut self, tokens_bought : Balance)->Balance{
+            assert!(tokens_bought > 0);
+            let token_reserve = self.token_balance();
+            Self::stable_output_price(tokens_bought, self.env().balance(), token_reserve, self.a(), self.fee)
+        }
+
+        /// Return amount of Dot that can be bought with input Tokens.
+        #[ink(message)]
+        pub fn token_to_dot_input_price(&mut self, tokens_sold : Balance)->Balance{
+            assert!(tokens_sold > 0);
+            let token_reserve = self.token_balance();
+            Self::stable_input_price(tokens_sold, token_reserve, self.env().balance(), self.a(), self.fee)
+        }
+
+        /// Return amount of Tokens needed to buy output Dot.
+        #[ink(message)]
+        pub fn token_to_dot_output_price(&mut self, dot_bought : Balance)->Balance{
+            assert!(dot_bought > 0);
+            let token_reserve = self.token_balance();
+            Self::stable_output_price(dot_bought, token_reserve, self.env().balance(), self.a(), self.fee)
+        }
+
+        /// Return the amplification coefficient at `now` of a linear ramp.
+        pub fn ramped_a(initial_a : u128, future_a : u128, initial_a_time : Timestamp, future_a_time : Timestamp, now : Timestamp) -> u128{
+            if now >= future_a_time {
+                return future_a
+            }
+            let elapsed = (now - initial_a_time) as u128;
+            let duration = (future_a_time - initial_a_time) as u128;
+            if future_a > initial_a {
+                initial_a + (future_a - initial_a) * elapsed / duration
+            }else{
+                initial_a - (initial_a - future_a) * elapsed / duration
+            }
+        }
+
+        /// Return the StableSwap invariant `D` of two reserves, solved with Newton iterations.
+        pub fn get_d(reserves : [Balance; 2], amp : u128) -> Balance{
+            let sum = U256::from(reserves[0]) + U256::from(reserves[1]);
+            if sum.is_zero() {
+                return 0
+            }
+            assert!(reserves[0] > 0 && reserves[1] > 0);
+            let n = U256::from(2u8);
+            let ann = U256::from(amp) * n;
+            let a_precision = U256::from(A_PRECISION);
+            let mut d = sum;
+            for _ in 0..MAX_ITERATIONS {
+                let mut d_p = d;
+                for reserve in reserves.iter() {
+                    d_p = d_p * d / (U256::from(*reserve) * n);
+                }
+                let d_prev = d;
+                d = (ann * sum / a_precision + d_p * n) * d / ((ann - a_precision) * d / a_precision + (n + 1) * d_p);
+                if Self::converged(d, d_prev) {
+                    return d.as_u128()
+                }
+            }
+            panic!("D does not converge")
+        }
+
+        /// Return the reserve of one asset keeping the invariant `d` when the reserve of the other asset is `reserve`.
+        pub fn get_y(reserve : Balance, d : Balance, amp : u128) -> Balance{
+            assert!(reserve > 0);
+            let n = U256::from(2u8);
+            let ann = U256::from(amp) * n;
+            let a_precision = U256::from(A_PRECISION);
+            let d = U256::from(d);
+            let x = U256::from(reserve);
+
+            let c = d * d / (x * n) * d * a_precision / (ann * n);
+            let b = x + d * a_precision / ann;
+            let mut y = d;
+            for _ in 0..MAX_ITERATIONS {
+                let y_prev = y;
+                y = (y * y + c) / (y * 2 + b - d);
+                if Self::converged(y, y_prev) {
+                    return y.as_u128()
+                }
+            }
+            panic!("y does not converge")
+        }
+
+        fn converged(value : U256, previous : U256) -> bool{
+            if value > previous { value - previous <= U256::one() } else { previous - value <= U256::one() }
+        }
+
+        /// Return the output bought with `input_ammount`, after the swap fee.
+        pub fn stable_input_price(input_ammount : Balance, input_reserve : Balance, output_reserve : Balance, amp : u128, fee : u128) -> Balance{
+            assert!(input_reserve > 0 && output_reserve > 0);
+            let d = Self::get_d([input_reserve, output_reserve], amp);
+            let y = Self::get_y(input_reserve + input_ammount, d, amp);
+            //One unit is kept in the pool against rounding.
+            let output_ammount = output_reserve.saturating_sub(y).saturating_sub(1);
+            output_ammount - output_ammount * fee / FEE_DENOMINATOR
+        }
+
+        /// Return the input needed to buy `output_ammount` after the swap fee.
+        pub fn stable_output_price(output_ammount : Balance, input_reserve : Balance, output_reserve : Balance, amp : u128, fee : u128) -> Balance{
+            assert!(input_reserve > 0 && output_reserve > 0);
+            let output_with_fee = output_ammount * FEE_DENOMINATOR / (FEE_DENOMINATOR - fee) + 1;
+            assert!(output_with_fee < output_reserve);
+            let d = Self::get_d([input_reserve, output_reserve], amp);
+            let x = Self::get_y(output_reserve - output_with_fee, d, amp);
+            x - input_reserve + 1
+        }
+
+        /// Return the liquidity minted for a deposit moving the reserves from `old_reserves` to `new_reserves`.
+        /// The part of the deposit away from the current ratio pays half the swap fee.
+        pub fn liquidity_minted(old_reserves : [Balance; 2], new_reserves : [Balance; 2], total_liquidity : Balance, amp : u128, fee : u128) -> Balance{
+            let d1 = Self::get_d(new_reserves, amp);
+            if total_liquidity == 0 {
+                return d1
+            }
+            let d0 = Self::get_d(old_reserves, amp);
+            assert!(d1 > d0);
+
+            let mut reserves_after_fee = [0; 2];
+            for i in 0..2 {
+                let ideal_reserve = (U256::from(d1) * U256::from(old_reserves[i]) / U256::from(d0)).as_u128();
+                let difference = if ideal_reserve > new_reserves[i] { ideal_reserve - new_reserves[i] } else { new_reserves[i] - ideal_reserve };
+                reserves_after_fee[i] = new_reserves[i] - difference * fee / 2 / FEE_DENOMINATOR;
+            }
+            let d2 = Self::get_d(reserves_after_fee, amp);
+            (U256::from(total_liquidity) * U256::from(d2.saturating_sub(d0)) / U256::from(d0)).as_u128()
+        }
+
+        /// Return the total liqudity in this trading pair.
+        #[ink(message)]
+        pub fn total_supply(&self) ->Balance{
+            self.total_supply
+        }
+
+        /// Return the liquidity of the owner
+        #[ink(message)]
+        pub fn balance_of(&self, owner : AccountId) -> Balance{
+            self.balances.get(&owner).unwrap_or(&0u128).clone()
+        }
+
+        fn transfer_from_to(&mut self, from: AccountId, to: AccountId, value: Balance) -> bool {
+            let from_balance = self.balance_of(from);
+            if from_balance < value {
+                return false
+            }
+            self.balances.insert(from, from_balance - value);
+            let to_balance = self.balance_of(to);
+            self.balances.insert(to, to_balance + value);
+
+            self.env().emit_event(Transfer {
+                from,
+                to,
+                value,
+            });
+
+            true
+        }
+
+        /// The Contract caller transfer some liquidity to another account
+        #[ink(message, selector = "0xfae3a09d")]
+        pub fn transfer(&mut self, to : AccountId, value : Balance) -> bool{
+            let caller = self.env().caller();
+            self.transfer_from_to(caller, to, value)
+        }
+
+        /// The Contract caller transfer some liquidity from an account to another account
+        ///
+        /// NOTE: The allowance is only spent if the transfer succeeds.
+        #[ink(message, selector = "0xfcfb2ccd")]
+        pub fn transfer_from(&mut self, from : AccountId, to : AccountId, value : Balance) -> bool{
+            let caller = self.env().caller();
+            let allowance = self.allowance(from, caller);
+            if allowance < value {
+                 return false
+            }
+            if !self.transfer_from_to(from, to, value) {
+                return false
+            }
+            self.allowances.insert((from, caller), allowance - value);
+
+            true
+        }
+
+        /// Approve spender can transfer liquidity from the caller account
+        #[ink(message)]
+        pub fn approve(&mut self, spender: AccountId, value: Balance) -> bool {
+            let owner = self.env().caller();
+            self.allowances.insert((owner, spender), value);
+            self.env().emit_event(Approval {
+                owner,
+                spender,
+                value,
+            });
+            true
+        }
+
+        /// Return the liquidity spender can transfer from the owner account
+        #[ink(message)]
+        pub fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+            self.allowances.get(&(owner, spender)).unwrap_or(&0u128).clone()
+        }
+
+        ///Return the token amount in liqudity pool
+        #[ink(message)]
+        pub fn token_balance(&mut self) -> Balance{
+            common::token::balance_of(self.token, self.exchange_account_id, self.gas_limit / 2)
+        }
+
+        fn token_transfer(&mut self, to : AccountId, value : Balance)->Result<()>{
+            common::token::transfer(self.token, to, value, self.gas_limit / 2)
+        }
+
+        fn token_transfer_from(&mut self, from : AccountId, to : AccountId, value : Balance) ->Result<()>{
+            common::token::transfer_from(self.token, from, to, value, self.gas_limit / 2)
+        }
+
+        ///Return the StableExchange self account id.
+        #[ink(message)]
+        pub fn get_address(&self) ->AccountId{
+            self.exchange_account_id
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::stable_exchange::{A_PRECISION, MIN_RAMP_TIME};
+    use ink_env::{
+        AccountId,
+    };
+    use ink_lang as ink;
+
+    fn set_caller(caller: AccountId) {
+        let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().unwrap_or([0x0; 32].into());
+        let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+        data.push_arg(&caller);
+        ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+            caller,
+            callee,
+            1000000,
+            1000000,
+            data,
+        );
+    }
+
+    fn now() -> u64 {
+        ink_env::block_timestamp::<ink_env::DefaultEnvironment>().unwrap()
+    }
+
+    #[ink::test]
+    fn test_get_d_of_balanced_reserves_should_be_their_sum(){
+        let amp = 100 * A_PRECISION;
+        assert_eq!(StableExchange::get_d([0, 0], amp), 0);
+        let d = StableExchange::get_d([1_000_000_000_000, 1_000_000_000_000], amp);
+        assert!(d >= 1_999_999_999_999 && d <= 2_000_000_000_001);
+
+        let d = StableExchange::get_d([1_000_000_000_000, 2_000_000_000_000], amp);
+        assert!(d < 3_000_000_000_000 && d > 2_990_000_000_000);
+    }
+
+    #[ink::test]
+    fn test_stable_price_should_be_close_to_peg(){
+        let amp = 100 * A_PRECISION;
+        let reserve = 1_000_000_000_000_000u128;
+        let bought = StableExchange::stable_input_price(1_000_000_000_000, reserve, reserve, amp, 4);
+        //0.04% fee, almost no slippage.
+        assert!(bought > 999_500_000_000 && bought < 999_600_000_001);
+
+        let sold = StableExchange::stable_output_price(bought, reserve, reserve, amp, 4);
+        assert!(sold >= 1_000_000_000_000 && sold < 1_000_000_001_000);
+    }
+
+    #[ink::test]
+    fn test_higher_a_should_give_less_slippage(){
+        let reserve = 1_000_000_000_000u128;
+        let low = StableExchange::stable_input_price(100_000_000_000, reserve, reserve, 10 * A_PRECISION, 4);
+        let high = StableExchange::stable_input_price(100_000_000_000, reserve, reserve, 1000 * A_PRECISION, 4);
+        assert!(high > low);
+    }
+
+    #[ink::test]
+    fn test_imbalanced_deposit_should_pay_fee(){
+        let amp = 100 * A_PRECISION;
+        let reserves = [1_000_000_000_000u128, 1_000_000_000_000];
+        let total_liquidity = StableExchange::liquidity_minted([0, 0], reserves, 0, amp, 4);
+
+        let balanced = StableExchange::liquidity_minted(reserves, [1_100_000_000_000, 1_100_000_000_000], total_liquidity, amp, 4);
+        let imbalanced = StableExchange::liquidity_minted(reserves, [1_200_000_000_000, 1_000_000_000_000], total_liquidity, amp, 4);
+        assert!(balanced >= 199_999_999_998);
+        assert!(imbalanced < balanced);
+    }
+
+    #[ink::test]
+    fn test_ramped_a_should_be_linear(){
+        assert_eq!(StableExchange::ramped_a(100, 200, 1000, 2000, 1500), 150);
+        assert_eq!(StableExchange::ramped_a(200, 100, 1000, 2000, 1250), 175);
+        assert_eq!(StableExchange::ramped_a(200, 100, 1000, 2000, 3000), 100);
+    }
+
+    #[ink::test]
+    fn test_fee_should_be_set_by_constructor(){
+        let stable_exchange = StableExchange::new(AccountId::default(), 100, 10);
+        assert_eq!(stable_exchange.fee(), 10);
+
+        let reserve = 1_000_000_000_000_000u128;
+        let amp = 100 * A_PRECISION;
+        let low_fee = StableExchange::stable_input_price(1_000_000_000_000, reserve, reserve, amp, 4);
+        let high_fee = StableExchange::stable_input_price(1_000_000_000_000, reserve, reserve, amp, 10);
+        assert!(high_fee < low_fee);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_fee_above_max_should_panic(){
+        StableExchange::new(AccountId::default(), 100, 101);
+    }
+
+    #[ink::test]
+    fn test_owner_should_ramp_and_stop_a(){
+        let mut stable_exchange = StableExchange::new(AccountId::default(), 100, 4);
+        ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+        let start = now();
+        stable_exchange.ramp_a(1000, start + MIN_RAMP_TIME);
+        assert_eq!(stable_exchange.a_ramp(), (100 * A_PRECISION, 1000 * A_PRECISION, start, start + MIN_RAMP_TIME));
+
+        ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+        let current_a = stable_exchange.a();
+        stable_exchange.stop_ramp_a();
+        assert_eq!(stable_exchange.a_ramp(), (current_a, current_a, now(), now()));
+        assert_eq!(stable_exchange.a(), current_a);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_ramp_a_by_non_owner_should_panic(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut stable_exchange = StableExchange::new(AccountId::default(), 100, 4);
+        set_caller(accounts.bob);
+        stable_exchange.ramp_a(200, now() + MIN_RAMP_TIME);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_stop_ramp_a_by_non_owner_should_panic(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().unwrap();
+        let mut stable_exchange = StableExchange::new(AccountId::default(), 100, 4);
+        set_caller(accounts.bob);
+        stable_exchange.stop_ramp_a();
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_ramp_shorter_than_min_ramp_time_should_panic(){
+        let mut stable_exchange = StableExchange::new(AccountId::default(), 100, 4);
+        ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+        stable_exchange.ramp_a(200, now() + MIN_RAMP_TIME - 1);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_ramp_too_soon_after_previous_ramp_should_panic(){
+        let mut stable_exchange = StableExchange::new(AccountId::default(), 100, 4);
+        ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+        stable_exchange.ramp_a(200, now() + MIN_RAMP_TIME);
+        ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+        stable_exchange.ramp_a(300, now() + MIN_RAMP_TIME);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_ramp_above_max_a_change_should_panic(){
+        let mut stable_exchange = StableExchange::new(AccountId::default(), 100, 4);
+        ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+        stable_exchange.ramp_a(1001, now() + MIN_RAMP_TIME);
+    }
+
+    #[ink::test]
+    fn test_failed_transfer_from_should_keep_allowance(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut contract = StableExchange::new(AccountId::default(), 100, 4);
+        contract.balances.insert(accounts.alice, 100);
+        set_caller(accounts.alice);
+        assert!(contract.approve(accounts.bob, 300));
+
+        set_caller(accounts.bob);
+        assert!(!contract.transfer_from(accounts.alice, accounts.bob, 200));
+        assert_eq!(contract.allowance(accounts.alice, accounts.bob), 300);
+        assert!(contract.transfer_from(accounts.alice, accounts.bob, 100));
+        assert_eq!(contract.allowance(accounts.alice, accounts.bob), 200);
+        assert_eq!(contract.balance_of(accounts.bob), 100);
+    }
+}
@@ -0,0 +1,848 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::stable_pool::StablePool;
+
+use ink_lang as ink;
+
+#[ink::contract]
+mod stable_pool {
+
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::{
+        collections::HashMap as StorageHashMap,
+    };
+
+    use ink_prelude::{
+        vec,
+        vec::Vec,
+    };
+
+    use primitive_types::U256;
+
+    /// Precision of the amplification coefficient, `A` is stored multiplied by it.
+    pub const A_PRECISION : u128 = 100;
+    /// Maximum amplification coefficient.
+    pub const MAX_A : u128 = 1_000_000;
+    /// Maximum factor `A` can change by in a single ramp.
+    pub const MAX_A_CHANGE : u128 = 10;
+    /// Minimum duration of a ramp, in milliseconds.
+    pub const MIN_RAMP_TIME : Timestamp = 24 * 3600 * 1000;
+    /// The swap fee is `fee / FEE_DENOMINATOR` of the output.
+    pub const FEE_DENOMINATOR : u128 = 10_000_000_000;
+    /// Maximum swap fee, 1%.
+    pub const MAX_FEE : u128 = 100_000_000;
+    /// Maximum number of tokens in a pool.
+    pub const MAX_COINS : usize = 8;
+    /// Maximum number of Newton iterations of the invariant solvers.
+    pub const MAX_ITERATIONS : u32 = 255;
+
+    #[ink(event)]
+    pub struct TokenExchange {
+        #[ink(topic)]
+        buyer : AccountId,
+        sold_id : u32,
+        tokens_sold : Balance,
+        bought_id : u32,
+        tokens_bought : Balance,
+    }
+
+    #[ink(event)]
+    pub struct AddLiquidity {
+        #[ink(topic)]
+        provider : AccountId,
+        token_amounts : Vec<Balance>,
+        fees : Vec<Balance>,
+        #[ink(topic)]
+        liquidity_minted : Balance,
+    }
+
+    #[ink(event)]
+    pub struct RemoveLiquidity {
+        #[ink(topic)]
+        provider : AccountId,
+        token_amounts : Vec<Balance>,
+        fees : Vec<Balance>,
+        #[ink(topic)]
+        liquidity_burned : Balance,
+    }
+
+    #[ink(event)]
+    pub struct Transfer{
+        #[ink(topic)]
+        from   : AccountId,
+        #[ink(topic)]
+        to     : AccountId,
+        #[ink(topic)]
+        value  : Balance,
+    }
+
+    #[ink(event)]
+    pub struct Approval{
+        #[ink(topic)]
+        owner : AccountId,
+        #[ink(topic)]
+        spender:AccountId,
+        #[ink(topic)]
+        value : Balance,
+    }
+
+    #[ink(event)]
+    pub struct RampA{
+        #[ink(topic)]
+        old_a : u128,
+        #[ink(topic)]
+        new_a : u128,
+        initial_time : Timestamp,
+        future_time : Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct StopRampA{
+        #[ink(topic)]
+        a : u128,
+        time : Timestamp,
+    }
+
+    pub use common::token::{Error, Result};
+
+    /// A pool of N pegged Erc20 tokens priced with the Curve StableSwap invariant, e.g. several bridged USD tokens.
+    ///
+    /// NOTE: All tokens are assumed to have the same decimals.
+    #[ink(storage)]
+    pub struct StablePool {
+        //total liquidity
+        pub total_supply : Balance,
+
+        pub balances : StorageHashMap<AccountId, Balance>,
+        allowances: StorageHashMap<(AccountId, AccountId), Balance>,
+        //addresses of the ERC20 tokens traded in this pool
+        coins : Vec<AccountId>,
+        //amount of each token held by the pool, including the fees
+        reserves : Vec<Balance>,
+        //swap fee, in units of 1 / FEE_DENOMINATOR
+        fee : u128,
+        //the account allowed to ramp the amplification coefficient
+        owner : AccountId,
+        //amplification coefficient at the start of the ramp, multiplied by A_PRECISION
+        initial_a : u128,
+        //amplification coefficient at the end of the ramp, multiplied by A_PRECISION
+        future_a : u128,
+        initial_a_time : Timestamp,
+        future_a_time : Timestamp,
+        gas_limit :u64,
+        pool_account_id: AccountId,
+    }
+
+    impl StablePool {
+
+        /// Constructor of the StablePool contract
+        ///
+        /// NOTE: The pool is usually instantiated by the Factory. The first `add_liquidity` must deposit every token.
+        ///
+        /// #Params
+        ///
+        /// - `coins`: AccountIds of the Erc20 tokens traded in this pool, 2 to MAX_COINS distinct tokens.
+        /// - `initial_a`: Amplification coefficient, not multiplied by A_PRECISION.
+        /// - `fee`: Swap fee, in units of 1 / FEE_DENOMINATOR.
+        /// - `owner`: The account allowed to ramp the amplification coefficient.
+        #[ink(constructor)]
+        pub fn new(coins : Vec<AccountId>, initial_a : u128, fee : u128, owner : AccountId) -> Self {
+            assert!(coins.len() >= 2 && coins.len() <= MAX_COINS);
+            for (i, coin) in coins.iter().enumerate() {
+                assert!(*coin != AccountId::default() && !coins[i + 1..].contains(coin));
+            }
+            assert!(initial_a > 0 && initial_a < MAX_A && fee <= MAX_FEE);
+            let reserves = vec![0; coins.len()];
+            Self{
+                total_supply : 0,
+                balances : StorageHashMap::new(),
+                allowances : StorageHashMap::new(),
+                coins,
+                reserves,
+                fee,
+                owner,
+                initial_a : initial_a * A_PRECISION,
+                future_a : initial_a * A_PRECISION,
+                initial_a_time : 0,
+                future_a_time : 0,
+                gas_limit : 507085500000,
+                pool_account_id : Self::env().account_id(),
+            }
+        }
+
+        #[ink(constructor)]
+        pub fn default() -> Self {
+            Self::new(vec![AccountId::from([0x01; 32]), AccountId::from([0x02; 32])], 100, 4_000_000, AccountId::default())
+        }
+
+        /// Return the tokens traded in this pool.
+        #[ink(message)]
+        pub fn coins(&self) -> Vec<AccountId>{
+            self.coins.clone()
+        }
+
+        /// Return the amount of each token held by the pool.
+        #[ink(message)]
+        pub fn reserves(&self) -> Vec<Balance>{
+            self.reserves.clone()
+        }
+
+        /// Return the swap fee, in units of 1 / FEE_DENOMINATOR.
+        #[ink(message)]
+        pub fn fee(&self) -> u128{
+            self.fee
+        }
+
+        /// Return the current amplification coefficient, multiplied by A_PRECISION.
+        #[ink(message)]
+        pub fn a(&self) -> u128{
+            Self::ramped_a(self.initial_a, self.future_a, self.initial_a_time, self.future_a_time, self.env().block_timestamp())
+        }
+
+        /// Change the amplification coefficient linearly until `future_time`.
+        ///
+        /// NOTE: Only the owner can ramp. A ramp lasts at least MIN_RAMP_TIME, can start MIN_RAMP_TIME after the previous
+        /// one and changes `A` by at most MAX_A_CHANGE times.
+        ///
+        /// #Params
+        ///
+        /// - `future_a`: Amplification coefficient at the end of the ramp, not multiplied by A_PRECISION.
+        /// - `future_time`: Time the ramp ends at.
+        #[ink(message)]
+        pub fn ramp_a(&mut self, future_a : u128, future_time : Timestamp){
+            assert!(self.env().caller() == self.owner);
+            let now = self.env().block_timestamp();
+            assert!(now >= self.initial_a_time + MIN_RAMP_TIME);
+            assert!(future_time >= now + MIN_RAMP_TIME);
+            assert!(future_a > 0 && future_a < MAX_A);
+
+            let initial_a = self.a();
+            let future_a = future_a * A_PRECISION;
+            if future_a < initial_a {
+                assert!(future_a * MAX_A_CHANGE >= initial_a);
+            }else{
+                assert!(future_a <= initial_a * MAX_A_CHANGE);
+            }
+
+            self.initial_a = initial_a;
+            self.future_a = future_a;
+            self.initial_a_time = now;
+            self.future_a_time = future_time;
+
+            self.env().emit_event( RampA {
+                old_a : initial_a,
+                new_a : future_a,
+                initial_time : now,
+                future_time,
+            });
+        }
+
+        /// Stop the ramp at the current amplification coefficient, only the owner can stop it.
+        #[ink(message)]
+        pub fn stop_ramp_a(&mut self){
+            assert!(self.env().caller() == self.owner);
+            let now = self.env().block_timestamp();
+            let current_a = self.a();
+            self.initial_a = current_a;
+            self.future_a = current_a;
+            self.initial_a_time = now;
+            self.future_a_time = now;
+
+            self.env().emit_event( StopRampA {
+                a : current_a,
+                time : now,
+            });
+        }
+
+        /// Return the value of one lp token in the pegged unit, multiplied by 10^18.
+        #[ink(message)]
+        pub fn get_virtual_price(&self) -> Balance{
+            assert!(self.total_supply > 0);
+            let d = Self::get_d(&self.reserves, self.a());
+            (U256::from(d) * U256::from(10u128.pow(18)) / U256::from(self.total_supply)).as_u128()
+        }
+
+        /// Deposit tokens in any ratio to mint lp tokens. The part of the deposit away from the current ratio pays the
+        /// imbalance fee.
+        ///
+        /// Return The amount of lp minted
+        ///
+        /// #Params
+        ///
+        /// - `amounts`: Amount of each token deposited.
+        /// - `min_mint_amount`: Minimum number of lp minted.
+        ///
+        /// NOTE: If a token transfer fails the tokens already transferred are refunded and `TokenTransferFailed` is returned.
+        #[ink(message)]
+        pub fn add_liquidity(&mut self, amounts : Vec<Balance>, min_mint_amount : Balance) -> Result<Balance>{
+            assert!(amounts.len() == self.coins.len());
+            let caller = self.env().caller();
+            if self.total_supply == 0 {
+                assert!(amounts.iter().all(|amount| *amount > 0));
+            }
+            let new_reserves : Vec<Balance> = self.reserves.iter().zip(amounts.iter()).map(|(reserve, amount)| reserve + amount).collect();
+            let (liquidity_minted, fees) = Self::liquidity_minted(&self.reserves, &new_reserves, self.total_supply, self.a(), self.fee);
+            assert!(liquidity_minted > 0 && liquidity_minted >= min_mint_amount);
+
+            for i in 0..self.coins.len() {
+                if amounts[i] == 0 {
+                    continue
+                }
+                if let Err(error) = self.token_transfer_from(self.coins[i], caller, self.pool_account_id, amounts[i]) {
+                    for j in 0..i {
+                        if amounts[j] > 0 {
+                            self.token_transfer(self.coins[j], caller, amounts[j]).expect("token transfer failed");
+                        }
+                    }
+                    return Err(error)
+                }
+            }
+
+            self.reserves = new_reserves;
+            self.mint_liquidity(caller, liquidity_minted);
+
+            self.env().emit_event( AddLiquidity {
+                provider : caller,
+                token_amounts : amounts,
+                fees,
+                liquidity_minted,
+            });
+
+            Ok(liquidity_minted)
+        }
+
+        /// Burn lp tokens to withdraw every token at current ratio.
+        ///
+        /// Return The amount of each token withdrawn.
+        ///
+        /// #Params
+        ///
+        /// - `amount`: Amount of lp burned.
+        /// - `min_amounts`: Minimum amount of each token withdrawn.
+        ///
+        /// NOTE: If a token transfer fails the whole call is reverted.
+        #[ink(message)]
+        pub fn remove_liquidity(&mut self, amount : Balance, min_amounts : Vec<Balance>) -> Vec<Balance>{
+            assert!(min_amounts.len() == self.coins.len() && amount > 0);
+            let caller = self.env().caller();
+            let total_liquidity = self.total_supply;
+            assert!(total_liquidity > 0 && self.balance_of(caller) >= amount);
+
+            let mut amounts = Vec::new();
+            for i in 0..self.coins.len() {
+                let value = (U256::from(self.reserves[i]) * U256::from(amount) / U256::from(total_liquidity)).as_u128();
+                assert!(value >= min_amounts[i]);
+                self.reserves[i] -= value;
+                amounts.push(value);
+            }
+            self.burn_liquidity(caller, amount);
+
+            for i in 0..self.coins.len() {
+                self.token_transfer(self.coins[i], caller, amounts[i]).expect("token transfer failed");
+            }
+
+            self.env().emit_event( RemoveLiquidity {
+                provider : caller,
+                token_amounts : amounts.clone(),
+                fees : vec![0; self.coins.len()],
+                liquidity_burned : amount,
+            });
+
+            amounts
+        }
+
+        /// Withdraw exact amounts of tokens in any ratio. The part of the withdrawal away from the current ratio pays the
+        /// imbalance fee.
+        ///
+        /// Return The amount of lp burned.
+        ///
+        /// #Params
+        ///
+        /// - `amounts`: Amount of each token withdrawn.
+        /// - `max_burn_amount`: Maximum number of lp burned.
+        ///
+        /// NOTE: If a token transfer fails the whole call is reverted.
+        #[ink(message)]
+        pub fn remove_liquidity_imbalance(&mut self, amounts : Vec<Balance>, max_burn_amount : Balance) -> Balance{
+            assert!(amounts.len() == self.coins.len());
+            let caller = self.env().caller();
+            assert!(self.total_supply > 0);
+            assert!(self.reserves.iter().zip(amounts.iter()).all(|(reserve, amount)| amount < reserve));
+            let new_reserves : Vec<Balance> = self.reserves.iter().zip(amounts.iter()).map(|(reserve, amount)| reserve - amount).collect();
+            let (liquidity_burned, fees) = Self::liquidity_burned(&self.reserves, &new_reserves, self.total_supply, self.a(), self.fee);
+            assert!(liquidity_burned > 0 && liquidity_burned <= max_burn_amount);
+            assert!(self.balance_of(caller) >= liquidity_burned);
+
+            self.reserves = new_reserves;
+            self.burn_liquidity(caller, liquidity_burned);
+
+            for i in 0..self.coins.len() {
+                if amounts[i] > 0 {
+                    self.token_transfer(self.coins[i], caller, amounts[i]).expect("token transfer failed");
+                }
+            }
+
+            self.env().emit_event( RemoveLiquidity {
+                provider : caller,
+                token_amounts : amounts,
+                fees,
+                liquidity_burned,
+            });
+
+            liquidity_burned
+        }
+
+        /// Return the amount of token `i` received for burning `amount` lp with `remove_liquidity_one_coin`.
+        #[ink(message)]
+        pub fn calc_withdraw_one_coin(&self, amount : Balance, i : u32) -> Balance{
+            Self::withdraw_one_coin(&self.reserves, self.total_supply, amount, i as usize, self.a(), self.fee).0
+        }
+
+        /// Burn lp tokens to withdraw a single token. The withdrawal pays the imbalance fee.
+        ///
+        /// Return The amount of the token withdrawn.
+        ///
+        /// #Params
+        ///
+        /// - `amount`: Amount of lp burned.
+        /// - `i`: Index of the token withdrawn.
+        /// - `min_amount`: Minimum amount of the token withdrawn.
+        #[ink(message)]
+        pub fn remove_liquidity_one_coin(&mut self, amount : Balance, i : u32, min_amount : Balance) -> Result<Balance>{
+            let i = i as usize;
+            assert!(i < self.coins.len() && amount > 0);
+            let caller = self.env().caller();
+            assert!(self.balance_of(caller) >= amount);
+            let (dy, fee) = Self::withdraw_one_coin(&self.reserves, self.total_supply, amount, i, self.a(), self.fee);
+            assert!(dy >= min_amount);
+
+            self.token_transfer(self.coins[i], caller, dy)?;
+
+            self.reserves[i] -= dy;
+            self.burn_liquidity(caller, amount);
+
+            let mut token_amounts = vec![0; self.coins.len()];
+            token_amounts[i] = dy;
+            let mut fees = vec![0; self.coins.len()];
+            fees[i] = fee;
+            self.env().emit_event( RemoveLiquidity {
+                provider : caller,
+                token_amounts,
+                fees,
+                liquidity_burned : amount,
+            });
+
+            Ok(dy)
+        }
+
+        /// Return the amount of token `j` bought with `dx` of token `i`.
+        #[ink(message)]
+        pub fn get_dy(&self, i : u32, j : u32, dx : Balance) -> Balance{
+            Self::swap_output(&self.reserves, i as usize, j as usize, dx, self.a(), self.fee).0
+        }
+
+        /// Return the lp minted for a deposit, or burned for a withdrawal, of `amounts`, including the imbalance fee.
+        #[ink(message)]
+        pub fn calc_token_amount(&self, amounts : Vec<Balance>, deposit : bool) -> Balance{
+            assert!(amounts.len() == self.coins.len());
+            if deposit {
+                let new_reserves : Vec<Balance> = self.reserves.iter().zip(amounts.iter()).map(|(reserve, amount)| reserve + amount).collect();
+                Self::liquidity_minted(&self.reserves, &new_reserves, self.total_supply, self.a(), self.fee).0
+            }else{
+                assert!(self.reserves.iter().zip(amounts.iter()).all(|(reserve, amount)| amount < reserve));
+                let new_reserves : Vec<Balance> = self.reserves.iter().zip(amounts.iter()).map(|(reserve, amount)| reserve - amount).collect();
+                Self::liquidity_burned(&self.reserves, &new_reserves, self.total_supply, self.a(), self.fee).0
+            }
+        }
+
+        /// Swap `dx` of token `i` for token `j`.
+        ///
+        /// Return The amount of token `j` bought.
+        ///
+        /// #Params
+        ///
+        /// - `i`: Index of the token sold.
+        /// - `j`: Index of the token bought.
+        /// - `dx`: Amount of token `i` sold.
+        /// - `min_dy`: Minimum amount of token `j` bought.
+        #[ink(message)]
+        pub fn swap(&mut self, i : u32, j : u32, dx : Balance, min_dy : Balance) -> Result<Balance>{
+            let (i, j) = (i as usize, j as usize);
+            let caller = self.env().caller();
+            let (dy, _) = Self::swap_output(&self.reserves, i, j, dx, self.a(), self.fee);
+            assert!(dy >= min_dy);
+
+            self.token_transfer_from(self.coins[i], caller, self.pool_account_id, dx)?;
+            self.token_transfer(self.coins[j], caller, dy).expect("token transfer failed");
+
+            self.reserves[i] += dx;
+            self.reserves[j] -= dy;
+
+            self.env().emit_event( TokenExchange {
+                buyer : caller,
+                sold_id : i as u32,
+                tokens_sold : dx,
+                bought_id : j as u32,
+                tokens_bought : dy,
+            });
+
+            Ok(dy)
+        }
+
+        /// Return the amplification coefficient at `now` of a linear ramp.
+        pub fn ramped_a(initial_a : u128, future_a : u128, initial_a_time : Timestamp, future_a_time : Timestamp, now : Timestamp) -> u128{
+            if now >= future_a_time {
+                return future_a
+            }
+            let elapsed = (now - initial_a_time) as u128;
+            let duration = (future_a_time - initial_a_time) as u128;
+            if future_a > initial_a {
+                initial_a + (future_a - initial_a) * elapsed / duration
+            }else{
+                initial_a - (initial_a - future_a) * elapsed / duration
+            }
+        }
+
+        /// Return the StableSwap invariant `D` of the reserves, solved with Newton iterations.
+        pub fn get_d(reserves : &[Balance], amp : u128) -> Balance{
+            let sum = reserves.iter().fold(U256::zero(), |sum, reserve| sum + U256::from(*reserve));
+            if sum.is_zero() {
+                return 0
+            }
+            assert!(reserves.iter().all(|reserve| *reserve > 0));
+            let n = U256::from(reserves.len());
+            let ann = U256::from(amp) * n;
+            let a_precision = U256::from(A_PRECISION);
+            let mut d = sum;
+            for _ in 0..MAX_ITERATIONS {
+                let mut d_p = d;
+                for reserve in reserves.iter() {
+                    d_p = d_p * d / (U256::from(*reserve) * n);
+                }
+                let d_prev = d;
+                d = (ann * sum / a_precision + d_p * n) * d / ((ann - a_precision) * d / a_precision + (n + 1) * d_p);
+                if Self::converged(d, d_prev) {
+                    return d.as_u128()
+                }
+            }
+            panic!("D does not converge")
+        }
+
+        /// Return the reserve of token `i` keeping the invariant `d` with the other reserves.
+        /// `reserves[i]` is ignored.
+        pub fn get_y_d(reserves : &[Balance], i : usize, d : Balance, amp : u128) -> Balance{
+            let n = U256::from(reserves.len());
+            let ann = U256::from(amp) * n;
+            let a_precision = U256::from(A_PRECISION);
+            let d = U256::from(d);
+
+            let mut c = d;
+            let mut sum = U256::zero();
+            for (k, reserve) in reserves.iter().enumerate() {
+                if k == i {
+                    continue
+                }
+                assert!(*reserve > 0);
+                sum = sum + U256::from(*reserve);
+                c = c * d / (U256::from(*reserve) * n);
+            }
+            c = c * d * a_precision / (ann * n);
+            let b = sum + d * a_precision / ann;
+            let mut y = d;
+            for _ in 0..MAX_ITERATIONS {
+                let y_prev = y;
+                y = (y * y + c) / (y * 2 + b - d);
+                if Self::converged(y, y_prev) {
+                    return y.as_u128()
+                }
+            }
+            panic!("y does not converge")
+        }
+
+        fn converged(value : U256, previous : U256) -> bool{
+            if value > previous { value - previous <= U256::one() } else { previous - value <= U256::one() }
+        }
+
+        /// Return the output of a swap of `dx` of token `i` for token `j`, and the fee kept in the pool.
+        pub fn swap_output(reserves : &[Balance], i : usize, j : usize, dx : Balance, amp : u128, fee : u128) -> (Balance, Balance){
+            assert!(i != j && i < reserves.len() && j < reserves.len() && dx > 0);
+            let d = Self::get_d(reserves, amp);
+            let mut new_reserves = reserves.to_vec();
+            new_reserves[i] += dx;
+            let y = Self::get_y_d(&new_reserves, j, d, amp);
+            //One unit is kept in the pool against rounding.
+            let dy = reserves[j].saturating_sub(y).saturating_sub(1);
+            let dy_fee = (U256::from(dy) * U256::from(fee) / U256::from(FEE_DENOMINATOR)).as_u128();
+            (dy - dy_fee, dy_fee)
+        }
+
+        /// Return the fee charged on each token for the imbalanced part of a change of the reserves, and the reserves
+        /// after the fee.
+        fn imbalance_fees(old_reserves : &[Balance], new_reserves : &[Balance], d0 : Balance, d1 : Balance, fee : u128) -> (Vec<Balance>, Vec<Balance>){
+            let n = old_reserves.len() as u128;
+            //The fee of a balanced swap through the pool, fee * n / (4 * (n - 1)).
+            let base_fee = fee * n / (4 * (n - 1));
+            let mut fees = Vec::new();
+            let mut reserves_after_fee = Vec::new();
+            for k in 0..old_reserves.len() {
+                let ideal_reserve = (U256::from(d1) * U256::from(old_reserves[k]) / U256::from(d0)).as_u128();
+                let difference = if ideal_reserve > new_reserves[k] { ideal_reserve - new_reserves[k] } else { new_reserves[k] - ideal_reserve };
+                let fee = (U256::from(base_fee) * U256::from(difference) / U256::from(FEE_DENOMINATOR)).as_u128();
+                fees.push(fee);
+                reserves_after_fee.push(new_reserves[k] - fee);
+            }
+            (fees, reserves_after_fee)
+        }
+
+        /// Return the lp minted for a deposit moving the reserves from `old_reserves` to `new_reserves`,
+        /// and the imbalance fee charged on each token.
+        pub fn liquidity_minted(old_reserves : &[Balance], new_reserves : &[Balance], total_liquidity : Balance, amp : u128, fee : u128) -> (Balance, Vec<Balance>){
+            let d1 = Self::get_d(new_reserves, amp);
+            if total_liquidity == 0 {
+                return (d1, vec![0; old_reserves.len()])
+            }
+            let d0 = Self::get_d(old_reserves, amp);
+            assert!(d1 > d0);
+            let (fees, reserves_after_fee) = Self::imbalance_fees(old_reserves, new_reserves, d0, d1, fee);
+            let d2 = Self::get_d(&reserves_after_fee, amp);
+            let minted = U256::from(total_liquidity) * U256::from(d2.saturating_sub(d0)) / U256::from(d0);
+            (minted.as_u128(), fees)
+        }
+
+        /// Return the lp burned for a withdrawal moving the reserves from `old_reserves` to `new_reserves`,
+        /// and the imbalance fee charged on each token.
+        pub fn liquidity_burned(old_reserves : &[Balance], new_reserves : &[Balance], total_liquidity : Balance, amp : u128, fee : u128) -> (Balance, Vec<Balance>){
+            assert!(total_liquidity > 0);
+            let d0 = Self::get_d(old_reserves, amp);
+            let d1 = Self::get_d(new_reserves, amp);
+            assert!(d1 < d0);
+            let (fees, reserves_after_fee) = Self::imbalance_fees(old_reserves, new_reserves, d0, d1, fee);
+            let d2 = Self::get_d(&reserves_after_fee, amp);
+            //Rounded up in favour of the pool.
+            let burned = U256::from(total_liquidity) * U256::from(d0 - d2) / U256::from(d0) + 1;
+            (burned.as_u128(), fees)
+        }
+
+        /// Return the amount of token `i` withdrawn for burning `amount` lp, and the imbalance fee charged on it.
+        pub fn withdraw_one_coin(reserves : &[Balance], total_liquidity : Balance, amount : Balance, i : usize, amp : u128, fee : u128) -> (Balance, Balance){
+            assert!(i < reserves.len() && amount <= total_liquidity);
+            let n = reserves.len() as u128;
+            let base_fee = fee * n / (4 * (n - 1));
+            let d0 = Self::get_d(reserves, amp);
+            let d1 = d0 - (U256::from(amount) * U256::from(d0) / U256::from(total_liquidity)).as_u128();
+            let new_y = Self::get_y_d(reserves, i, d1, amp);
+
+            let mut reserves_reduced = reserves.to_vec();
+            for k in 0..reserves.len() {
+                let expected_reserve = (U256::from(reserves[k]) * U256::from(d1) / U256::from(d0)).as_u128();
+                let dx_expected = if k == i { expected_reserve - new_y } else { reserves[k] - expected_reserve };
+                reserves_reduced[k] -= (U256::from(base_fee) * U256::from(dx_expected) / U256::from(FEE_DENOMINATOR)).as_u128();
+            }
+            let dy = reserves_reduced[i] - Self::get_y_d(&reserves_reduced, i, d1, amp) - 1;
+            let dy_without_fee = reserves[i] - new_y;
+            (dy, dy_without_fee - dy)
+        }
+
+        fn mint_liquidity(&mut self, to : AccountId, value : Balance){
+            let to_liquidity = self.balance_of(to);
+            self.balances.insert(to, to_liquidity + value);
+            self.total_supply += value;
+
+            self.env().emit_event( Transfer {
+                from : AccountId::default(),
+                to,
+                value,
+            });
+        }
+
+        fn burn_liquidity(&mut self, from : AccountId, value : Balance){
+            let from_liquidity = self.balance_of(from);
+            assert!(from_liquidity >= value);
+            self.balances.insert(from, from_liquidity - value);
+            self.total_supply -= value;
+
+            self.env().emit_event( Transfer {
+                from,
+                to : AccountId::default(),
+                value,
+            });
+        }
+
+        /// Return the total liqudity in this pool.
+        #[ink(message)]
+        pub fn total_supply(&self) ->Balance{
+            self.total_supply
+        }
+
+        /// Return the liquidity of the owner
+        #[ink(message)]
+        pub fn balance_of(&self, owner : AccountId) -> Balance{
+            self.balances.get(&owner).unwrap_or(&0u128).clone()
+        }
+
+        fn transfer_from_to(&mut self, from: AccountId, to: AccountId, value: Balance) -> bool {
+            let from_balance = self.balance_of(from);
+            if from_balance < value {
+                return false
+            }
+            self.balances.insert(from, from_balance - value);
+            let to_balance = self.balance_of(to);
+            self.balances.insert(to, to_balance + value);
+
+            self.env().emit_event(Transfer {
+                from,
+                to,
+                value,
+            });
+
+            true
+        }
+
+        /// The Contract caller transfer some liquidity to another account
+        #[ink(message, selector = "0xfae3a09d")]
+        pub fn transfer(&mut self, to : AccountId, value : Balance) -> bool{
+            let caller = self.env().caller();
+            self.transfer_from_to(caller, to, value)
+        }
+
+        /// The Contract caller transfer some liquidity from an account to another account
+        ///
+        /// NOTE: The allowance is only spent if the transfer succeeds.
+        #[ink(message, selector = "0xfcfb2ccd")]
+        pub fn transfer_from(&mut self, from : AccountId, to : AccountId, value : Balance) -> bool{
+            let caller = self.env().caller();
+            let allowance = self.allowance(from, caller);
+            if allowance < value {
+                 return false
+            }
+            if !self.transfer_from_to(from, to, value) {
+                return false
+            }
+            self.allowances.insert((from, caller), allowance - value);
+
+            true
+        }
+
+        /// Approve spender can transfer liquidity from the caller account
+        #[ink(message)]
+        pub fn approve(&mut self, spender: AccountId, value: Balance) -> bool {
+            let owner = self.env().caller();
+            self.allowances.insert((owner, spender), value);
+            self.env().emit_event(Approval {
+                owner,
+                spender,
+                value,
+            });
+            true
+        }
+
+        /// Return the liquidity spender can transfer from the owner account
+        #[ink(message)]
+        pub fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+            self.allowances.get(&(owner, spender)).unwrap_or(&0u128).clone()
+        }
+
+        fn token_transfer(&mut self, token : AccountId, to : AccountId, value : Balance)->Result<()>{
+            common::token::transfer(token, to, value, self.gas_limit / 2)
+        }
+
+        fn token_transfer_from(&mut self, token : AccountId, from : AccountId, to : AccountId, value : Balance) ->Result<()>{
+            common::token::transfer_from(token, from, to, value, self.gas_limit / 2)
+        }
+
+        ///Return the StablePool self account id.
+        #[ink(message)]
+        pub fn get_address(&self) ->AccountId{
+            self.pool_account_id
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::stable_pool::A_PRECISION;
+    use ink_env::{
+        AccountId,
+    };
+    use ink_lang as ink;
+
+    const FEE : u128 = 4_000_000;
+
+    fn set_caller(caller: AccountId) {
+        let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().unwrap_or([0x0; 32].into());
+        let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+        data.push_arg(&caller);
+        ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+            caller,
+            callee,
+            1000000,
+            1000000,
+            data,
+        );
+    }
+
+    #[ink::test]
+    fn test_get_d_of_balanced_reserves_should_be_their_sum(){
+        let amp = 200 * A_PRECISION;
+        let d = StablePool::get_d(&[1_000_000_000_000, 1_000_000_000_000, 1_000_000_000_000], amp);
+        assert!(d >= 2_999_999_999_999 && d <= 3_000_000_000_001);
+        let d = StablePool::get_d(&[1_000_000_000_000, 2_000_000_000_000, 1_000_000_000_000, 500_000_000_000], amp);
+        assert!(d < 4_500_000_000_000 && d > 4_480_000_000_000);
+    }
+
+    #[ink::test]
+    fn test_swap_should_be_close_to_peg(){
+        let amp = 200 * A_PRECISION;
+        let reserves = [1_000_000_000_000_000u128; 3];
+        let (dy, fee) = StablePool::swap_output(&reserves, 0, 2, 1_000_000_000_000, amp, FEE);
+        assert!(dy > 999_500_000_000 && dy < 999_600_000_001);
+        assert!(fee > 399_000_000 && fee <= 400_000_000);
+    }
+
+    #[ink::test]
+    fn test_imbalanced_deposit_should_pay_fee(){
+        let amp = 200 * A_PRECISION;
+        let reserves = [1_000_000_000_000u128; 3];
+        let (total_liquidity, _) = StablePool::liquidity_minted(&[0; 3], &reserves, 0, amp, FEE);
+
+        let (balanced, fees) = StablePool::liquidity_minted(&reserves, &[1_100_000_000_000; 3], total_liquidity, amp, FEE);
+        assert!(balanced >= 299_999_999_998);
+        assert!(fees.iter().all(|fee| *fee <= 1));
+        let (imbalanced, fees) = StablePool::liquidity_minted(&reserves, &[1_300_000_000_000, 1_000_000_000_000, 1_000_000_000_000],
+            total_liquidity, amp, FEE);
+        assert!(imbalanced < balanced);
+        assert!(fees[0] > 0);
+    }
+
+    #[ink::test]
+    fn test_withdraw_one_coin_should_pay_fee(){
+        let amp = 200 * A_PRECISION;
+        let reserves = [1_000_000_000_000u128; 4];
+        let total_liquidity = StablePool::get_d(&reserves, amp);
+
+        let (dy, fee) = StablePool::withdraw_one_coin(&reserves, total_liquidity, 100_000_000_000, 1, amp, FEE);
+        assert!(fee > 0);
+        assert!(dy < 100_000_000_000 && dy > 99_000_000_000);
+
+        let (burned, _) = StablePool::liquidity_burned(&reserves, &[1_000_000_000_000, 1_000_000_000_000 - dy, 1_000_000_000_000, 1_000_000_000_000],
+            total_liquidity, amp, FEE);
+        assert!(burned <= 100_000_000_001 && burned > 99_900_000_000);
+    }
+
+    #[ink::test]
+    fn test_failed_transfer_from_should_keep_allowance(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut contract = StablePool::default();
+        contract.balances.insert(accounts.alice, 100);
+        set_caller(accounts.alice);
+        assert!(contract.approve(accounts.bob, 300));
+
+        set_caller(accounts.bob);
+        assert!(!contract.transfer_from(accounts.alice, accounts.bob, 200));
+        assert_eq!(contract.allowance(accounts.alice, accounts.bob), 300);
+        assert!(contract.transfer_from(accounts.alice, accounts.bob, 100));
+        assert_eq!(contract.allowance(accounts.alice, accounts.bob), 200);
+        assert_eq!(contract.balance_of(accounts.bob), 100);
+    }
+}
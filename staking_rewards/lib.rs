@@ -0,0 +1,452 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::staking_rewards::StakingRewards;
+
+use ink_lang as ink;
+
+#[ink::contract]
+mod staking_rewards {
+
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::{
+        collections::HashMap as StorageHashMap,
+        traits::{PackedLayout, SpreadLayout},
+    };
+
+    use ink_prelude::vec::Vec;
+
+    use primitive_types::U256;
+
+    /// Rewards per staked token are multiplied by it.
+    pub const REWARD_PRECISION : u128 = 1_000_000_000_000_000_000;
+
+    /// Distribution of a reward token.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub struct RewardData {
+        //the account allowed to notify new rewards
+        pub distributor : AccountId,
+        //length of a reward period, in milliseconds
+        pub duration : Timestamp,
+        //time at which the current reward period ends
+        pub period_finish : Timestamp,
+        //rewards distributed per millisecond until `period_finish`
+        pub reward_rate : Balance,
+        //time up to which `reward_per_token_stored` is accumulated
+        pub last_update_time : Timestamp,
+        //rewards per staked token since the reward was added, multiplied by REWARD_PRECISION
+        pub reward_per_token_stored : u128,
+    }
+
+    #[ink(event)]
+    pub struct RewardAdded {
+        #[ink(topic)]
+        reward_token : AccountId,
+        reward : Balance,
+        period_finish : Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct Staked {
+        #[ink(topic)]
+        user : AccountId,
+        amount : Balance,
+    }
+
+    #[ink(event)]
+    pub struct Withdrawn {
+        #[ink(topic)]
+        user : AccountId,
+        amount : Balance,
+    }
+
+    #[ink(event)]
+    pub struct RewardPaid {
+        #[ink(topic)]
+        user : AccountId,
+        #[ink(topic)]
+        reward_token : AccountId,
+        reward : Balance,
+    }
+
+    pub use common::token::{Error, Result};
+
+    /// Staking of one LP token rewarded in several Erc20 tokens. Each reward token is distributed at its own rate over
+    /// reward periods funded by its distributor, pro rata to the stakes.
+    #[ink(storage)]
+    pub struct StakingRewards {
+        //the account allowed to add reward tokens
+        owner : AccountId,
+        //the Exchange whose LP token is staked
+        staking_token : AccountId,
+        total_supply : Balance,
+        balances : StorageHashMap<AccountId, Balance>,
+        reward_tokens : Vec<AccountId>,
+        reward_data : StorageHashMap<AccountId, RewardData>,
+        //reward per token already counted in `rewards`, per (reward token, user)
+        user_reward_per_token_paid : StorageHashMap<(AccountId, AccountId), u128>,
+        //rewards earned and not paid yet, per (reward token, user)
+        rewards : StorageHashMap<(AccountId, AccountId), Balance>,
+        gas_limit :u64,
+        staking_rewards_account_id : AccountId,
+    }
+
+    impl StakingRewards {
+
+        /// Constructor of the StakingRewards contract
+        ///
+        /// #Params
+        ///
+        /// - `staking_token`: The Exchange whose LP token is staked.
+        #[ink(constructor)]
+        pub fn new(staking_token : AccountId) -> Self {
+            Self{
+                owner : Self::env().caller(),
+                staking_token,
+                total_supply : 0,
+                balances : StorageHashMap::new(),
+                reward_tokens : Vec::new(),
+                reward_data : StorageHashMap::new(),
+                user_reward_per_token_paid : StorageHashMap::new(),
+                rewards : StorageHashMap::new(),
+                gas_limit : 507085500000,
+                staking_rewards_account_id : Self::env().account_id(),
+            }
+        }
+
+        #[ink(constructor)]
+        pub fn default() -> Self {
+            Self::new(AccountId::from([0x01; 32]))
+        }
+
+        /// Add a reward token. Only the owner can add reward tokens.
+        ///
+        /// #Params
+        ///
+        /// - `reward_token`: AccountId of the Erc20 reward token.
+        /// - `distributor`: The account allowed to notify rewards of this token.
+        /// - `duration`: Length of a reward period, in milliseconds.
+        #[ink(message)]
+        pub fn add_reward(&mut self, reward_token : AccountId, distributor : AccountId, duration : Timestamp){
+            assert!(self.env().caller() == self.owner);
+            assert!(reward_token != self.staking_token && !self.reward_data.contains_key(&reward_token));
+            assert!(duration > 0);
+            self.reward_tokens.push(reward_token);
+            self.reward_data.insert(reward_token, RewardData {
+                distributor,
+                duration,
+                period_finish : 0,
+                reward_rate : 0,
+                last_update_time : 0,
+                reward_per_token_stored : 0,
+            });
+        }
+
+        /// Change the distributor of a reward token. Only the owner can change it.
+        #[ink(message)]
+        pub fn set_reward_distributor(&mut self, reward_token : AccountId, distributor : AccountId){
+            assert!(self.env().caller() == self.owner);
+            let mut data = self.reward_data.get(&reward_token).unwrap().clone();
+            data.distributor = distributor;
+            self.reward_data.insert(reward_token, data);
+        }
+
+        /// Change the length of the next reward periods of a reward token, once the current period finished.
+        /// Only the distributor can change it.
+        #[ink(message)]
+        pub fn set_rewards_duration(&mut self, reward_token : AccountId, duration : Timestamp){
+            let mut data = self.reward_data.get(&reward_token).unwrap().clone();
+            assert!(self.env().caller() == data.distributor);
+            assert!(self.env().block_timestamp() > data.period_finish && duration > 0);
+            data.duration = duration;
+            self.reward_data.insert(reward_token, data);
+        }
+
+        /// Start a reward period distributing `reward` tokens, plus the rewards left from the current period. The
+        /// tokens are pulled from the distributor with `transfer_from`.
+        ///
+        /// #Params
+        ///
+        /// - `reward_token`: The reward token.
+        /// - `reward`: Amount of reward tokens added.
+        #[ink(message)]
+        pub fn notify_reward_amount(&mut self, reward_token : AccountId, reward : Balance) -> Result<()>{
+            let distributor = self.env().caller();
+            assert!(distributor == self.reward_data.get(&reward_token).unwrap().distributor);
+            self.token_transfer_from(reward_token, distributor, self.staking_rewards_account_id, reward)?;
+
+            self.update_rewards(None);
+            let mut data = self.reward_data.get(&reward_token).unwrap().clone();
+            let now = self.env().block_timestamp();
+            let total_reward = if now >= data.period_finish {
+                reward
+            } else {
+                let leftover = U256::from(data.period_finish - now) * U256::from(data.reward_rate);
+                (U256::from(reward) + leftover).as_u128()
+            };
+            data.reward_rate = total_reward / data.duration as Balance;
+            //The rate must be paid from the balance, or the last stakers to claim would not be paid.
+            assert!(data.reward_rate > 0 && data.reward_rate <= self.token_balance(reward_token) / data.duration as Balance);
+            data.last_update_time = now;
+            data.period_finish = now + data.duration;
+            let period_finish = data.period_finish;
+            self.reward_data.insert(reward_token, data);
+
+            self.env().emit_event( RewardAdded {
+                reward_token,
+                reward,
+                period_finish,
+            });
+            Ok(())
+        }
+
+        /// Stake LP tokens. They are pulled with `transfer_from`, so the caller approves this contract on the Exchange first.
+        #[ink(message)]
+        pub fn stake(&mut self, amount : Balance) -> Result<()>{
+            assert!(amount > 0);
+            let caller = self.env().caller();
+            self.token_transfer_from(self.staking_token, caller, self.staking_rewards_account_id, amount)?;
+
+            self.update_rewards(Some(caller));
+            self.total_supply += amount;
+            let balance = self.balance_of(caller);
+            self.balances.insert(caller, balance + amount);
+
+            self.env().emit_event( Staked {
+                user : caller,
+                amount,
+            });
+            Ok(())
+        }
+
+        /// Unstake LP tokens, the rewards stay claimable.
+        #[ink(message)]
+        pub fn withdraw(&mut self, amount : Balance) -> Result<()>{
+            assert!(amount > 0);
+            let caller = self.env().caller();
+            let balance = self.balance_of(caller);
+            assert!(balance >= amount);
+            self.token_transfer(self.staking_token, caller, amount)?;
+
+            self.update_rewards(Some(caller));
+            self.total_supply -= amount;
+            self.balances.insert(caller, balance - amount);
+
+            self.env().emit_event( Withdrawn {
+                user : caller,
+                amount,
+            });
+            Ok(())
+        }
+
+        /// Claim the rewards of every reward token in one call.
+        ///
+        /// Return (reward token, amount) of each reward paid.
+        ///
+        /// NOTE: The rewards of a token which refuses the transfer stay claimable.
+        #[ink(message)]
+        pub fn get_reward(&mut self) -> Vec<(AccountId, Balance)>{
+            let caller = self.env().caller();
+            self.update_rewards(Some(caller));
+            let mut paid = Vec::new();
+            for reward_token in self.reward_tokens.clone() {
+                let reward = self.rewards.get(&(reward_token, caller)).cloned().unwrap_or(0);
+                if reward == 0 || self.token_transfer(reward_token, caller, reward).is_err() {
+                    continue
+                }
+                self.rewards.insert((reward_token, caller), 0);
+                paid.push((reward_token, reward));
+
+                self.env().emit_event( RewardPaid {
+                    user : caller,
+                    reward_token,
+                    reward,
+                });
+            }
+            paid
+        }
+
+        /// Unstake all the LP tokens of the caller and claim all the rewards.
+        ///
+        /// Return (reward token, amount) of each reward paid.
+        #[ink(message)]
+        pub fn exit(&mut self) -> Result<Vec<(AccountId, Balance)>>{
+            let balance = self.balance_of(self.env().caller());
+            if balance > 0 {
+                self.withdraw(balance)?;
+            }
+            Ok(self.get_reward())
+        }
+
+        /// Return the time up to which the rewards of a reward token are distributed, now or the end of the period.
+        #[ink(message)]
+        pub fn last_time_reward_applicable(&self, reward_token : AccountId) -> Timestamp{
+            let data = self.reward_data.get(&reward_token).unwrap();
+            self.env().block_timestamp().min(data.period_finish)
+        }
+
+        /// Return the rewards per staked token since the reward token was added, multiplied by REWARD_PRECISION.
+        #[ink(message)]
+        pub fn reward_per_token(&self, reward_token : AccountId) -> u128{
+            let data = self.reward_data.get(&reward_token).unwrap();
+            let last_time = self.last_time_reward_applicable(reward_token);
+            if self.total_supply == 0 || last_time <= data.last_update_time {
+                return data.reward_per_token_stored
+            }
+            data.reward_per_token_stored + Self::reward_per_token_increase(last_time - data.last_update_time, data.reward_rate, self.total_supply)
+        }
+
+        /// Return the rewards of a reward token `account` can claim.
+        #[ink(message)]
+        pub fn earned(&self, account : AccountId, reward_token : AccountId) -> Balance{
+            let paid = self.user_reward_per_token_paid.get(&(reward_token, account)).cloned().unwrap_or(0);
+            let rewards = self.rewards.get(&(reward_token, account)).cloned().unwrap_or(0);
+            Self::earned_amount(self.balance_of(account), self.reward_per_token(reward_token), paid, rewards)
+        }
+
+        /// Return the rewards of a reward token distributed over a whole period at the current rate.
+        #[ink(message)]
+        pub fn get_reward_for_duration(&self, reward_token : AccountId) -> Balance{
+            let data = self.reward_data.get(&reward_token).unwrap();
+            (U256::from(data.reward_rate) * U256::from(data.duration)).as_u128()
+        }
+
+        /// Return the reward tokens.
+        #[ink(message)]
+        pub fn reward_tokens(&self) -> Vec<AccountId>{
+            self.reward_tokens.clone()
+        }
+
+        /// Return the distribution of a reward token, None if it is not a reward token.
+        #[ink(message)]
+        pub fn reward_data(&self, reward_token : AccountId) -> Option<RewardData>{
+            self.reward_data.get(&reward_token).cloned()
+        }
+
+        /// Return the LP tokens staked.
+        #[ink(message)]
+        pub fn total_supply(&self) -> Balance{
+            self.total_supply
+        }
+
+        /// Return the LP tokens staked by `owner`.
+        #[ink(message)]
+        pub fn balance_of(&self, owner : AccountId) -> Balance{
+            self.balances.get(&owner).cloned().unwrap_or(0)
+        }
+
+        /// Return the Exchange whose LP token is staked.
+        #[ink(message)]
+        pub fn staking_token(&self) -> AccountId{
+            self.staking_token
+        }
+
+        /// Accumulate the rewards of every reward token up to now, and move the rewards `account` earned to `rewards`.
+        fn update_rewards(&mut self, account : Option<AccountId>){
+            for reward_token in self.reward_tokens.clone() {
+                let reward_per_token = self.reward_per_token(reward_token);
+                let last_time = self.last_time_reward_applicable(reward_token);
+                let mut data = self.reward_data.get(&reward_token).unwrap().clone();
+                data.reward_per_token_stored = reward_per_token;
+                data.last_update_time = last_time;
+                self.reward_data.insert(reward_token, data);
+
+                if let Some(account) = account {
+                    let earned = self.earned(account, reward_token);
+                    self.rewards.insert((reward_token, account), earned);
+                    self.user_reward_per_token_paid.insert((reward_token, account), reward_per_token);
+                }
+            }
+        }
+
+        /// Return the increase of the rewards per staked token over `elapsed` milliseconds, multiplied by REWARD_PRECISION.
+        pub fn reward_per_token_increase(elapsed : Timestamp, reward_rate : Balance, total_supply : Balance) -> u128{
+            let increase = U256::from(elapsed) * U256::from(reward_rate) * U256::from(REWARD_PRECISION) / U256::from(total_supply);
+            assert!(increase <= U256::from(u128::MAX));
+            increase.as_u128()
+        }
+
+        /// Return the rewards of a stake of `balance` between `reward_per_token_paid` and `reward_per_token`, plus the
+        /// `rewards` earned before.
+        pub fn earned_amount(balance : Balance, reward_per_token : u128, reward_per_token_paid : u128, rewards : Balance) -> Balance{
+            let earned = U256::from(balance) * U256::from(reward_per_token - reward_per_token_paid) / U256::from(REWARD_PRECISION);
+            (earned + U256::from(rewards)).as_u128()
+        }
+
+        fn token_balance(&self, token : AccountId) -> Balance{
+            common::token::balance_of(token, self.staking_rewards_account_id, self.gas_limit / 2)
+        }
+
+        fn token_transfer(&mut self, token : AccountId, to : AccountId, value : Balance)->Result<()>{
+            common::token::transfer(token, to, value, self.gas_limit / 2)
+        }
+
+        fn token_transfer_from(&mut self, token : AccountId, from : AccountId, to : AccountId, value : Balance) ->Result<()>{
+            common::token::transfer_from(token, from, to, value, self.gas_limit / 2)
+        }
+
+        ///Return the StakingRewards self account id.
+        #[ink(message)]
+        pub fn get_address(&self) ->AccountId{
+            self.staking_rewards_account_id
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::staking_rewards::REWARD_PRECISION;
+    use ink_env::{
+        AccountId,
+    };
+    use ink_lang as ink;
+
+    fn set_caller(caller: AccountId) {
+        let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().unwrap_or([0x0; 32].into());
+        let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+        data.push_arg(&caller);
+        ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+            caller,
+            callee,
+            1000000,
+            1000000,
+            data,
+        );
+    }
+
+    #[ink::test]
+    fn test_rewards_should_accumulate_per_staked_token(){
+        //10 tokens per millisecond over 1000 milliseconds, shared by 4000 staked tokens.
+        let reward_per_token = StakingRewards::reward_per_token_increase(1000, 10, 4000);
+        assert_eq!(reward_per_token, 5 * REWARD_PRECISION / 2);
+        assert_eq!(StakingRewards::earned_amount(1000, reward_per_token, 0, 0), 2500);
+        assert_eq!(StakingRewards::earned_amount(3000, reward_per_token, 0, 7), 7507);
+        //A stake only earns from the reward per token paid at its last update.
+        assert_eq!(StakingRewards::earned_amount(3000, 2 * reward_per_token, reward_per_token, 0), 7500);
+    }
+
+    #[ink::test]
+    fn test_reward_per_token_should_not_overflow(){
+        let reward_per_token = StakingRewards::reward_per_token_increase(u64::MAX, u64::MAX as u128, 1 << 100);
+        assert_eq!(StakingRewards::earned_amount(1 << 100, reward_per_token, 0, 0), 340282366920938463426481118595677261525);
+    }
+
+    #[ink::test]
+    fn test_add_reward_should_register_reward_tokens(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut staking_rewards = StakingRewards::default();
+        staking_rewards.add_reward(AccountId::from([0x02; 32]), accounts.bob, 1000);
+        staking_rewards.add_reward(AccountId::from([0x03; 32]), accounts.charlie, 2000);
+        assert_eq!(staking_rewards.reward_tokens(), vec![AccountId::from([0x02; 32]), AccountId::from([0x03; 32])]);
+        let data = staking_rewards.reward_data(AccountId::from([0x03; 32])).unwrap();
+        assert_eq!((data.distributor, data.duration, data.reward_rate), (accounts.charlie, 2000, 0));
+        assert_eq!(staking_rewards.reward_per_token(AccountId::from([0x02; 32])), 0);
+        assert_eq!(staking_rewards.earned(accounts.alice, AccountId::from([0x02; 32])), 0);
+
+        //Only the distributor can change the duration.
+        set_caller(accounts.bob);
+        staking_rewards.set_rewards_duration(AccountId::from([0x02; 32]), 500);
+        assert_eq!(staking_rewards.reward_data(AccountId::from([0x02; 32])).unwrap().duration, 500);
+    }
+}
@@ -0,0 +1,874 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::twamm::Twamm;
+
+use ink_lang as ink;
+
+#[ink::contract]
+mod twamm {
+
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::{
+        collections::HashMap as StorageHashMap,
+        traits::{PackedLayout, SpreadLayout},
+    };
+
+    #[cfg(all(not(feature = "ink-as-dependency"), not(test)))]
+    use ink_env::{
+         call,
+         DefaultEnvironment,
+         call::{ build_call, ExecutionInput, utils::ReturnType,}
+    };
+
+    #[cfg(not(test))]
+    use exchange::TokenReceivedAction;
+
+    use primitive_types::U256;
+
+    /// Serial number of a long-term order.
+    pub type OrderId = u128;
+
+    /// Earnings factors are multiplied by it.
+    pub const EARNINGS_PRECISION : u128 = 1_000_000_000_000_000_000;
+
+    /// Prices are in Dot per token multiplied by it.
+    pub const PRICE_PRECISION : u128 = 1_000_000_000_000_000_000;
+
+    /// Price deviations are in basis points.
+    pub const DEVIATION_DENOMINATOR : u128 = 10000;
+
+    /// Number of intervals the messages touching the orders settle at most before they proceed.
+    pub const MAX_INTERVALS_PER_CALL : u32 = 50;
+
+    /// What a long-term order sells on the Exchange.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub enum Direction {
+        /// Sell Dot for the Exchange token.
+        DotToToken,
+        /// Sell the Exchange token for Dot.
+        TokenToDot,
+    }
+
+    impl Direction {
+        fn index(self) -> usize {
+            match self {
+                Direction::DotToToken => 0,
+                Direction::TokenToDot => 1,
+            }
+        }
+    }
+
+    /// An order selling at a constant rate per block until its expiry.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub struct LongTermOrder {
+        pub owner : AccountId,
+        pub direction : Direction,
+        //amount sold per block
+        pub sales_rate : Balance,
+        //block at which the order stops selling, an interval boundary
+        pub expiry_block : BlockNumber,
+        //earnings factor of the direction when the proceeds were last withdrawn
+        pub earnings_factor_last : u128,
+    }
+
+    #[ink(event)]
+    pub struct LongTermOrderSubmitted {
+        #[ink(topic)]
+        order_id : OrderId,
+        #[ink(topic)]
+        owner : AccountId,
+        direction : Direction,
+        sales_rate : Balance,
+        expiry_block : BlockNumber,
+    }
+
+    #[ink(event)]
+    pub struct LongTermOrderCancelled {
+        #[ink(topic)]
+        order_id : OrderId,
+        unsold : Balance,
+        proceeds : Balance,
+    }
+
+    #[ink(event)]
+    pub struct ProceedsWithdrawn {
+        #[ink(topic)]
+        order_id : OrderId,
+        proceeds : Balance,
+        refund : Balance,
+    }
+
+    #[ink(event)]
+    pub struct SettlementDeferred {
+        block : BlockNumber,
+        price : u128,
+        reference_price : u128,
+    }
+
+    #[ink(event)]
+    pub struct VirtualOrdersExecuted {
+        from_block : BlockNumber,
+        to_block : BlockNumber,
+        dot_sold : Balance,
+        tokens_bought : Balance,
+        tokens_sold : Balance,
+        dot_bought : Balance,
+    }
+
+    pub use common::token::{Error, Result};
+
+    /// Time-weighted average market maker next to an Exchange. Long-term orders sell a large amount evenly over many
+    /// blocks, the sales of all orders are settled lazily through the Exchange each time this contract is touched.
+    #[ink(storage)]
+    pub struct Twamm {
+        exchange : AccountId,
+        //the Exchange token
+        token : AccountId,
+        //orders expire at multiples of this number of blocks
+        order_block_interval : BlockNumber,
+        //block up to which the sales are settled
+        last_virtual_order_block : BlockNumber,
+        //sum of the sales rates of the active orders, per direction
+        sales_rate : [Balance; 2],
+        //sales rate of the orders expiring at a block, per direction
+        sales_rate_ending : StorageHashMap<(u8, BlockNumber), Balance>,
+        //proceeds per sales rate since the creation, multiplied by EARNINGS_PRECISION, per direction
+        earnings_factor : [u128; 2],
+        //earnings factor at an interval boundary, per direction
+        earnings_factor_at_interval : StorageHashMap<(u8, BlockNumber), u128>,
+        //sales too small to buy anything yet, carried to the next settlement, per direction
+        pending_sales : [Balance; 2],
+        //pending sales given back per sales rate of the orders expiring at an interval boundary when no order of the
+        //direction is left to sell them, multiplied by EARNINGS_PRECISION, per direction
+        refund_factor_at_interval : StorageHashMap<(u8, BlockNumber), u128>,
+        //maximum deviation of the Exchange price from the reference price per interval, in basis points
+        max_price_deviation_bps : u32,
+        //the Exchange price after the last settlement, multiplied by PRICE_PRECISION. 0 until the first settlement
+        reference_price : u128,
+        //block at which the reference price was recorded
+        reference_block : BlockNumber,
+        orders : StorageHashMap<OrderId, LongTermOrder>,
+        order_count : OrderId,
+        gas_limit :u64,
+        twamm_account_id : AccountId,
+    }
+
+    impl Twamm {
+
+        /// Constructor of the Twamm contract
+        ///
+        /// #Params
+        ///
+        /// - `exchange`: The Exchange the orders are settled on.
+        /// - `token`: The Exchange token.
+        /// - `order_block_interval`: Orders expire at multiples of this number of blocks.
+        /// - `max_price_deviation_bps`: How far the Exchange price may move away from the price after the last
+        ///   settlement, per interval elapsed since, before the settlement is deferred.
+        #[ink(constructor)]
+        pub fn new(exchange : AccountId, token : AccountId, order_block_interval : BlockNumber, max_price_deviation_bps : u32) -> Self {
+            assert!(exchange != AccountId::default() && token != AccountId::default());
+            assert!(order_block_interval > 0);
+            assert!(max_price_deviation_bps > 0 && max_price_deviation_bps as u128 <= DEVIATION_DENOMINATOR);
+            Self{
+                exchange,
+                token,
+                order_block_interval,
+                last_virtual_order_block : Self::env().block_number(),
+                sales_rate : [0; 2],
+                sales_rate_ending : StorageHashMap::new(),
+                earnings_factor : [0; 2],
+                earnings_factor_at_interval : StorageHashMap::new(),
+                pending_sales : [0; 2],
+                refund_factor_at_interval : StorageHashMap::new(),
+                max_price_deviation_bps,
+                reference_price : 0,
+                reference_block : Self::env().block_number(),
+                orders : StorageHashMap::new(),
+                order_count : 0,
+                gas_limit : 507085500000,
+                twamm_account_id : Self::env().account_id(),
+            }
+        }
+
+        #[ink(constructor)]
+        pub fn default() -> Self {
+            Self::new(AccountId::from([0x01; 32]), AccountId::from([0x02; 32]), 10, 100)
+        }
+
+        /// Submit an order selling `amount` evenly per block until `num_intervals` intervals after the current one.
+        ///
+        /// Return The order id.
+        ///
+        /// #Params
+        ///
+        /// - `direction`: What the order sells. Dot is transferred with the call, tokens with `transfer_from`.
+        /// - `amount`: Amount sold. The part not divisible by the number of blocks is not taken.
+        /// - `num_intervals`: Number of whole intervals the order sells during.
+        #[ink(message, payable)]
+        pub fn submit_long_term_order(&mut self, direction : Direction, amount : Balance, num_intervals : BlockNumber) -> Result<OrderId>{
+            self.settle_up_to_date();
+            let owner = self.env().caller();
+            let transferred_balance = self.env().transferred_balance();
+            match direction {
+                Direction::DotToToken => assert!(transferred_balance == amount),
+                Direction::TokenToDot => assert!(transferred_balance == 0),
+            }
+            let current_block = self.env().block_number();
+            let expiry_block = Self::order_expiry(current_block, self.order_block_interval, num_intervals);
+            let sales_rate = amount / (expiry_block - current_block) as Balance;
+            assert!(sales_rate > 0);
+            let amount_sold = sales_rate * (expiry_block - current_block) as Balance;
+
+            match direction {
+                Direction::DotToToken => {
+                    if amount > amount_sold {
+                        self.env().transfer(owner, amount - amount_sold).expect("transfer error");
+                    }
+                }
+                Direction::TokenToDot => self.token_transfer_from(owner, self.twamm_account_id, amount_sold)?,
+            }
+
+            if self.sales_rate == [0; 2] {
+                //Nothing was settled while no order was active, the price may have moved since the last check.
+                self.refresh_reference_price();
+            }
+            let index = direction.index();
+            self.sales_rate[index] += sales_rate;
+            let ending = self.sales_rate_ending.get(&(index as u8, expiry_block)).cloned().unwrap_or(0);
+            self.sales_rate_ending.insert((index as u8, expiry_block), ending + sales_rate);
+
+            let order_id = self.order_count + 1;
+            self.order_count = order_id;
+            self.orders.insert(order_id, LongTermOrder {
+                owner,
+                direction,
+                sales_rate,
+                expiry_block,
+                earnings_factor_last : self.earnings_factor[index],
+            });
+
+            self.env().emit_event( LongTermOrderSubmitted {
+                order_id,
+                owner,
+                direction,
+                sales_rate,
+                expiry_block,
+            });
+            Ok(order_id)
+        }
+
+        /// Cancel an order owned by the caller which is not settled up to its expiry. The amount not sold as of the last
+        /// settlement and the proceeds are sent back, so an order can be cancelled while the settlement is deferred.
+        ///
+        /// Return (unsold amount, proceeds).
+        #[ink(message)]
+        pub fn cancel_long_term_order(&mut self, order_id : OrderId) -> Result<(Balance, Balance)>{
+            self.execute_virtual_orders(MAX_INTERVALS_PER_CALL);
+            let order = self.orders.get(&order_id).unwrap().clone();
+            let owner = self.env().caller();
+            assert!(order.owner == owner);
+            assert!(self.last_virtual_order_block < order.expiry_block);
+
+            let index = order.direction.index();
+            let mut unsold = order.sales_rate * (order.expiry_block - self.last_virtual_order_block) as Balance;
+            let last_order = self.sales_rate[index] == order.sales_rate;
+            if last_order {
+                //No order is left to sell the pending sales, they are given back with the unsold amount.
+                unsold += self.pending_sales[index];
+            }
+            let proceeds = Self::proceeds(order.sales_rate, self.earnings_factor[index], order.earnings_factor_last);
+
+            //The unsold amount and the proceeds are in different assets, the token is paid first.
+            match order.direction {
+                Direction::DotToToken => {
+                    self.token_transfer(owner, proceeds)?;
+                    self.env().transfer(owner, unsold).expect("transfer error");
+                }
+                Direction::TokenToDot => {
+                    self.token_transfer(owner, unsold)?;
+                    self.env().transfer(owner, proceeds).expect("transfer error");
+                }
+            }
+
+            self.sales_rate[index] -= order.sales_rate;
+            let ending = self.sales_rate_ending.get(&(index as u8, order.expiry_block)).cloned().unwrap_or(0);
+            self.sales_rate_ending.insert((index as u8, order.expiry_block), ending - order.sales_rate);
+            if last_order {
+                self.pending_sales[index] = 0;
+            }
+            self.orders.take(&order_id);
+
+            self.env().emit_event( LongTermOrderCancelled {
+                order_id,
+                unsold,
+                proceeds,
+            });
+            Ok((unsold, proceeds))
+        }
+
+        /// Withdraw the proceeds of an order owned by the caller, as of the last settlement. An expired order is
+        /// removed once its sales are settled and withdrawn, and gets back its share of the sales left pending when
+        /// no order of its direction was left to sell them.
+        ///
+        /// Return (proceeds, refund). The proceeds are in tokens for DotToToken orders and in Dot for TokenToDot
+        /// orders, the refund is in what the order sells.
+        #[ink(message)]
+        pub fn withdraw_proceeds(&mut self, order_id : OrderId) -> Result<(Balance, Balance)>{
+            self.execute_virtual_orders(MAX_INTERVALS_PER_CALL);
+            let mut order = self.orders.get(&order_id).unwrap().clone();
+            let owner = self.env().caller();
+            assert!(order.owner == owner);
+
+            let index = order.direction.index();
+            let expired = self.last_virtual_order_block >= order.expiry_block;
+            let earnings_factor = self.order_earnings_factor(&order);
+            let proceeds = Self::proceeds(order.sales_rate, earnings_factor, order.earnings_factor_last);
+            let refund = if expired {
+                let refund_factor = self.refund_factor_at_interval.get(&(index as u8, order.expiry_block)).cloned().unwrap_or(0);
+                Self::proceeds(order.sales_rate, refund_factor, 0)
+            } else {
+                0
+            };
+            //The proceeds and the refund are in different assets, the token is paid first.
+            match order.direction {
+                Direction::DotToToken => {
+                    self.token_transfer(owner, proceeds)?;
+                    if refund > 0 {
+                        self.env().transfer(owner, refund).expect("transfer error");
+                    }
+                }
+                Direction::TokenToDot => {
+                    if refund > 0 {
+                        self.token_transfer(owner, refund)?;
+                    }
+                    self.env().transfer(owner, proceeds).expect("transfer error");
+                }
+            }
+
+            if expired {
+                self.orders.take(&order_id);
+            } else {
+                order.earnings_factor_last = earnings_factor;
+                self.orders.insert(order_id, order);
+            }
+
+            self.env().emit_event( ProceedsWithdrawn {
+                order_id,
+                proceeds,
+                refund,
+            });
+            Ok((proceeds, refund))
+        }
+
+        /// Return the proceeds an order can withdraw, as of the last settlement.
+        #[ink(message)]
+        pub fn proceeds_of(&self, order_id : OrderId) -> Balance{
+            let order = self.orders.get(&order_id).unwrap();
+            Self::proceeds(order.sales_rate, self.order_earnings_factor(order), order.earnings_factor_last)
+        }
+
+        /// Return an order, None if it does not exist.
+        #[ink(message)]
+        pub fn order(&self, order_id : OrderId) -> Option<LongTermOrder>{
+            self.orders.get(&order_id).cloned()
+        }
+
+        /// Return the number of orders submitted.
+        #[ink(message)]
+        pub fn order_count(&self) -> OrderId{
+            self.order_count
+        }
+
+        /// Return the Dot and token sold per block by the active orders, (Dot, token).
+        #[ink(message)]
+        pub fn sales_rates(&self) -> (Balance, Balance){
+            (self.sales_rate[0], self.sales_rate[1])
+        }
+
+        /// Return the block up to which the sales are settled.
+        #[ink(message)]
+        pub fn last_virtual_order_block(&self) -> BlockNumber{
+            self.last_virtual_order_block
+        }
+
+        /// Return the number of blocks of an interval.
+        #[ink(message)]
+        pub fn order_block_interval(&self) -> BlockNumber{
+            self.order_block_interval
+        }
+
+        /// Return the Exchange price after the last settlement, in Dot per token multiplied by PRICE_PRECISION, and
+        /// the block it was recorded at.
+        #[ink(message)]
+        pub fn reference_price(&self) -> (u128, BlockNumber){
+            (self.reference_price, self.reference_block)
+        }
+
+        /// Settle the sales of the active orders up to the current block, `max_intervals` intervals at most. Each
+        /// interval is settled with one swap per direction through the Exchange, and the proceeds are shared pro rata
+        /// to the sales rates.
+        ///
+        /// Before each swap the Exchange price is compared to the price after the last settlement. If it moved more
+        /// than `max_price_deviation_bps` per interval elapsed since, e.g. because of a swap placed right before the
+        /// settlement, the settlement stops there and is retried on the next call. The allowed deviation keeps growing,
+        /// so a price which moved for good is accepted after enough intervals.
+        ///
+        /// NOTE: Every message touching the orders calls it first. Anyone can call it so that a quiet period does not
+        /// leave too many intervals to settle at once, and a long backlog can be settled in chunks.
+        ///
+        /// Return Whether the sales are settled up to the current block.
+        #[ink(message)]
+        pub fn execute_virtual_orders(&mut self, max_intervals : u32) -> bool{
+            let current_block = self.env().block_number();
+            let mut block = self.last_virtual_order_block;
+            let mut intervals = 0;
+            while block < current_block && intervals < max_intervals {
+                if self.sales_rate == [0; 2] {
+                    //No active order, so no order expires until the current block. The reference follows the price.
+                    block = current_block;
+                    self.refresh_reference_price();
+                    break
+                }
+                if !self.price_within_bounds(block, current_block) {
+                    break
+                }
+                let next_boundary = (block / self.order_block_interval + 1) * self.order_block_interval;
+                let segment_end = next_boundary.min(current_block);
+                self.execute_segment(block, segment_end);
+                if segment_end == next_boundary {
+                    for index in 0..2 {
+                        self.earnings_factor_at_interval.insert((index as u8, next_boundary), self.earnings_factor[index]);
+                        if let Some(ending) = self.sales_rate_ending.take(&(index as u8, next_boundary)) {
+                            self.sales_rate[index] -= ending;
+                            if self.sales_rate[index] == 0 && ending > 0 && self.pending_sales[index] > 0 {
+                                //Nobody is left to earn what the pending sales buy, the expiring orders get them back.
+                                let refund_factor = U256::from(self.pending_sales[index]) * U256::from(EARNINGS_PRECISION) / U256::from(ending);
+                                self.refund_factor_at_interval.insert((index as u8, next_boundary), refund_factor.as_u128());
+                                self.pending_sales[index] = 0;
+                            }
+                        }
+                    }
+                }
+                block = segment_end;
+                intervals += 1;
+            }
+            self.last_virtual_order_block = block;
+            block == current_block
+        }
+
+        /// Settle the sales up to the current block, which orders are only submitted or cancelled at.
+        fn settle_up_to_date(&mut self){
+            assert!(self.execute_virtual_orders(MAX_INTERVALS_PER_CALL), "virtual orders not settled");
+        }
+
+        /// Check the Exchange price against the reference price, allowing more deviation the longer ago it was recorded.
+        fn price_within_bounds(&mut self, block : BlockNumber, current_block : BlockNumber) -> bool{
+            if self.reference_price == 0 {
+                //The first settlement has nothing to check against, the next ones are checked against its price.
+                return true
+            }
+            let (dot_reserve, token_reserve) = self.exchange_reserves();
+            let price = Self::spot_price(dot_reserve, token_reserve);
+            let max_deviation_bps = Self::max_deviation_bps(self.max_price_deviation_bps,
+                current_block - self.reference_block, self.order_block_interval);
+            if Self::price_deviation_bps(price, self.reference_price) > max_deviation_bps {
+                self.env().emit_event( SettlementDeferred {
+                    block,
+                    price,
+                    reference_price : self.reference_price,
+                });
+                return false
+            }
+            true
+        }
+
+        fn execute_segment(&mut self, from_block : BlockNumber, to_block : BlockNumber){
+            let blocks = (to_block - from_block) as Balance;
+            let dot_sold = self.sales_rate[0] * blocks + self.pending_sales[0];
+            let tokens_sold = self.sales_rate[1] * blocks + self.pending_sales[1];
+            let mut tokens_bought = 0;
+            let mut dot_bought = 0;
+
+            let min_tokens = if dot_sold > 0 { self.exchange_price(Direction::DotToToken, dot_sold) } else { 0 };
+            if min_tokens > 0 {
+                tokens_bought = self.exchange_dot_to_token(dot_sold, min_tokens);
+                self.pending_sales[0] = 0;
+                self.add_earnings(0, tokens_bought);
+            } else {
+                self.pending_sales[0] = dot_sold;
+            }
+            let min_dot = if tokens_sold > 0 { self.exchange_price(Direction::TokenToDot, tokens_sold) } else { 0 };
+            if min_dot > 0 {
+                dot_bought = self.exchange_token_to_dot(tokens_sold, min_dot);
+                self.pending_sales[1] = 0;
+                self.add_earnings(1, dot_bought);
+            } else {
+                self.pending_sales[1] = tokens_sold;
+            }
+
+            self.refresh_reference_price();
+
+            self.env().emit_event( VirtualOrdersExecuted {
+                from_block,
+                to_block,
+                dot_sold,
+                tokens_bought,
+                tokens_sold,
+                dot_bought,
+            });
+        }
+
+        /// Record the current Exchange price as the reference the next settlements are checked against.
+        fn refresh_reference_price(&mut self){
+            let (dot_reserve, token_reserve) = self.exchange_reserves();
+            self.reference_price = Self::spot_price(dot_reserve, token_reserve);
+            self.reference_block = self.env().block_number();
+        }
+
+        fn add_earnings(&mut self, index : usize, proceeds : Balance){
+            if self.sales_rate[index] > 0 {
+                let earnings = U256::from(proceeds) * U256::from(EARNINGS_PRECISION) / U256::from(self.sales_rate[index]);
+                self.earnings_factor[index] += earnings.as_u128();
+            }
+        }
+
+        /// Return the earnings factor an order earns up to, the one at its expiry once it expired.
+        fn order_earnings_factor(&self, order : &LongTermOrder) -> u128{
+            let index = order.direction.index();
+            if self.last_virtual_order_block >= order.expiry_block {
+                *self.earnings_factor_at_interval.get(&(index as u8, order.expiry_block)).unwrap()
+            } else {
+                self.earnings_factor[index]
+            }
+        }
+
+        /// Return the block at which an order submitted at `current_block` for `num_intervals` intervals expires.
+        pub fn order_expiry(current_block : BlockNumber, order_block_interval : BlockNumber, num_intervals : BlockNumber) -> BlockNumber{
+            let last_boundary = current_block - current_block % order_block_interval;
+            last_boundary + order_block_interval * (num_intervals + 1)
+        }
+
+        /// Return the Dot price of a token for the reserves, multiplied by PRICE_PRECISION.
+        pub fn spot_price(dot_reserve : Balance, token_reserve : Balance) -> u128{
+            if token_reserve == 0 {
+                return 0
+            }
+            (U256::from(dot_reserve) * U256::from(PRICE_PRECISION) / U256::from(token_reserve)).as_u128()
+        }
+
+        /// Return the deviation allowed `blocks_elapsed` blocks after the reference price was recorded, in basis points.
+        /// It is not capped, otherwise a price moving further than the cap for good would defer the settlement forever.
+        pub fn max_deviation_bps(max_price_deviation_bps : u32, blocks_elapsed : BlockNumber, order_block_interval : BlockNumber) -> u128{
+            let intervals = (blocks_elapsed / order_block_interval + 1) as u128;
+            max_price_deviation_bps as u128 * intervals
+        }
+
+        /// Return how far `price` is from `reference_price`, in basis points of the reference.
+        pub fn price_deviation_bps(price : u128, reference_price : u128) -> u128{
+            let difference = if price > reference_price { price - reference_price } else { reference_price - price };
+            (U256::from(difference) * U256::from(DEVIATION_DENOMINATOR) / U256::from(reference_price)).as_u128()
+        }
+
+        /// Return the proceeds of `sales_rate` between two earnings factors.
+        pub fn proceeds(sales_rate : Balance, earnings_factor : u128, earnings_factor_last : u128) -> Balance{
+            (U256::from(sales_rate) * U256::from(earnings_factor - earnings_factor_last) / U256::from(EARNINGS_PRECISION)).as_u128()
+        }
+
+        #[cfg(not(test))]
+        fn exchange_price(&self, direction : Direction, amount_sold : Balance) -> Balance{
+            let selector = match direction {
+                //dot_to_token_input_price selector in exchange 0xc10785bc
+                Direction::DotToToken => call::Selector::new([0xc1, 0x07, 0x85, 0xbc]),
+                //token_to_dot_input_price selector in exchange 0xe486494d
+                Direction::TokenToDot => call::Selector::new([0xe4, 0x86, 0x49, 0x4d]),
+            };
+            build_call::<DefaultEnvironment>()
+                .callee(self.exchange)
+                .gas_limit(self.gas_limit / 2)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(selector.into())
+                    .push_arg(amount_sold),
+                ).returns::<ReturnType<Balance>>().fire().unwrap()
+        }
+
+        #[cfg(not(test))]
+        fn exchange_reserves(&self) -> (Balance, Balance){
+            //reserves selector in exchange 0x84393fdc
+            let selector_reserves = call::Selector::new([0x84, 0x39, 0x3f, 0xdc]);
+            build_call::<DefaultEnvironment>()
+                .callee(self.exchange)
+                .gas_limit(self.gas_limit / 2)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(selector_reserves.into()),
+                ).returns::<ReturnType<(Balance, Balance)>>().fire().unwrap()
+        }
+
+        /// The minimum output is the quote of the same block, the price itself is bounded by `price_within_bounds`.
+        #[cfg(not(test))]
+        fn exchange_dot_to_token(&mut self, dot_sold : Balance, min_tokens : Balance) -> Balance{
+            //dot_to_token_transfer_input selector in exchange 0xa0a8e619
+            let selector_dot_to_token_transfer_input = call::Selector::new([0xa0, 0xa8, 0xe6, 0x19]);
+            let tokens_bought = build_call::<DefaultEnvironment>()
+                .callee(self.exchange)
+                .gas_limit(self.gas_limit / 2)
+                .transferred_value(dot_sold)
+                .exec_input(
+                    ExecutionInput::new(selector_dot_to_token_transfer_input.into())
+                    .push_arg(min_tokens)
+                    .push_arg(self.env().block_timestamp())
+                    .push_arg(self.twamm_account_id),
+                ).returns::<ReturnType<Result<Balance>>>().fire().unwrap();
+            tokens_bought.expect("virtual order swap failed")
+        }
+
+        #[cfg(not(test))]
+        fn exchange_token_to_dot(&mut self, tokens_sold : Balance, min_dot : Balance) -> Balance{
+            let balance_before = self.env().balance();
+            let data = scale::Encode::encode(&TokenReceivedAction::TokenToDot {
+                min_dot,
+                deadline : self.env().block_timestamp(),
+                recipient : self.twamm_account_id,
+            });
+            //transfer_and_call selector in erc20 0x0e60abc1, the Exchange sells the tokens in `on_token_received`
+            let selector_transfer_and_call = call::Selector::new([0x0e, 0x60, 0xab, 0xc1]);
+            let transferred = build_call::<DefaultEnvironment>()
+                .callee(self.token)
+                .gas_limit(self.gas_limit / 2)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(selector_transfer_and_call.into())
+                    .push_arg(&self.exchange)
+                    .push_arg(tokens_sold)
+                    .push_arg(data),
+                ).returns::<ReturnType<bool>>().fire().unwrap();
+            assert!(transferred, "virtual order swap failed");
+            self.env().balance() - balance_before
+        }
+
+        #[cfg(not(test))]
+        fn token_transfer(&mut self, to : AccountId, value : Balance)->Result<()>{
+            common::token::transfer(self.token, to, value, self.gas_limit / 2)
+        }
+
+        #[cfg(not(test))]
+        fn token_transfer_from(&mut self, from : AccountId, to : AccountId, value : Balance) ->Result<()>{
+            common::token::transfer_from(self.token, from, to, value, self.gas_limit / 2)
+        }
+
+        /// The off-chain environment can not call contracts, the tests set the Exchange reserves instead. Swaps buy
+        /// their quote without moving the reserves.
+        #[cfg(test)]
+        fn exchange_price(&self, direction : Direction, amount_sold : Balance) -> Balance{
+            let (dot_reserve, token_reserve) = self.exchange_reserves();
+            let (input_reserve, output_reserve) = match direction {
+                Direction::DotToToken => (dot_reserve, token_reserve),
+                Direction::TokenToDot => (token_reserve, dot_reserve),
+            };
+            (U256::from(amount_sold) * U256::from(output_reserve) / U256::from(input_reserve + amount_sold)).as_u128()
+        }
+
+        #[cfg(test)]
+        fn exchange_reserves(&self) -> (Balance, Balance){
+            crate::tests::EXCHANGE_RESERVES.with(|reserves| reserves.get())
+        }
+
+        #[cfg(test)]
+        fn exchange_dot_to_token(&mut self, _dot_sold : Balance, min_tokens : Balance) -> Balance{
+            min_tokens
+        }
+
+        #[cfg(test)]
+        fn exchange_token_to_dot(&mut self, _tokens_sold : Balance, min_dot : Balance) -> Balance{
+            min_dot
+        }
+
+        #[cfg(test)]
+        fn token_transfer(&mut self, _to : AccountId, _value : Balance)->Result<()>{
+            Ok(())
+        }
+
+        #[cfg(test)]
+        fn token_transfer_from(&mut self, _from : AccountId, _to : AccountId, _value : Balance) ->Result<()>{
+            Ok(())
+        }
+
+        ///Return the Twamm self account id.
+        #[ink(message)]
+        pub fn get_address(&self) ->AccountId{
+            self.twamm_account_id
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::twamm::{Direction, EARNINGS_PRECISION, MAX_INTERVALS_PER_CALL, PRICE_PRECISION};
+    use ink_env::{
+        AccountId,
+        Balance,
+    };
+    use ink_lang as ink;
+
+    thread_local! {
+        /// Dot and token reserves of the Exchange.
+        pub(crate) static EXCHANGE_RESERVES: core::cell::Cell<(Balance, Balance)> = core::cell::Cell::new((1_000_000, 1_000_000));
+    }
+
+    fn set_caller(caller: AccountId) {
+        let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().unwrap_or([0x0; 32].into());
+        let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+        data.push_arg(&caller);
+        ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+            caller,
+            callee,
+            1000000,
+            1000000,
+            data,
+        );
+    }
+
+    fn set_exchange_reserves(dot_reserve : Balance, token_reserve : Balance) {
+        EXCHANGE_RESERVES.with(|cell| cell.set((dot_reserve, token_reserve)));
+    }
+
+    fn set_twamm_balance(balance: Balance) {
+        let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().expect("cannot get contract account");
+        ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(callee, balance).expect("cannot set account balance");
+    }
+
+    fn advance_blocks(blocks : u32) {
+        for _ in 0..blocks {
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+        }
+    }
+
+    #[ink::test]
+    fn test_order_expiry_should_end_on_an_interval_boundary(){
+        assert_eq!(Twamm::order_expiry(0, 10, 1), 20);
+        assert_eq!(Twamm::order_expiry(7, 10, 1), 20);
+        assert_eq!(Twamm::order_expiry(10, 10, 3), 50);
+    }
+
+    #[ink::test]
+    fn test_proceeds_should_be_pro_rata_to_sales_rate(){
+        //1000 proceeds shared by a sales rate of 400, then 300 by 600.
+        let earnings_factor = 1000 * EARNINGS_PRECISION / 400 + 300 * EARNINGS_PRECISION / 600;
+        assert_eq!(Twamm::proceeds(100, earnings_factor, 0), 300);
+        assert_eq!(Twamm::proceeds(300, earnings_factor, 1000 * EARNINGS_PRECISION / 400), 150);
+    }
+
+    #[ink::test]
+    fn test_submit_long_term_order_should_add_sales_rate(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        set_exchange_reserves(1_000_000, 1_000_000);
+        let mut twamm = Twamm::default();
+        set_caller(accounts.alice);
+        //1000000 Dot sold over the 20 blocks until the second boundary.
+        let order_id = twamm.submit_long_term_order(Direction::DotToToken, 1000000, 1).unwrap();
+        assert_eq!(twamm.sales_rates(), (50000, 0));
+        let order = twamm.order(order_id).unwrap();
+        assert_eq!((order.owner, order.sales_rate, order.expiry_block), (accounts.alice, 50000, 20));
+        assert_eq!(twamm.proceeds_of(order_id), 0);
+    }
+
+    #[ink::test]
+    fn test_price_deviation_should_bound_settlement(){
+        //1 Dot per token, then a swap moves it to 1.02.
+        let reference_price = Twamm::spot_price(1_000_000, 1_000_000);
+        assert_eq!(reference_price, PRICE_PRECISION);
+        let price = Twamm::spot_price(1_010_000, 990_100);
+        assert_eq!(Twamm::price_deviation_bps(price, reference_price), 200);
+        assert_eq!(Twamm::price_deviation_bps(reference_price, price), 197);
+        //1% per interval, so the settlement is deferred in the same interval and goes through two intervals later.
+        assert!(Twamm::price_deviation_bps(price, reference_price) > Twamm::max_deviation_bps(100, 9, 10));
+        assert!(Twamm::price_deviation_bps(price, reference_price) <= Twamm::max_deviation_bps(100, 10, 10));
+        //Not capped, a price tripled for good is accepted after 200 intervals.
+        assert_eq!(Twamm::max_deviation_bps(100, 1990, 10), 20000);
+    }
+
+    #[ink::test]
+    fn test_execute_virtual_orders_without_orders_should_catch_up(){
+        set_exchange_reserves(2_000_000, 1_000_000);
+        let mut twamm = Twamm::default();
+        advance_blocks(25);
+        assert!(twamm.execute_virtual_orders(1));
+        assert_eq!(twamm.last_virtual_order_block(), 25);
+        assert_eq!(twamm.reference_price(), (2 * PRICE_PRECISION, 25));
+    }
+
+    #[ink::test]
+    fn test_deferred_settlement_should_recover(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        set_exchange_reserves(1_000_000, 1_000_000);
+        let mut twamm = Twamm::default();
+        set_caller(accounts.alice);
+        let order_id = twamm.submit_long_term_order(Direction::DotToToken, 1000000, 1).unwrap();
+        assert_eq!(twamm.reference_price(), (PRICE_PRECISION, 0));
+
+        //The price triples before the first settlement, 20000 bps away from the reference.
+        set_exchange_reserves(3_000_000, 1_000_000);
+        advance_blocks(1989);
+        assert!(!twamm.execute_virtual_orders(MAX_INTERVALS_PER_CALL));
+        assert_eq!(twamm.last_virtual_order_block(), 0);
+
+        //1% per interval, accepted once 200 intervals elapsed. Each interval sells 500000 Dot at the new price.
+        advance_blocks(1);
+        assert!(twamm.execute_virtual_orders(MAX_INTERVALS_PER_CALL));
+        assert_eq!(twamm.last_virtual_order_block(), 1990);
+        assert_eq!(twamm.sales_rates(), (0, 0));
+        assert_eq!(twamm.reference_price(), (3 * PRICE_PRECISION, 1990));
+        assert_eq!(twamm.proceeds_of(order_id), 2 * 142857);
+    }
+
+    #[ink::test]
+    fn test_cancel_should_refund_unsold_while_settlement_is_deferred(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        set_exchange_reserves(1_000_000, 1_000_000);
+        let mut twamm = Twamm::default();
+        set_caller(accounts.alice);
+        let order_id = twamm.submit_long_term_order(Direction::DotToToken, 1000000, 1).unwrap();
+        set_twamm_balance(1000000);
+
+        set_exchange_reserves(3_000_000, 1_000_000);
+        advance_blocks(5);
+        assert_eq!(twamm.cancel_long_term_order(order_id), Ok((1000000, 0)));
+        assert_eq!(twamm.last_virtual_order_block(), 0);
+        assert_eq!(twamm.sales_rates(), (0, 0));
+        assert_eq!(twamm.order(order_id), None);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_submit_while_settlement_is_deferred_should_panic(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        set_exchange_reserves(1_000_000, 1_000_000);
+        let mut twamm = Twamm::default();
+        set_caller(accounts.alice);
+        let _ = twamm.submit_long_term_order(Direction::DotToToken, 1000000, 1);
+        set_exchange_reserves(3_000_000, 1_000_000);
+        advance_blocks(5);
+        let _ = twamm.submit_long_term_order(Direction::DotToToken, 1000000, 1);
+    }
+
+    #[ink::test]
+    fn test_pending_sales_should_be_refunded_at_expiry(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        //Too few tokens in the Exchange for the sales to buy any.
+        set_exchange_reserves(1_000_000_000_000, 1);
+        let mut twamm = Twamm::default();
+        set_caller(accounts.alice);
+        let order_id = twamm.submit_long_term_order(Direction::DotToToken, 1000000, 1).unwrap();
+        set_twamm_balance(1000000);
+
+        advance_blocks(20);
+        assert_eq!(twamm.withdraw_proceeds(order_id), Ok((0, 1000000)));
+        assert_eq!(twamm.last_virtual_order_block(), 20);
+        assert_eq!(twamm.sales_rates(), (0, 0));
+        assert_eq!(twamm.order(order_id), None);
+    }
+}
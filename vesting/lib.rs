@@ -0,0 +1,388 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::vesting::Vesting;
+
+use ink_lang as ink;
+
+#[ink::contract]
+mod vesting {
+
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::{
+        collections::HashMap as StorageHashMap,
+        traits::{PackedLayout, SpreadLayout},
+    };
+
+    /// Vesting schedule of a beneficiary.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub struct VestingSchedule {
+        //tokens locked for the beneficiary
+        pub total : Balance,
+        //tokens already released to the beneficiary
+        pub released : Balance,
+        //time the linear release is computed from
+        pub start : Timestamp,
+        //time before which no token is vested
+        pub cliff : Timestamp,
+        //time after start when all tokens are vested
+        pub duration : Timestamp,
+        pub revocable : bool,
+        pub revoked : bool,
+    }
+
+    #[ink(event)]
+    pub struct VestingCreated {
+        #[ink(topic)]
+        beneficiary : AccountId,
+        #[ink(topic)]
+        total : Balance,
+    }
+
+    #[ink(event)]
+    pub struct TokensReleased {
+        #[ink(topic)]
+        beneficiary : AccountId,
+        #[ink(topic)]
+        value : Balance,
+    }
+
+    #[ink(event)]
+    pub struct VestingRevoked {
+        #[ink(topic)]
+        beneficiary : AccountId,
+        #[ink(topic)]
+        refund : Balance,
+    }
+
+    pub use common::token::{Error, Result};
+
+    #[ink(storage)]
+    pub struct Vesting {
+        //address of the ERC20 token locked in this contract
+        pub token : AccountId,
+        //the account creating and revoking vesting schedules
+        pub owner : AccountId,
+        schedules : StorageHashMap<AccountId, VestingSchedule>,
+        gas_limit : u64,
+    }
+
+    impl Vesting {
+
+        /// Constructor of the Vesting contract
+        /// 
+        /// NOTE: The deployer becomes the owner of the contract.
+        /// 
+        /// #Params
+        /// 
+        /// - `token_account_id`: AccountId of the Erc20 token locked in this contract
+        #[ink(constructor)]
+        pub fn new(token_account_id : AccountId) -> Self {
+            Self{
+                token : token_account_id,
+                owner : Self::env().caller(),
+                schedules : StorageHashMap::new(),
+                gas_limit : 507085500000,
+            }
+        }
+
+        #[ink(constructor)]
+        pub fn default() -> Self {
+            Self::new(AccountId::default())
+        }
+
+        /// Lock tokens of the owner for a beneficiary. Tokens vest linearly from `start` to `start + duration`,
+        /// nothing can be released before `start + cliff_duration`.
+        /// 
+        /// NOTE: Only the owner can create a vesting schedule, a beneficiary has at most one schedule.
+        /// 
+        /// #Params
+        /// 
+        /// - `beneficiary`: The account the tokens are released to.
+        /// - `total`: Amount of tokens transferred from the owner and locked.
+        /// - `start`: Time the linear release is computed from.
+        /// - `cliff_duration`: Time after start before which no token is vested.
+        /// - `duration`: Time after start when all tokens are vested.
+        /// - `revocable`: Whether the owner can revoke the tokens which are not vested yet.
+        #[ink(message)]
+        pub fn create_vesting(&mut self, beneficiary : AccountId, total : Balance, start : Timestamp,
+            cliff_duration : Timestamp, duration : Timestamp, revocable : bool) -> Result<()>{
+            let caller = self.env().caller();
+            assert!(caller == self.owner);
+            assert!(self.schedules.get(&beneficiary).is_none());
+            assert!(total > 0 && duration > 0 && cliff_duration <= duration);
+
+            self.token_transfer_from(caller, self.env().account_id(), total)?;
+
+            self.schedules.insert(beneficiary, VestingSchedule{
+                total,
+                released : 0,
+                start,
+                cliff : start + cliff_duration,
+                duration,
+                revocable,
+                revoked : false,
+            });
+
+            self.env().emit_event( VestingCreated {
+                beneficiary,
+                total,
+            });
+            Ok(())
+        }
+
+        /// Release the vested tokens of the caller.
+        /// 
+        /// Return the amount of tokens released.
+        #[ink(message)]
+        pub fn release(&mut self) -> Result<Balance>{
+            let beneficiary = self.env().caller();
+            let releasable = self.releasable(beneficiary);
+            assert!(releasable > 0);
+
+            self.token_transfer(beneficiary, releasable)?;
+
+            let mut schedule = self.schedules.get(&beneficiary).unwrap().clone();
+            schedule.released += releasable;
+            self.schedules.insert(beneficiary, schedule);
+
+            self.env().emit_event( TokensReleased {
+                beneficiary,
+                value : releasable,
+            });
+            Ok(releasable)
+        }
+
+        /// Return the amount of vested tokens which are not released yet.
+        /// 
+        /// #Params
+        /// 
+        /// - `beneficiary`: The account the tokens are released to.
+        #[ink(message)]
+        pub fn releasable(&self, beneficiary : AccountId) -> Balance{
+            match self.schedules.get(&beneficiary) {
+                Some(schedule) => Self::vested_amount(schedule, self.env().block_timestamp()) - schedule.released,
+                None => 0,
+            }
+        }
+
+        /// Send the tokens which are not vested yet back to the owner, the vested tokens stay releasable.
+        /// 
+        /// Return the amount of tokens sent back.
+        /// 
+        /// NOTE: Only the owner can revoke, and only revocable schedules.
+        /// 
+        /// #Params
+        /// 
+        /// - `beneficiary`: The account of the revoked schedule.
+        #[ink(message)]
+        pub fn revoke(&mut self, beneficiary : AccountId) -> Result<Balance>{
+            assert!(self.env().caller() == self.owner);
+            let mut schedule = self.schedules.get(&beneficiary).expect("no vesting schedule").clone();
+            assert!(schedule.revocable && !schedule.revoked);
+
+            let vested = Self::vested_amount(&schedule, self.env().block_timestamp());
+            let refund = schedule.total - vested;
+            if refund > 0 {
+                self.token_transfer(self.owner, refund)?;
+            }
+
+            schedule.total = vested;
+            schedule.revoked = true;
+            self.schedules.insert(beneficiary, schedule);
+
+            self.env().emit_event( VestingRevoked {
+                beneficiary,
+                refund,
+            });
+            Ok(refund)
+        }
+
+        /// Return the vesting schedule of a beneficiary.
+        #[ink(message)]
+        pub fn vesting_schedule(&self, beneficiary : AccountId) -> Option<VestingSchedule>{
+            self.schedules.get(&beneficiary).cloned()
+        }
+
+        /// Return the amount of tokens vested at `now`, including the released tokens.
+        pub fn vested_amount(schedule : &VestingSchedule, now : Timestamp) -> Balance{
+            if now < schedule.cliff {
+                return 0
+            }
+            let elapsed = now.saturating_sub(schedule.start);
+            if schedule.revoked || elapsed >= schedule.duration {
+                return schedule.total
+            }
+            //total * elapsed / duration, split so the multiplication can not overflow.
+            let elapsed = elapsed as Balance;
+            let duration = schedule.duration as Balance;
+            schedule.total / duration * elapsed + schedule.total % duration * elapsed / duration
+        }
+
+        #[cfg(not(test))]
+        fn token_transfer(&mut self, to : AccountId, value : Balance)->Result<()>{
+            common::token::transfer(self.token, to, value, self.gas_limit / 2)
+        }
+
+        #[cfg(not(test))]
+        fn token_transfer_from(&mut self, from : AccountId, to : AccountId, value : Balance) ->Result<()>{
+            common::token::transfer_from(self.token, from, to, value, self.gas_limit / 2)
+        }
+
+        /// The off-chain environment can not call contracts, the tests take the token transfers as done.
+        #[cfg(test)]
+        fn token_transfer(&mut self, _to : AccountId, _value : Balance)->Result<()>{
+            Ok(())
+        }
+
+        #[cfg(test)]
+        fn token_transfer_from(&mut self, _from : AccountId, _to : AccountId, _value : Balance) ->Result<()>{
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::vesting::VestingSchedule;
+    use ink_env::{
+        AccountId,
+    };
+    use ink_lang as ink;
+
+    fn set_caller(caller: AccountId) {
+        let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().unwrap_or([0x0; 32].into());
+        let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+        data.push_arg(&caller);
+        ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+            caller,
+            callee,
+            1000000,
+            1000000,
+            data,
+        );
+    }
+
+    fn now() -> u64 {
+        ink_env::block_timestamp::<ink_env::DefaultEnvironment>().unwrap()
+    }
+
+    fn schedule(total : u128, start : u64, cliff : u64, duration : u64) -> VestingSchedule{
+        VestingSchedule{
+            total,
+            released : 0,
+            start,
+            cliff,
+            duration,
+            revocable : true,
+            revoked : false,
+        }
+    }
+
+    #[ink::test]
+    fn test_vested_amount_should_be_linear_after_cliff(){
+        let schedule = schedule(1000, 100, 200, 1000);
+        assert_eq!(Vesting::vested_amount(&schedule, 0), 0);
+        assert_eq!(Vesting::vested_amount(&schedule, 199), 0);
+        assert_eq!(Vesting::vested_amount(&schedule, 200), 100);
+        assert_eq!(Vesting::vested_amount(&schedule, 600), 500);
+        assert_eq!(Vesting::vested_amount(&schedule, 1100), 1000);
+        assert_eq!(Vesting::vested_amount(&schedule, 5000), 1000);
+    }
+
+    #[ink::test]
+    fn test_vested_amount_should_not_overflow(){
+        let schedule = schedule(u128::MAX / 2, 0, 0, 4 * 365 * 24 * 3600 * 1000);
+        assert_eq!(Vesting::vested_amount(&schedule, 2 * 365 * 24 * 3600 * 1000), u128::MAX / 4);
+    }
+
+    #[ink::test]
+    fn test_revoked_schedule_should_be_fully_vested(){
+        let mut schedule = schedule(1000, 100, 200, 1000);
+        schedule.total = 500;
+        schedule.revoked = true;
+        assert_eq!(Vesting::vested_amount(&schedule, 0), 0);
+        assert_eq!(Vesting::vested_amount(&schedule, 300), 500);
+    }
+
+    #[ink::test]
+    fn test_create_vesting_should_lock_tokens_for_beneficiary(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut vesting = Vesting::new(AccountId::from([0x01; 32]));
+        assert_eq!(vesting.create_vesting(accounts.bob, 1000, 100, 200, 1000, true), Ok(()));
+        let mut expected = schedule(1000, 100, 300, 1000);
+        assert_eq!(vesting.vesting_schedule(accounts.bob), Some(expected.clone()));
+        assert_eq!(vesting.vesting_schedule(accounts.charlie), None);
+
+        assert_eq!(vesting.create_vesting(accounts.charlie, 500, 100, 0, 1000, false), Ok(()));
+        expected = schedule(500, 100, 100, 1000);
+        expected.revocable = false;
+        assert_eq!(vesting.vesting_schedule(accounts.charlie), Some(expected));
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_create_vesting_by_other_account_should_fail(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut vesting = Vesting::new(AccountId::from([0x01; 32]));
+        set_caller(accounts.bob);
+        let _ = vesting.create_vesting(accounts.bob, 1000, 100, 200, 1000, true);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_create_vesting_twice_for_beneficiary_should_fail(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut vesting = Vesting::new(AccountId::from([0x01; 32]));
+        assert_eq!(vesting.create_vesting(accounts.bob, 1000, 100, 200, 1000, true), Ok(()));
+        let _ = vesting.create_vesting(accounts.bob, 500, 100, 200, 1000, true);
+    }
+
+    #[ink::test]
+    fn test_release_after_cliff_should_pay_vested_tokens(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut vesting = Vesting::new(AccountId::from([0x01; 32]));
+        assert_eq!(vesting.create_vesting(accounts.bob, 1000, now(), 1, 1, true), Ok(()));
+        assert_eq!(vesting.releasable(accounts.bob), 0);
+
+        ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("cannot advance block");
+        assert_eq!(vesting.releasable(accounts.bob), 1000);
+        set_caller(accounts.bob);
+        assert_eq!(vesting.release(), Ok(1000));
+        assert_eq!(vesting.releasable(accounts.bob), 0);
+        assert_eq!(vesting.vesting_schedule(accounts.bob).unwrap().released, 1000);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_release_before_cliff_should_fail(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut vesting = Vesting::new(AccountId::from([0x01; 32]));
+        assert_eq!(vesting.create_vesting(accounts.bob, 1000, now(), 1, 1, true), Ok(()));
+        set_caller(accounts.bob);
+        let _ = vesting.release();
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_revoke_non_revocable_schedule_should_fail(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut vesting = Vesting::new(AccountId::from([0x01; 32]));
+        assert_eq!(vesting.create_vesting(accounts.bob, 1000, now(), 1, 1, false), Ok(()));
+        let _ = vesting.revoke(accounts.bob);
+    }
+
+    #[ink::test]
+    #[should_panic]
+    fn test_revoke_twice_should_fail(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut vesting = Vesting::new(AccountId::from([0x01; 32]));
+        assert_eq!(vesting.create_vesting(accounts.bob, 1000, now(), 1, 1, true), Ok(()));
+        //Nothing is vested before the cliff, so everything goes back to the owner.
+        assert_eq!(vesting.revoke(accounts.bob), Ok(1000));
+        let schedule = vesting.vesting_schedule(accounts.bob).unwrap();
+        assert_eq!((schedule.total, schedule.revoked), (0, true));
+        let _ = vesting.revoke(accounts.bob);
+    }
+}
@@ -0,0 +1,749 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::weighted_pool::WeightedPool;
+
+use ink_lang as ink;
+
+#[ink::contract]
+mod weighted_pool {
+
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::{
+        collections::HashMap as StorageHashMap,
+    };
+
+    use ink_prelude::{
+        vec,
+        vec::Vec,
+    };
+
+    use primitive_types::U256;
+
+    /// Fixed point one, weights, fees and ratios are multiplied by it.
+    pub const BONE : u128 = 1_000_000_000_000_000_000;
+    /// Minimum normalized weight of a token, 1%.
+    pub const MIN_WEIGHT : u128 = BONE / 100;
+    /// Maximum swap fee, 10%.
+    pub const MAX_FEE : u128 = BONE / 10;
+    /// Maximum number of tokens in a pool.
+    pub const MAX_TOKENS : usize = 8;
+    /// Lp tokens minted by `initialize`.
+    pub const INIT_POOL_SUPPLY : Balance = 100 * BONE;
+    /// A swap can add at most this share of the input reserve.
+    pub const MAX_IN_RATIO : u128 = BONE / 2;
+    /// A swap can remove at most this share of the output reserve.
+    pub const MAX_OUT_RATIO : u128 = BONE / 3 + 1;
+    /// Smallest and largest base of `bpow`.
+    pub const MIN_BPOW_BASE : u128 = 1;
+    pub const MAX_BPOW_BASE : u128 = 2 * BONE - 1;
+    /// Precision of the series approximating the fractional part of `bpow`.
+    pub const BPOW_PRECISION : u128 = BONE / 10_000_000_000;
+    /// Maximum number of terms of the series approximating the fractional part of `bpow`.
+    pub const MAX_BPOW_ITERATIONS : u128 = 100;
+
+    #[ink(event)]
+    pub struct Swap {
+        #[ink(topic)]
+        buyer : AccountId,
+        token_in : u32,
+        token_out : u32,
+        #[ink(topic)]
+        amount_in : Balance,
+        #[ink(topic)]
+        amount_out : Balance,
+    }
+
+    #[ink(event)]
+    pub struct Join {
+        #[ink(topic)]
+        provider : AccountId,
+        token_amounts : Vec<Balance>,
+        #[ink(topic)]
+        liquidity_minted : Balance,
+    }
+
+    #[ink(event)]
+    pub struct Exit {
+        #[ink(topic)]
+        provider : AccountId,
+        token_amounts : Vec<Balance>,
+        #[ink(topic)]
+        liquidity_burned : Balance,
+    }
+
+    #[ink(event)]
+    pub struct Transfer{
+        #[ink(topic)]
+        from   : AccountId,
+        #[ink(topic)]
+        to     : AccountId,
+        #[ink(topic)]
+        value  : Balance,
+    }
+
+    #[ink(event)]
+    pub struct Approval{
+        #[ink(topic)]
+        owner : AccountId,
+        #[ink(topic)]
+        spender:AccountId,
+        #[ink(topic)]
+        value : Balance,
+    }
+
+    pub use common::token::{Error, Result};
+
+    /// A Balancer-style pool of Erc20 tokens with custom weights, e.g. an 80/20 pool. The value of each token in the
+    /// pool stays its weight of the pool value.
+    #[ink(storage)]
+    pub struct WeightedPool {
+        //total liquidity
+        pub total_supply : Balance,
+
+        pub balances : StorageHashMap<AccountId, Balance>,
+        allowances: StorageHashMap<(AccountId, AccountId), Balance>,
+        //addresses of the ERC20 tokens traded in this pool
+        tokens : Vec<AccountId>,
+        //normalized weight of each token, they sum to BONE
+        weights : Vec<u128>,
+        //amount of each token held by the pool, including the fees
+        reserves : Vec<Balance>,
+        //swap fee, multiplied by BONE
+        swap_fee : u128,
+        gas_limit :u64,
+        pool_account_id: AccountId,
+    }
+
+    impl WeightedPool {
+
+        /// Constructor of the WeightedPool contract
+        ///
+        /// NOTE: The pool is usually instantiated by the Factory. It can be traded once `initialize` deposited every token.
+        ///
+        /// #Params
+        ///
+        /// - `tokens`: AccountIds of the Erc20 tokens traded in this pool, 2 to MAX_TOKENS distinct tokens.
+        /// - `weights`: Normalized weight of each token multiplied by BONE, at least MIN_WEIGHT each and BONE in total.
+        /// - `swap_fee`: Swap fee multiplied by BONE.
+        #[ink(constructor)]
+        pub fn new(tokens : Vec<AccountId>, weights : Vec<u128>, swap_fee : u128) -> Self {
+            assert!(tokens.len() >= 2 && tokens.len() <= MAX_TOKENS && tokens.len() == weights.len());
+            for (i, token) in tokens.iter().enumerate() {
+                assert!(*token != AccountId::default() && !tokens[i + 1..].contains(token));
+            }
+            assert!(weights.iter().all(|weight| *weight >= MIN_WEIGHT));
+            assert!(weights.iter().sum::<u128>() == BONE);
+            assert!(swap_fee <= MAX_FEE);
+            let reserves = vec![0; tokens.len()];
+            Self{
+                total_supply : 0,
+                balances : StorageHashMap::new(),
+                allowances : StorageHashMap::new(),
+                tokens,
+                weights,
+                reserves,
+                swap_fee,
+                gas_limit : 507085500000,
+                pool_account_id : Self::env().account_id(),
+            }
+        }
+
+        #[ink(constructor)]
+        pub fn default() -> Self {
+            Self::new(vec![AccountId::from([0x01; 32]), AccountId::from([0x02; 32])], vec![BONE / 2, BONE / 2], BONE / 1000 * 3)
+        }
+
+        /// Return the tokens traded in this pool.
+        #[ink(message)]
+        pub fn tokens(&self) -> Vec<AccountId>{
+            self.tokens.clone()
+        }
+
+        /// Return the normalized weight of each token, multiplied by BONE.
+        #[ink(message)]
+        pub fn weights(&self) -> Vec<u128>{
+            self.weights.clone()
+        }
+
+        /// Return the amount of each token held by the pool.
+        #[ink(message)]
+        pub fn reserves(&self) -> Vec<Balance>{
+            self.reserves.clone()
+        }
+
+        /// Return the swap fee, multiplied by BONE.
+        #[ink(message)]
+        pub fn swap_fee(&self) -> u128{
+            self.swap_fee
+        }
+
+        /// Return the price of token `token_out` in token `token_in` including the swap fee, multiplied by BONE.
+        #[ink(message)]
+        pub fn spot_price(&self, token_in : u32, token_out : u32) -> u128{
+            let (i, o) = (token_in as usize, token_out as usize);
+            Self::calc_spot_price(self.reserves[i], self.weights[i], self.reserves[o], self.weights[o], self.swap_fee)
+        }
+
+        /// Deposit the first liquidity of the pool, which mints INIT_POOL_SUPPLY lp tokens.
+        ///
+        /// NOTE: The deposited amounts set the initial prices, the value of each amount should be its token weight of the total.
+        ///
+        /// #Params
+        ///
+        /// - `amounts_in`: Amount of each token deposited.
+        #[ink(message)]
+        pub fn initialize(&mut self, amounts_in : Vec<Balance>) -> Result<Balance>{
+            assert!(self.total_supply == 0);
+            assert!(amounts_in.len() == self.tokens.len() && amounts_in.iter().all(|amount| *amount > 0));
+            let caller = self.env().caller();
+
+            self.pull_tokens(caller, &amounts_in)?;
+
+            self.reserves = amounts_in.clone();
+            self.mint_liquidity(caller, INIT_POOL_SUPPLY);
+
+            self.env().emit_event( Join {
+                provider : caller,
+                token_amounts : amounts_in,
+                liquidity_minted : INIT_POOL_SUPPLY,
+            });
+            Ok(INIT_POOL_SUPPLY)
+        }
+
+        /// Deposit every token at current ratio to mint an exact amount of lp tokens.
+        ///
+        /// Return The amount of each token deposited.
+        ///
+        /// #Params
+        ///
+        /// - `pool_amount_out`: Amount of lp minted.
+        /// - `max_amounts_in`: Maximum amount of each token deposited.
+        ///
+        /// NOTE: If a token transfer fails the tokens already transferred are refunded and `TokenTransferFailed` is returned.
+        #[ink(message)]
+        pub fn join_pool(&mut self, pool_amount_out : Balance, max_amounts_in : Vec<Balance>) -> Result<Vec<Balance>>{
+            assert!(self.total_supply > 0 && pool_amount_out > 0 && max_amounts_in.len() == self.tokens.len());
+            let caller = self.env().caller();
+            let ratio = Self::bdiv(pool_amount_out, self.total_supply);
+            let mut amounts_in = Vec::new();
+            for i in 0..self.tokens.len() {
+                //Rounded up in favour of the pool.
+                let amount_in = Self::bmul(ratio, self.reserves[i]) + 1;
+                assert!(amount_in <= max_amounts_in[i]);
+                amounts_in.push(amount_in);
+            }
+
+            self.pull_tokens(caller, &amounts_in)?;
+
+            for i in 0..self.tokens.len() {
+                self.reserves[i] += amounts_in[i];
+            }
+            self.mint_liquidity(caller, pool_amount_out);
+
+            self.env().emit_event( Join {
+                provider : caller,
+                token_amounts : amounts_in.clone(),
+                liquidity_minted : pool_amount_out,
+            });
+            Ok(amounts_in)
+        }
+
+        /// Burn lp tokens to withdraw every token at current ratio.
+        ///
+        /// Return The amount of each token withdrawn.
+        ///
+        /// #Params
+        ///
+        /// - `pool_amount_in`: Amount of lp burned.
+        /// - `min_amounts_out`: Minimum amount of each token withdrawn.
+        ///
+        /// NOTE: If a token transfer fails the whole call is reverted.
+        #[ink(message)]
+        pub fn exit_pool(&mut self, pool_amount_in : Balance, min_amounts_out : Vec<Balance>) -> Vec<Balance>{
+            assert!(pool_amount_in > 0 && min_amounts_out.len() == self.tokens.len());
+            let caller = self.env().caller();
+            assert!(self.balance_of(caller) >= pool_amount_in);
+            let ratio = Self::bdiv(pool_amount_in, self.total_supply);
+            let mut amounts_out = Vec::new();
+            for i in 0..self.tokens.len() {
+                let amount_out = Self::bmul(ratio, self.reserves[i]).min(self.reserves[i]);
+                assert!(amount_out >= min_amounts_out[i]);
+                self.reserves[i] -= amount_out;
+                amounts_out.push(amount_out);
+            }
+            self.burn_liquidity(caller, pool_amount_in);
+
+            for i in 0..self.tokens.len() {
+                self.token_transfer(self.tokens[i], caller, amounts_out[i]).expect("token transfer failed");
+            }
+
+            self.env().emit_event( Exit {
+                provider : caller,
+                token_amounts : amounts_out.clone(),
+                liquidity_burned : pool_amount_in,
+            });
+            amounts_out
+        }
+
+        /// Deposit a single token to mint lp tokens. The part of the deposit which is swapped into the other tokens pays the swap fee.
+        ///
+        /// Return The amount of lp minted.
+        ///
+        /// #Params
+        ///
+        /// - `token_in`: Index of the token deposited.
+        /// - `token_amount_in`: Amount of the token deposited.
+        /// - `min_pool_amount_out`: Minimum amount of lp minted.
+        #[ink(message)]
+        pub fn join_swap_extern_amount_in(&mut self, token_in : u32, token_amount_in : Balance, min_pool_amount_out : Balance) -> Result<Balance>{
+            let i = token_in as usize;
+            assert!(self.total_supply > 0 && token_amount_in > 0);
+            assert!(token_amount_in <= Self::bmul(self.reserves[i], MAX_IN_RATIO));
+            let caller = self.env().caller();
+            let pool_amount_out = Self::calc_pool_out_given_single_in(self.reserves[i], self.weights[i], self.total_supply, token_amount_in, self.swap_fee);
+            assert!(pool_amount_out >= min_pool_amount_out);
+
+            self.token_transfer_from(self.tokens[i], caller, self.pool_account_id, token_amount_in)?;
+
+            self.reserves[i] += token_amount_in;
+            self.mint_liquidity(caller, pool_amount_out);
+
+            let mut token_amounts = vec![0; self.tokens.len()];
+            token_amounts[i] = token_amount_in;
+            self.env().emit_event( Join {
+                provider : caller,
+                token_amounts,
+                liquidity_minted : pool_amount_out,
+            });
+            Ok(pool_amount_out)
+        }
+
+        /// Burn lp tokens to withdraw a single token. The part of the withdrawal which is swapped from the other tokens pays the swap fee.
+        ///
+        /// Return The amount of the token withdrawn.
+        ///
+        /// #Params
+        ///
+        /// - `token_out`: Index of the token withdrawn.
+        /// - `pool_amount_in`: Amount of lp burned.
+        /// - `min_amount_out`: Minimum amount of the token withdrawn.
+        #[ink(message)]
+        pub fn exit_swap_pool_amount_in(&mut self, token_out : u32, pool_amount_in : Balance, min_amount_out : Balance) -> Result<Balance>{
+            let o = token_out as usize;
+            assert!(pool_amount_in > 0 && pool_amount_in < self.total_supply);
+            let caller = self.env().caller();
+            assert!(self.balance_of(caller) >= pool_amount_in);
+            let token_amount_out = Self::calc_single_out_given_pool_in(self.reserves[o], self.weights[o], self.total_supply, pool_amount_in, self.swap_fee);
+            assert!(token_amount_out >= min_amount_out);
+            assert!(token_amount_out <= Self::bmul(self.reserves[o], MAX_OUT_RATIO));
+
+            self.token_transfer(self.tokens[o], caller, token_amount_out)?;
+
+            self.reserves[o] -= token_amount_out;
+            self.burn_liquidity(caller, pool_amount_in);
+
+            let mut token_amounts = vec![0; self.tokens.len()];
+            token_amounts[o] = token_amount_out;
+            self.env().emit_event( Exit {
+                provider : caller,
+                token_amounts,
+                liquidity_burned : pool_amount_in,
+            });
+            Ok(token_amount_out)
+        }
+
+        /// Swap an exact amount of token `token_in` for token `token_out`.
+        ///
+        /// Return The amount of token `token_out` bought.
+        ///
+        /// #Params
+        ///
+        /// - `token_in`: Index of the token sold.
+        /// - `token_amount_in`: Amount of the token sold.
+        /// - `token_out`: Index of the token bought.
+        /// - `min_amount_out`: Minimum amount of the token bought.
+        #[ink(message)]
+        pub fn swap_exact_in(&mut self, token_in : u32, token_amount_in : Balance, token_out : u32, min_amount_out : Balance) -> Result<Balance>{
+            let (i, o) = (token_in as usize, token_out as usize);
+            assert!(i != o && token_amount_in > 0);
+            assert!(token_amount_in <= Self::bmul(self.reserves[i], MAX_IN_RATIO));
+            let caller = self.env().caller();
+            let token_amount_out = Self::calc_out_given_in(self.reserves[i], self.weights[i], self.reserves[o], self.weights[o],
+                token_amount_in, self.swap_fee);
+            assert!(token_amount_out >= min_amount_out);
+
+            self.swap(caller, i, token_amount_in, o, token_amount_out)?;
+            Ok(token_amount_out)
+        }
+
+        /// Swap token `token_in` for an exact amount of token `token_out`.
+        ///
+        /// Return The amount of token `token_in` sold.
+        ///
+        /// #Params
+        ///
+        /// - `token_in`: Index of the token sold.
+        /// - `max_amount_in`: Maximum amount of the token sold.
+        /// - `token_out`: Index of the token bought.
+        /// - `token_amount_out`: Amount of the token bought.
+        #[ink(message)]
+        pub fn swap_exact_out(&mut self, token_in : u32, max_amount_in : Balance, token_out : u32, token_amount_out : Balance) -> Result<Balance>{
+            let (i, o) = (token_in as usize, token_out as usize);
+            assert!(i != o && token_amount_out > 0);
+            assert!(token_amount_out <= Self::bmul(self.reserves[o], MAX_OUT_RATIO));
+            let caller = self.env().caller();
+            let token_amount_in = Self::calc_in_given_out(self.reserves[i], self.weights[i], self.reserves[o], self.weights[o],
+                token_amount_out, self.swap_fee);
+            assert!(token_amount_in <= max_amount_in);
+
+            self.swap(caller, i, token_amount_in, o, token_amount_out)?;
+            Ok(token_amount_in)
+        }
+
+        fn swap(&mut self, buyer : AccountId, i : usize, amount_in : Balance, o : usize, amount_out : Balance) -> Result<()>{
+            self.token_transfer_from(self.tokens[i], buyer, self.pool_account_id, amount_in)?;
+            self.token_transfer(self.tokens[o], buyer, amount_out).expect("token transfer failed");
+
+            self.reserves[i] += amount_in;
+            self.reserves[o] -= amount_out;
+
+            self.env().emit_event( Swap {
+                buyer,
+                token_in : i as u32,
+                token_out : o as u32,
+                amount_in,
+                amount_out,
+            });
+            Ok(())
+        }
+
+        fn pull_tokens(&mut self, from : AccountId, amounts : &[Balance]) -> Result<()>{
+            for i in 0..self.tokens.len() {
+                if let Err(error) = self.token_transfer_from(self.tokens[i], from, self.pool_account_id, amounts[i]) {
+                    for j in 0..i {
+                        self.token_transfer(self.tokens[j], from, amounts[j]).expect("token transfer failed");
+                    }
+                    return Err(error)
+                }
+            }
+            Ok(())
+        }
+
+        /// Return `a * b` of two fixed point numbers, rounded to nearest.
+        pub fn bmul(a : u128, b : u128) -> u128{
+            ((U256::from(a) * U256::from(b) + U256::from(BONE / 2)) / U256::from(BONE)).as_u128()
+        }
+
+        /// Return `a / b` of two fixed point numbers, rounded to nearest.
+        pub fn bdiv(a : u128, b : u128) -> u128{
+            assert!(b > 0);
+            ((U256::from(a) * U256::from(BONE) + U256::from(b / 2)) / U256::from(b)).as_u128()
+        }
+
+        /// Return `a ^ n` of a fixed point number and an integer.
+        fn bpowi(a : u128, n : u128) -> u128{
+            let mut a = a;
+            let mut n = n;
+            let mut z = if n % 2 != 0 { a } else { BONE };
+            n /= 2;
+            while n != 0 {
+                a = Self::bmul(a, a);
+                if n % 2 != 0 {
+                    z = Self::bmul(z, a);
+                }
+                n /= 2;
+            }
+            z
+        }
+
+        /// Return `base ^ exp` of two fixed point numbers. The integer part of `exp` is computed exactly and the
+        /// fractional part with a binomial series.
+        pub fn bpow(base : u128, exp : u128) -> u128{
+            assert!(base >= MIN_BPOW_BASE && base <= MAX_BPOW_BASE);
+            let whole = exp / BONE * BONE;
+            let remain = exp - whole;
+            let whole_pow = Self::bpowi(base, whole / BONE);
+            if remain == 0 {
+                return whole_pow
+            }
+            Self::bmul(whole_pow, Self::bpow_approx(base, remain))
+        }
+
+        fn bpow_approx(base : u128, exp : u128) -> u128{
+            let (x, x_negative) = if base >= BONE { (base - BONE, false) } else { (BONE - base, true) };
+            let mut term = BONE;
+            let mut sum = term;
+            let mut negative = false;
+            let mut i = 1;
+            //Each term is `term * (exp - (i - 1)) * x / i`, the sum converges as `x < 1`.
+            while term >= BPOW_PRECISION && i <= MAX_BPOW_ITERATIONS {
+                let big_k = i * BONE;
+                let (c, c_negative) = if exp >= big_k - BONE { (exp - (big_k - BONE), false) } else { ((big_k - BONE) - exp, true) };
+                term = Self::bdiv(Self::bmul(term, Self::bmul(c, x)), big_k);
+                if term == 0 {
+                    break
+                }
+                if x_negative {
+                    negative = !negative;
+                }
+                if c_negative {
+                    negative = !negative;
+                }
+                if negative {
+                    sum -= term;
+                }else{
+                    sum += term;
+                }
+                i += 1;
+            }
+            sum
+        }
+
+        /// Return the price of the output token in the input token including the swap fee, multiplied by BONE.
+        pub fn calc_spot_price(balance_in : Balance, weight_in : u128, balance_out : Balance, weight_out : u128, swap_fee : u128) -> u128{
+            let numer = Self::bdiv(balance_in, weight_in);
+            let denom = Self::bdiv(balance_out, weight_out);
+            let ratio = Self::bdiv(numer, denom);
+            let scale = Self::bdiv(BONE, BONE - swap_fee);
+            Self::bmul(ratio, scale)
+        }
+
+        /// Return the output bought with `amount_in`:
+        /// `balance_out * (1 - (balance_in / (balance_in + amount_in * (1 - fee))) ^ (weight_in / weight_out))`
+        pub fn calc_out_given_in(balance_in : Balance, weight_in : u128, balance_out : Balance, weight_out : u128,
+            amount_in : Balance, swap_fee : u128) -> Balance{
+            let weight_ratio = Self::bdiv(weight_in, weight_out);
+            let adjusted_in = Self::bmul(amount_in, BONE - swap_fee);
+            let y = Self::bdiv(balance_in, balance_in + adjusted_in);
+            let foo = Self::bpow(y, weight_ratio);
+            Self::bmul(balance_out, BONE - foo)
+        }
+
+        /// Return the input needed to buy `amount_out`:
+        /// `balance_in * ((balance_out / (balance_out - amount_out)) ^ (weight_out / weight_in) - 1) / (1 - fee)`
+        pub fn calc_in_given_out(balance_in : Balance, weight_in : u128, balance_out : Balance, weight_out : u128,
+            amount_out : Balance, swap_fee : u128) -> Balance{
+            let weight_ratio = Self::bdiv(weight_out, weight_in);
+            let y = Self::bdiv(balance_out, balance_out - amount_out);
+            let foo = Self::bpow(y, weight_ratio) - BONE;
+            Self::bdiv(Self::bmul(balance_in, foo), BONE - swap_fee)
+        }
+
+        /// Return the lp minted for a single token deposit. The share of the deposit swapped into the other tokens,
+        /// `1 - weight`, pays the swap fee.
+        pub fn calc_pool_out_given_single_in(balance_in : Balance, weight_in : u128, pool_supply : Balance, amount_in : Balance,
+            swap_fee : u128) -> Balance{
+            let zaz = Self::bmul(BONE - weight_in, swap_fee);
+            let amount_in_after_fee = Self::bmul(amount_in, BONE - zaz);
+            let token_in_ratio = Self::bdiv(balance_in + amount_in_after_fee, balance_in);
+            let pool_ratio = Self::bpow(token_in_ratio, weight_in);
+            Self::bmul(pool_ratio, pool_supply) - pool_supply
+        }
+
+        /// Return the token withdrawn for burning `pool_amount_in` lp. The share of the withdrawal swapped from the
+        /// other tokens, `1 - weight`, pays the swap fee.
+        pub fn calc_single_out_given_pool_in(balance_out : Balance, weight_out : u128, pool_supply : Balance, pool_amount_in : Balance,
+            swap_fee : u128) -> Balance{
+            let pool_ratio = Self::bdiv(pool_supply - pool_amount_in, pool_supply);
+            let token_out_ratio = Self::bpow(pool_ratio, Self::bdiv(BONE, weight_out));
+            let amount_out_before_fee = balance_out - Self::bmul(token_out_ratio, balance_out);
+            let zaz = Self::bmul(BONE - weight_out, swap_fee);
+            Self::bmul(amount_out_before_fee, BONE - zaz)
+        }
+
+        fn mint_liquidity(&mut self, to : AccountId, value : Balance){
+            let to_liquidity = self.balance_of(to);
+            self.balances.insert(to, to_liquidity + value);
+            self.total_supply += value;
+
+            self.env().emit_event( Transfer {
+                from : AccountId::default(),
+                to,
+                value,
+            });
+        }
+
+        fn burn_liquidity(&mut self, from : AccountId, value : Balance){
+            let from_liquidity = self.balance_of(from);
+            assert!(from_liquidity >= value);
+            self.balances.insert(from, from_liquidity - value);
+            self.total_supply -= value;
+
+            self.env().emit_event( Transfer {
+                from,
+                to : AccountId::default(),
+                value,
+            });
+        }
+
+        /// Return the total liqudity in this pool.
+        #[ink(message)]
+        pub fn total_supply(&self) ->Balance{
+            self.total_supply
+        }
+
+        /// Return the liquidity of the owner
+        #[ink(message)]
+        pub fn balance_of(&self, owner : AccountId) -> Balance{
+            self.balances.get(&owner).unwrap_or(&0u128).clone()
+        }
+
+        fn transfer_from_to(&mut self, from: AccountId, to: AccountId, value: Balance) -> bool {
+            let from_balance = self.balance_of(from);
+            if from_balance < value {
+                return false
+            }
+            self.balances.insert(from, from_balance - value);
+            let to_balance = self.balance_of(to);
+            self.balances.insert(to, to_balance + value);
+
+            self.env().emit_event(Transfer {
+                from,
+                to,
+                value,
+            });
+
+            true
+        }
+
+        /// The Contract caller transfer some liquidity to another account
+        #[ink(message, selector = "0xfae3a09d")]
+        pub fn transfer(&mut self, to : AccountId, value : Balance) -> bool{
+            let caller = self.env().caller();
+            self.transfer_from_to(caller, to, value)
+        }
+
+        /// The Contract caller transfer some liquidity from an account to another account
+        ///
+        /// NOTE: The allowance is only spent if the transfer succeeds.
+        #[ink(message, selector = "0xfcfb2ccd")]
+        pub fn transfer_from(&mut self, from : AccountId, to : AccountId, value : Balance) -> bool{
+            let caller = self.env().caller();
+            let allowance = self.allowance(from, caller);
+            if allowance < value {
+                 return false
+            }
+            if !self.transfer_from_to(from, to, value) {
+                return false
+            }
+            self.allowances.insert((from, caller), allowance - value);
+
+            true
+        }
+
+        /// Approve spender can transfer liquidity from the caller account
+        #[ink(message)]
+        pub fn approve(&mut self, spender: AccountId, value: Balance) -> bool {
+            let owner = self.env().caller();
+            self.allowances.insert((owner, spender), value);
+            self.env().emit_event(Approval {
+                owner,
+                spender,
+                value,
+            });
+            true
+        }
+
+        /// Return the liquidity spender can transfer from the owner account
+        #[ink(message)]
+        pub fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+            self.allowances.get(&(owner, spender)).unwrap_or(&0u128).clone()
+        }
+
+        fn token_transfer(&mut self, token : AccountId, to : AccountId, value : Balance)->Result<()>{
+            common::token::transfer(token, to, value, self.gas_limit / 2)
+        }
+
+        fn token_transfer_from(&mut self, token : AccountId, from : AccountId, to : AccountId, value : Balance) ->Result<()>{
+            common::token::transfer_from(token, from, to, value, self.gas_limit / 2)
+        }
+
+        ///Return the WeightedPool self account id.
+        #[ink(message)]
+        pub fn get_address(&self) ->AccountId{
+            self.pool_account_id
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::weighted_pool::BONE;
+    use ink_env::{
+        AccountId,
+    };
+    use ink_lang as ink;
+
+    fn set_caller(caller: AccountId) {
+        let callee = ink_env::account_id::<ink_env::DefaultEnvironment>().unwrap_or([0x0; 32].into());
+        let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+        data.push_arg(&caller);
+        ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+            caller,
+            callee,
+            1000000,
+            1000000,
+            data,
+        );
+    }
+
+    fn assert_close(value : u128, expected : u128, tolerance : u128){
+        let difference = if value > expected { value - expected } else { expected - value };
+        assert!(difference <= tolerance, "{} is not close to {}", value, expected);
+    }
+
+    #[ink::test]
+    fn test_bpow_should_approximate_fractional_powers(){
+        //0.5 ^ 1.5 = 0.35355339059...
+        assert_close(WeightedPool::bpow(BONE / 2, 3 * BONE / 2), 353_553_390_593_273_762, 100_000_000);
+        //1.5 ^ 0.25 = 1.10668191970...
+        assert_close(WeightedPool::bpow(3 * BONE / 2, BONE / 4), 1_106_681_919_700_321_500, 100_000_000);
+        assert_eq!(WeightedPool::bpow(BONE / 2, 2 * BONE), BONE / 4);
+    }
+
+    #[ink::test]
+    fn test_equal_weights_should_match_constant_product(){
+        let out = WeightedPool::calc_out_given_in(BONE, BONE / 2, BONE, BONE / 2, BONE / 100, 0);
+        //1 * 0.01 / 1.01
+        assert_close(out, 9_900_990_099_009_900, 10);
+    }
+
+    #[ink::test]
+    fn test_swap_exact_out_should_invert_swap_exact_in(){
+        let fee = BONE / 1000 * 3;
+        let out = WeightedPool::calc_out_given_in(BONE, 8 * BONE / 10, BONE, 2 * BONE / 10, BONE / 100, fee);
+        //An 80/20 pool prices the light token higher than a 50/50 pool.
+        assert!(out > 38_000_000_000_000_000 && out < 39_000_000_000_000_000);
+        let amount_in = WeightedPool::calc_in_given_out(BONE, 8 * BONE / 10, BONE, 2 * BONE / 10, out, fee);
+        assert_close(amount_in, BONE / 100, 10_000_000);
+    }
+
+    #[ink::test]
+    fn test_single_asset_join_and_exit_should_pay_fee(){
+        let fee = BONE / 1000 * 3;
+        let pool_supply = 100 * BONE;
+        let pool_out = WeightedPool::calc_pool_out_given_single_in(BONE, 8 * BONE / 10, pool_supply, BONE / 100, fee);
+        //Without fee the deposit would mint 100 * (1.01 ^ 0.8 - 1) = 0.7992...
+        assert!(pool_out > 798_000_000_000_000_000 && pool_out < 799_300_000_000_000_000);
+
+        let amount_out = WeightedPool::calc_single_out_given_pool_in(BONE + BONE / 100, 8 * BONE / 10, pool_supply + pool_out, pool_out, fee);
+        assert!(amount_out < BONE / 100 && amount_out > 9_980_000_000_000_000);
+    }
+
+    #[ink::test]
+    fn test_failed_transfer_from_should_keep_allowance(){
+        let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+        let mut contract = WeightedPool::default();
+        contract.balances.insert(accounts.alice, 100);
+        set_caller(accounts.alice);
+        assert!(contract.approve(accounts.bob, 300));
+
+        set_caller(accounts.bob);
+        assert!(!contract.transfer_from(accounts.alice, accounts.bob, 200));
+        assert_eq!(contract.allowance(accounts.alice, accounts.bob), 300);
+        assert!(contract.transfer_from(accounts.alice, accounts.bob, 100));
+        assert_eq!(contract.allowance(accounts.alice, accounts.bob), 200);
+        assert_eq!(contract.balance_of(accounts.bob), 100);
+    }
+}
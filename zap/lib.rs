@@ -0,0 +1,421 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use self::zap::{Zap, ZapAsset};
+
+use ink_lang as ink;
+
+#[ink::contract]
+mod zap {
+
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_env::{
+         call,
+         DefaultEnvironment,
+         call::{ build_call, ExecutionInput, utils::ReturnType,}
+    };
+
+    use exchange::TokenReceivedAction;
+
+    use ink_prelude::vec::Vec;
+
+    use primitive_types::U256;
+
+    /// The Exchange swap fees are in basis points of the input.
+    pub const FEE_DENOMINATOR : u128 = 10000;
+
+    /// The single asset of a zap.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum ZapAsset {
+        Dot,
+        /// The Exchange token.
+        Token,
+    }
+
+    #[ink(event)]
+    pub struct ZapIn {
+        #[ink(topic)]
+        provider : AccountId,
+        #[ink(topic)]
+        exchange : AccountId,
+        asset : ZapAsset,
+        amount_in : Balance,
+        liquidity : Balance,
+    }
+
+    #[ink(event)]
+    pub struct ZapOut {
+        #[ink(topic)]
+        provider : AccountId,
+        #[ink(topic)]
+        exchange : AccountId,
+        asset : ZapAsset,
+        liquidity : Balance,
+        amount_out : Balance,
+    }
+
+    pub use common::token::{Error, Result};
+
+    /// Single-sided liquidity for the Exchange. `zap_in` swaps the optimal share of a Dot or token deposit so the rest
+    /// matches the reserves ratio, then adds liquidity, in one transaction. `zap_out` removes liquidity and swaps one
+    /// side, so a single asset is returned.
+    ///
+    /// NOTE: The Zap holds nothing between calls. It does not support liquidity living in a MultiToken contract.
+    #[ink(storage)]
+    pub struct Zap {
+        gas_limit :u64,
+        zap_account_id : AccountId,
+    }
+
+    impl Zap {
+
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self{
+                gas_limit : 507085500000,
+                zap_account_id : Self::env().account_id(),
+            }
+        }
+
+        #[ink(constructor)]
+        pub fn default() -> Self {
+            Self::new()
+        }
+
+        /// Add liquidity to an Exchange from the transferred Dot only, or from tokens only. The optimal share is swapped
+        /// through the Exchange, the rest is deposited with the swap output, and the liquidity is sent to the caller.
+        /// The little Dot and tokens above the reserves ratio after rounding are sent back.
+        ///
+        /// Return The amount of liquidity minted.
+        ///
+        /// #Params
+        ///
+        /// - `exchange`: The Exchange.
+        /// - `token`: The Exchange token.
+        /// - `token_amount`: Amount of tokens deposited, pulled with `transfer_from`. 0 to deposit the transferred Dot.
+        /// - `min_liquidity`: Minimum liquidity minted.
+        /// - `deadline`: Time after which this transaction can no longer be executed.
+        #[ink(message, payable)]
+        pub fn zap_in(&mut self, exchange : AccountId, token : AccountId, token_amount : Balance, min_liquidity : Balance,
+            deadline : Timestamp) -> Result<Balance>{
+            let dot_amount = self.env().transferred_balance();
+            assert!((dot_amount > 0) != (token_amount > 0));
+            assert!(deadline >= self.env().block_timestamp() && min_liquidity > 0);
+            let caller = self.env().caller();
+            let (dot_reserve, token_reserve) = self.reserves(exchange);
+            //The fee of an Exchange with the dynamic fee changes with each swap.
+            let fee_numerator = FEE_DENOMINATOR - self.fee_bps(exchange) as u128;
+
+            let liquidity = if dot_amount > 0 {
+                let swap_amount = Self::optimal_swap_amount(dot_amount, dot_reserve, fee_numerator, FEE_DENOMINATOR);
+                assert!(swap_amount > 0);
+                //The Exchange refunds the Dot if it fails.
+                let tokens_bought = match self.exchange_dot_to_token(exchange, swap_amount, deadline) {
+                    Ok(tokens_bought) => tokens_bought,
+                    Err(error) => {
+                        self.env().transfer(caller, dot_amount).expect("transfer error");
+                        return Err(error)
+                    }
+                };
+                self.deposit(exchange, token, dot_amount - swap_amount, tokens_bought, min_liquidity, deadline, caller)?
+            }else{
+                self.token_transfer_from(token, caller, self.zap_account_id, token_amount)?;
+                let swap_amount = Self::optimal_swap_amount(token_amount, token_reserve, fee_numerator, FEE_DENOMINATOR);
+                assert!(swap_amount > 0);
+                let dot_bought = match self.exchange_token_to_dot(exchange, token, swap_amount, deadline) {
+                    Ok(dot_bought) => dot_bought,
+                    Err(error) => {
+                        self.token_transfer(token, caller, token_amount).expect("token transfer failed");
+                        return Err(error)
+                    }
+                };
+                self.deposit(exchange, token, dot_bought, token_amount - swap_amount, min_liquidity, deadline, caller)?
+            };
+
+            self.env().emit_event( ZapIn {
+                provider : caller,
+                exchange,
+                asset : if dot_amount > 0 { ZapAsset::Dot } else { ZapAsset::Token },
+                amount_in : dot_amount + token_amount,
+                liquidity,
+            });
+            Ok(liquidity)
+        }
+
+        /// Remove liquidity from an Exchange and swap one side, so the caller receives a single asset. The liquidity is
+        /// pulled with the Exchange `transfer_from`, so the caller approves the Zap on the Exchange first.
+        ///
+        /// Return The amount of `asset` received.
+        ///
+        /// #Params
+        ///
+        /// - `exchange`: The Exchange.
+        /// - `token`: The Exchange token.
+        /// - `liquidity`: Amount of liquidity burned.
+        /// - `asset`: The asset received.
+        /// - `min_amount_out`: Minimum amount of `asset` received.
+        /// - `deadline`: Time after which this transaction can no longer be executed.
+        #[ink(message)]
+        pub fn zap_out(&mut self, exchange : AccountId, token : AccountId, liquidity : Balance, asset : ZapAsset,
+            min_amount_out : Balance, deadline : Timestamp) -> Result<Balance>{
+            assert!(liquidity > 0 && min_amount_out > 0 && deadline >= self.env().block_timestamp());
+            let caller = self.env().caller();
+            if !self.liquidity_transfer_from(exchange, caller, liquidity) {
+                return Err(Error::TokenTransferFailed)
+            }
+            let (dot_amount, token_amount) = match self.remove_liquidity(exchange, liquidity, deadline) {
+                Ok(amounts) => amounts,
+                Err(error) => {
+                    assert!(self.liquidity_transfer(exchange, caller, liquidity));
+                    return Err(error)
+                }
+            };
+
+            //The removal and the swap are reverted together if the output is too small.
+            let amount_out = match asset {
+                ZapAsset::Dot => {
+                    let dot_bought = self.exchange_token_to_dot(exchange, token, token_amount, deadline).expect("token transfer failed");
+                    assert!(dot_amount + dot_bought >= min_amount_out);
+                    self.env().transfer(caller, dot_amount + dot_bought).expect("transfer error");
+                    dot_amount + dot_bought
+                }
+                ZapAsset::Token => {
+                    let tokens_bought = self.exchange_dot_to_token(exchange, dot_amount, deadline).expect("token transfer failed");
+                    assert!(token_amount + tokens_bought >= min_amount_out);
+                    self.token_transfer(token, caller, token_amount + tokens_bought).expect("token transfer failed");
+                    token_amount + tokens_bought
+                }
+            };
+
+            self.env().emit_event( ZapOut {
+                provider : caller,
+                exchange,
+                asset,
+                liquidity,
+                amount_out,
+            });
+            Ok(amount_out)
+        }
+
+        /// Return the amount of a single asset deposit to swap, so the rest matches the reserves ratio after the swap.
+        ///
+        /// Solves `s * (1 + g) * r + g * s^2 = g * a * r` for the swap amount `s`, where `a` is the deposit, `r` the
+        /// reserve of the deposited asset and `g = fee_numerator / fee_denominator` the share of the input priced.
+        ///
+        /// #Params
+        ///
+        /// - `amount_in`: Amount deposited.
+        /// - `reserve_in`: Reserve of the deposited asset before the swap.
+        /// - `fee_numerator`, `fee_denominator`: The share of the input priced after the Exchange fee, 9970 and 10000 for 0.3%.
+        pub fn optimal_swap_amount(amount_in : Balance, reserve_in : Balance, fee_numerator : u128, fee_denominator : u128) -> Balance{
+            assert!(reserve_in > 0 && fee_numerator > 0 && fee_numerator <= fee_denominator);
+            let reserve_in = U256::from(reserve_in);
+            let sum = U256::from(fee_denominator + fee_numerator);
+            let discriminant = reserve_in * reserve_in * sum * sum
+                + U256::from(4u8) * U256::from(fee_numerator) * U256::from(fee_denominator) * U256::from(amount_in) * reserve_in;
+            ((Self::sqrt(discriminant) - reserve_in * sum) / (U256::from(2u8) * U256::from(fee_numerator))).as_u128()
+        }
+
+        /// Return the integer square root, rounded down.
+        fn sqrt(value : U256) -> U256{
+            if value.is_zero() {
+                return value
+            }
+            //Newton's method from a value above the root decreases to it.
+            let mut root = value;
+            let mut next = (value >> 1) + U256::one();
+            while next < root {
+                root = next;
+                next = (value / next + next) >> 1;
+            }
+            root
+        }
+
+        /// Deposit `dot_amount` and `token_amount` held by the Zap at the current ratio, send the liquidity and what is
+        /// above the ratio to `provider`.
+        fn deposit(&mut self, exchange : AccountId, token : AccountId, dot_amount : Balance, token_amount : Balance,
+            min_liquidity : Balance, deadline : Timestamp, provider : AccountId) -> Result<Balance>{
+            let (dot_reserve, token_reserve) = self.reserves(exchange);
+            //The Exchange takes one token above the ratio, so the Dot deposited is limited by the tokens left.
+            let dot_deposited = core::cmp::min(dot_amount,
+                (U256::from(token_amount.saturating_sub(1)) * U256::from(dot_reserve) / U256::from(token_reserve)).as_u128());
+            assert!(dot_deposited > 0);
+            let tokens_deposited = (U256::from(dot_deposited) * U256::from(token_reserve) / U256::from(dot_reserve)).as_u128() + 1;
+
+            let liquidity_before = self.liquidity_of(exchange);
+            if let Err(error) = self.exchange_add_liquidity(exchange, token, dot_deposited, token_amount, min_liquidity, deadline) {
+                self.env().transfer(provider, dot_amount).expect("transfer error");
+                self.token_transfer(token, provider, token_amount).expect("token transfer failed");
+                return Err(error)
+            }
+            let liquidity = self.liquidity_of(exchange) - liquidity_before;
+            assert!(liquidity >= min_liquidity);
+
+            assert!(self.liquidity_transfer(exchange, provider, liquidity));
+            //The Exchange sent the tokens above the ratio back to the Zap.
+            if token_amount > tokens_deposited {
+                self.token_transfer(token, provider, token_amount - tokens_deposited).expect("token transfer failed");
+            }
+            if dot_amount > dot_deposited {
+                self.env().transfer(provider, dot_amount - dot_deposited).expect("transfer error");
+            }
+            Ok(liquidity)
+        }
+
+        fn reserves(&mut self, exchange : AccountId) -> (Balance, Balance){
+            //reserves selector in exchange 0x84393fdc
+            let selector_reserves = call::Selector::new([0x84, 0x39, 0x3f, 0xdc]);
+            build_call::<DefaultEnvironment>()
+                .callee(exchange)
+                .gas_limit(self.gas_limit / 2)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(selector_reserves.into()),
+                ).returns::<ReturnType<(Balance, Balance)>>().fire().unwrap()
+        }
+
+        fn fee_bps(&mut self, exchange : AccountId) -> u32{
+            //fee_bps selector in exchange 0xd8e36575
+            let selector_fee_bps = call::Selector::new([0xd8, 0xe3, 0x65, 0x75]);
+            build_call::<DefaultEnvironment>()
+                .callee(exchange)
+                .gas_limit(self.gas_limit / 2)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(selector_fee_bps.into()),
+                ).returns::<ReturnType<u32>>().fire().unwrap()
+        }
+
+        fn exchange_dot_to_token(&mut self, exchange : AccountId, dot_sold : Balance, deadline : Timestamp) -> Result<Balance>{
+            //dot_to_token_transfer_input selector in exchange 0xa0a8e619
+            let selector_dot_to_token_transfer_input = call::Selector::new([0xa0, 0xa8, 0xe6, 0x19]);
+            build_call::<DefaultEnvironment>()
+                .callee(exchange)
+                .gas_limit(self.gas_limit)
+                .transferred_value(dot_sold)
+                .exec_input(
+                    ExecutionInput::new(selector_dot_to_token_transfer_input.into())
+                    .push_arg(1u128)
+                    .push_arg(deadline)
+                    .push_arg(self.zap_account_id),
+                ).returns::<ReturnType<Result<Balance>>>().fire().unwrap()
+        }
+
+        fn exchange_token_to_dot(&mut self, exchange : AccountId, token : AccountId, tokens_sold : Balance, deadline : Timestamp) -> Result<Balance>{
+            let balance_before = self.env().balance();
+            let data = scale::Encode::encode(&TokenReceivedAction::TokenToDot {
+                min_dot : 1,
+                deadline,
+                recipient : self.zap_account_id,
+            });
+            if !self.token_transfer_and_call(token, exchange, tokens_sold, 0, data) {
+                return Err(Error::TokenTransferFailed)
+            }
+            Ok(self.env().balance() - balance_before)
+        }
+
+        fn exchange_add_liquidity(&mut self, exchange : AccountId, token : AccountId, dot_amount : Balance, token_amount : Balance,
+            min_liquidity : Balance, deadline : Timestamp) -> Result<()>{
+            let data = scale::Encode::encode(&TokenReceivedAction::AddLiquidity {
+                min_liquidity,
+                deadline,
+            });
+            //The token refunds the Dot if the transfer fails.
+            if !self.token_transfer_and_call(token, exchange, token_amount, dot_amount, data) {
+                return Err(Error::TokenTransferFailed)
+            }
+            Ok(())
+        }
+
+        fn remove_liquidity(&mut self, exchange : AccountId, liquidity : Balance, deadline : Timestamp) -> Result<(Balance, Balance)>{
+            //remove_liquidity selector in exchange 0x78025f59
+            let selector_remove_liquidity = call::Selector::new([0x78, 0x02, 0x5f, 0x59]);
+            build_call::<DefaultEnvironment>()
+                .callee(exchange)
+                .gas_limit(self.gas_limit)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(selector_remove_liquidity.into())
+                    .push_arg(liquidity)
+                    .push_arg(1u128)
+                    .push_arg(1u128)
+                    .push_arg(deadline),
+                ).returns::<ReturnType<Result<(Balance, Balance)>>>().fire().unwrap()
+        }
+
+        fn liquidity_of(&mut self, exchange : AccountId) -> Balance{
+            common::token::lp_balance_of(exchange, self.zap_account_id, self.gas_limit / 2)
+        }
+
+        fn liquidity_transfer(&mut self, exchange : AccountId, to : AccountId, value : Balance) -> bool{
+            common::token::transfer(exchange, to, value, self.gas_limit / 2).is_ok()
+        }
+
+        fn liquidity_transfer_from(&mut self, exchange : AccountId, from : AccountId, value : Balance) -> bool{
+            common::token::transfer_from(exchange, from, self.zap_account_id, value, self.gas_limit / 2).is_ok()
+        }
+
+        fn token_transfer_and_call(&mut self, token : AccountId, to : AccountId, value : Balance, dot_value : Balance,
+            data : Vec<u8>) -> bool{
+            //transfer_and_call selector in erc20 0x0e60abc1, the Dot is forwarded to `on_token_received`
+            let selector_transfer_and_call = call::Selector::new([0x0e, 0x60, 0xab, 0xc1]);
+            build_call::<DefaultEnvironment>()
+                .callee(token)
+                .gas_limit(self.gas_limit)
+                .transferred_value(dot_value)
+                .exec_input(
+                    ExecutionInput::new(selector_transfer_and_call.into())
+                    .push_arg(&to)
+                    .push_arg(value)
+                    .push_arg(data),
+                ).returns::<ReturnType<bool>>().fire().unwrap()
+        }
+
+        fn token_transfer(&mut self, token : AccountId, to : AccountId, value : Balance)->Result<()>{
+            common::token::transfer(token, to, value, self.gas_limit / 2)
+        }
+
+        fn token_transfer_from(&mut self, token : AccountId, from : AccountId, to : AccountId, value : Balance) ->Result<()>{
+            common::token::transfer_from(token, from, to, value, self.gas_limit / 2)
+        }
+
+        ///Return the Zap self account id.
+        #[ink(message)]
+        pub fn get_address(&self) ->AccountId{
+            self.zap_account_id
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::zap::FEE_DENOMINATOR;
+    use ink_lang as ink;
+
+    #[ink::test]
+    fn test_optimal_swap_amount_should_match_reserves_ratio(){
+        let (dot_amount, dot_reserve, token_reserve) = (10000u128, 1000000u128, 2000000u128);
+        let swap_amount = Zap::optimal_swap_amount(dot_amount, dot_reserve, 9970, FEE_DENOMINATOR);
+        assert_eq!(swap_amount, 4995);
+
+        //After the swap the Dot left and the tokens bought have the ratio of the reserves.
+        let tokens_bought = swap_amount * 997 * token_reserve / (dot_reserve * 1000 + swap_amount * 997);
+        assert_eq!(tokens_bought, 9910);
+        //The Exchange takes one token above the ratio, so one Dot is sent back after rounding.
+        let (dot_reserve, token_reserve) = (dot_reserve + swap_amount, token_reserve - tokens_bought);
+        let dot_deposited = core::cmp::min(dot_amount - swap_amount, (tokens_bought - 1) * dot_reserve / token_reserve);
+        assert_eq!(dot_deposited, 5004);
+        assert_eq!(dot_deposited * token_reserve / dot_reserve + 1, 9909);
+    }
+
+    #[ink::test]
+    fn test_optimal_swap_amount_should_not_overflow(){
+        assert_eq!(Zap::optimal_swap_amount(1_000_000_000_000_000_000, 1_000_000_000_000_000_000_000, 9970, FEE_DENOMINATOR),
+            500626001745396739);
+        assert_eq!(Zap::optimal_swap_amount(500_000_000_000_000_000_000, 100_000_000_000_000_000_000, 9970, FEE_DENOMINATOR),
+            145166821296039977046);
+        //Without a fee half of the deposit is swapped when it is small.
+        assert_eq!(Zap::optimal_swap_amount(1000, 1_000_000_000, 1, 1), 499);
+    }
+}